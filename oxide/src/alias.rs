@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::io;
+use std::io::Write;
+use std::sync::Mutex;
+
+use crate::config::config_file_path;
+use crate::lexer::{is_control_operator, quote, tokenize, unquote};
+
+const ALIAS_FILE: &str = "aliases";
+
+lazy_static! {
+    pub static ref ALIASES: Mutex<HashMap<String, String>> = {
+        Mutex::new(HashMap::new())
+    };
+}
+
+// Tokens after which the next word is in command position again. Case
+// patterns follow ';;', so it is left out.
+fn starts_command(token: &str) -> bool
+{
+    match token {
+        "|" | "!" | "{" | "if" | "then" | "else" | "elif" | "do" | "while" | "until" => true,
+        ";;" => false,
+        _ => is_control_operator(token),
+    }
+}
+
+pub fn is_valid_alias_name(name: &str) -> bool
+{
+    !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || "/=$`\\'\"|<>&;()".contains(c))
+}
+
+// Expands aliases in command position. An alias whose value ends in a space
+// also causes the word following it to be checked for alias expansion.
+pub fn expand_aliases(tokens: Vec<String>) -> Vec<String>
+{
+    let aliases = ALIASES.lock().unwrap();
+    let mut expanded_tokens = Vec::new();
+    expand_tokens(&aliases, tokens, &mut Vec::new(), &mut expanded_tokens);
+
+    return expanded_tokens
+}
+
+// Returns whether the word after the last token should be checked for an alias
+fn expand_tokens(
+    aliases: &HashMap<String, String>,
+    tokens: Vec<String>,
+    expanding: &mut Vec<String>,
+    expanded_tokens: &mut Vec<String>
+) -> bool
+{
+    let mut command_position = true;
+    for token in tokens
+    {
+        if command_position {
+            command_position = expand_word(aliases, token, expanding, expanded_tokens);
+        } else {
+            command_position = starts_command(&token);
+            expanded_tokens.push(token);
+        }
+    }
+
+    return command_position
+}
+
+// Returns whether the word after this one should also be checked for an alias.
+// `expanding` holds the aliases currently being expanded; an alias is never
+// expanded inside its own expansion, which allows `alias ls='ls -F'` and stops
+// loops such as `alias a=b` and `alias b=a`.
+fn expand_word(
+    aliases: &HashMap<String, String>,
+    word: String,
+    expanding: &mut Vec<String>,
+    expanded_tokens: &mut Vec<String>
+) -> bool
+{
+    let value = match aliases.get(&word) {
+        Some(value) if !expanding.contains(&word) => value,
+        _ => {
+            let next_is_command = starts_command(&word);
+            expanded_tokens.push(word);
+            return next_is_command
+        }
+    };
+
+    let value_tokens = match tokenize(value) {
+        Ok(value_tokens) => value_tokens,
+        Err(_) => value.split_whitespace().map(String::from).collect(),
+    };
+
+    expanding.push(word);
+    let check_next = expand_tokens(aliases, value_tokens, expanding, expanded_tokens);
+    expanding.pop();
+
+    return check_next || value.ends_with(' ')
+}
+
+// Defines an alias from a `name=value` argument
+pub fn define_alias(definition: &str) -> Result<(), String>
+{
+    let (name, value) = match definition.find('=') {
+        Some(index) => (&definition[..index], &definition[index + 1..]),
+        None => return Err(format!("{}: expected name=value", definition)),
+    };
+
+    if !is_valid_alias_name(name) {
+        return Err(format!("`{}': invalid alias name", name));
+    }
+
+    ALIASES.lock().unwrap().insert(name.to_string(), value.to_string());
+    Ok(())
+}
+
+pub fn format_alias(name: &str, value: &str) -> String
+{
+    format!("alias {}={}", name, quote(value))
+}
+
+// Loads aliases saved in the config folder
+pub fn load_aliases()
+{
+    let alias_path = match config_file_path(ALIAS_FILE) {
+        Some(path) => path,
+        None => return,
+    };
+
+    if !alias_path.is_file() {
+        return;
+    }
+
+    match read_to_string(&alias_path) {
+        Ok(contents) => {
+            ALIASES.lock().unwrap().clear();
+            for line in contents.lines()
+            {
+                match tokenize(line).as_ref().map(|tokens| tokens.as_slice()) {
+                    Ok([command, definition]) if command == "alias" => {
+                        if let Err(err) = define_alias(&unquote(definition)) {
+                            eprintln!("Skipping saved alias: {}", err);
+                        }
+                    }
+                    Ok([]) => (),
+                    _ => eprintln!("Skipping malformed line in {}: {}", alias_path.display(), line),
+                }
+            }
+        }
+        Err(err) => eprintln!("Could not read aliases from {}: {}", alias_path.display(), err),
+    }
+}
+
+pub fn save_aliases() -> io::Result<()>
+{
+    let alias_path = match config_file_path(ALIAS_FILE) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let aliases = ALIASES.lock().unwrap();
+    let mut names: Vec<&String> = aliases.keys().collect();
+    names.sort();
+
+    let mut alias_file = File::create(alias_path)?;
+    for name in names
+    {
+        writeln!(alias_file, "{}", format_alias(name, &aliases[name]))?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(aliases: &HashMap<String, String>, input: &str) -> Vec<String>
+    {
+        let mut expanded_tokens = Vec::new();
+        expand_tokens(aliases, tokenize(input).unwrap(), &mut Vec::new(), &mut expanded_tokens);
+        expanded_tokens
+    }
+
+    #[test]
+    fn test_expand_aliases()
+    {
+        let mut aliases = HashMap::new();
+        aliases.insert("removef".to_string(), "rm -r".to_string());
+        aliases.insert("ls".to_string(), "ls -F".to_string());
+        aliases.insert("ll".to_string(), "ls -l".to_string());
+        aliases.insert("sudo".to_string(), "sudo ".to_string());
+        aliases.insert("a".to_string(), "b".to_string());
+        aliases.insert("b".to_string(), "a x".to_string());
+
+        assert_eq!(expand(&aliases, "removef dir"), vec!("rm", "-r", "dir"));
+        assert_eq!(expand(&aliases, "ll ll | ll"), vec!("ls", "-F", "-l", "ll", "|", "ls", "-F", "-l"));
+        assert_eq!(expand(&aliases, "sudo ll"), vec!("sudo", "ls", "-F", "-l"));
+        assert_eq!(expand(&aliases, "a"), vec!("a", "x"));
+        assert_eq!(expand(&aliases, "ll '|' ll"), vec!("ls", "-F", "-l", "'|'", "ll"));
+        assert_eq!(expand(&aliases, "'ll' x"), vec!("'ll'", "x"));
+        assert_eq!(expand(&aliases, "true && ll; if ll; then ll; fi"),
+            vec!("true", "&&", "ls", "-F", "-l", ";", "if", "ls", "-F", "-l", ";", "then", "ls", "-F", "-l", ";", "fi"));
+    }
+}
@@ -0,0 +1,283 @@
+// Arithmetic expansion, $((...)): signed 64 bit integers with the operators
+// of C that POSIX asks for, including assignments to shell variables
+use crate::options::nounset;
+use crate::variables::{get_variable, set_variable};
+
+type Result<T> = std::result::Result<T, String>;
+
+// Longest first, so `<<=` isn't read as `<` `<=`
+const OPERATORS: [&str; 35] = [
+    "<<=", ">>=", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "=", "?", ":", "(", ")",
+];
+
+// Binary operators from the loosest binding to the tightest
+const PRECEDENCE: [&[&str]; 10] = [
+    &["||"], &["&&"], &["|"], &["^"], &["&"], &["==", "!="], &["<", "<=", ">", ">="], &["<<", ">>"], &["+", "-"],
+    &["*", "/", "%"],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+}
+
+// Evaluates an expression that has already had its parameters expanded. An
+// empty expression is 0.
+pub fn evaluate(expression: &str) -> Result<i64>
+{
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        position: 0,
+        expression,
+    };
+    if parser.tokens.is_empty() {
+        return Ok(0)
+    }
+    let value = parser.assignment(true)?;
+    if parser.position < parser.tokens.len() {
+        return Err(parser.syntax_error())
+    }
+    Ok(value)
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>>
+{
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while !rest.is_empty()
+    {
+        let length = if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            let word = &rest[..length];
+            if word.starts_with(|c: char| c.is_ascii_digit()) {
+                tokens.push(Token::Number(parse_number(word)?));
+            } else {
+                tokens.push(Token::Name(word.to_string()));
+            }
+            length
+        } else {
+            match OPERATORS.iter().find(|operator| rest.starts_with(*operator)) {
+                Some(operator) => {
+                    tokens.push(Token::Operator(operator));
+                    operator.len()
+                }
+                None => return Err(format!("{}: syntax error: invalid character '{}'", expression, rest.chars().next().unwrap())),
+            }
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+// Decimal, octal with a leading 0 or hexadecimal with a leading 0x
+fn parse_number(word: &str) -> Result<i64>
+{
+    let (digits, radix) = if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        (hex, 16)
+    } else if word.len() > 1 && word.starts_with('0') {
+        (&word[1..], 8)
+    } else {
+        (word, 10)
+    };
+    i64::from_str_radix(digits, radix).map_err(|_| format!("{}: invalid number", word))
+}
+
+// The value of a variable named in an expression. Unset and empty variables
+// are 0, unless set -u makes unset ones an error.
+fn variable_value(name: &str) -> Result<i64>
+{
+    let value = match get_variable(name) {
+        Some(value) => value,
+        None if nounset() => return Err(format!("{}: unbound variable", name)),
+        None => String::new(),
+    };
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0)
+    }
+    match value.strip_prefix('-') {
+        Some(magnitude) => parse_number(magnitude).map(i64::wrapping_neg),
+        None => parse_number(value.strip_prefix('+').unwrap_or(value)),
+    }
+    .map_err(|_| format!("{}: {}: not a number", name, value))
+}
+
+// Reads and evaluates an expression. Parts that are skipped, such as the
+// right of `0 && ...`, are read with `evaluate` false, so they neither
+// assign nor fail on a division by zero.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    expression: &'a str,
+}
+
+impl Parser<'_> {
+    fn syntax_error(&self) -> String {
+        format!("{}: syntax error in expression", self.expression)
+    }
+
+    fn next_operator(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(operator)) => Some(*operator),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<()> {
+        if self.next_operator() != Some(operator) {
+            return Err(self.syntax_error())
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn assignment(&mut self, evaluate: bool) -> Result<i64> {
+        let name = match (self.tokens.get(self.position), self.tokens.get(self.position + 1)) {
+            (Some(Token::Name(name)), Some(Token::Operator(operator))) if operator.ends_with('=')
+                && !matches!(*operator, "==" | "!=" | "<=" | ">=") => name.clone(),
+            _ => return self.conditional(evaluate),
+        };
+        let operator = match &self.tokens[self.position + 1] {
+            Token::Operator(operator) => *operator,
+            _ => unreachable!(),
+        };
+        self.position += 2;
+        let value = self.assignment(evaluate)?;
+        if !evaluate {
+            return Ok(0)
+        }
+
+        let value = match operator.strip_suffix('=').unwrap() {
+            "" => value,
+            binary => self.apply(binary, variable_value(&name)?, value, true)?,
+        };
+        set_variable(&name, &value.to_string())?;
+        Ok(value)
+    }
+
+    fn conditional(&mut self, evaluate: bool) -> Result<i64> {
+        let condition = self.binary(0, evaluate)?;
+        if self.next_operator() != Some("?") {
+            return Ok(condition)
+        }
+        self.position += 1;
+        let if_true = self.assignment(evaluate && condition != 0)?;
+        self.expect(":")?;
+        let if_false = self.conditional(evaluate && condition == 0)?;
+        Ok(if condition != 0 { if_true } else { if_false })
+    }
+
+    fn binary(&mut self, level: usize, evaluate: bool) -> Result<i64> {
+        if level == PRECEDENCE.len() {
+            return self.unary(evaluate)
+        }
+        let mut left = self.binary(level + 1, evaluate)?;
+        while let Some(operator) = self.next_operator().filter(|operator| PRECEDENCE[level].contains(operator))
+        {
+            self.position += 1;
+            let evaluate_right = match operator {
+                "&&" => evaluate && left != 0,
+                "||" => evaluate && left == 0,
+                _ => evaluate,
+            };
+            let right = self.binary(level + 1, evaluate_right)?;
+            left = self.apply(operator, left, right, evaluate)?;
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self, evaluate: bool) -> Result<i64> {
+        let operator = match self.tokens.get(self.position) {
+            Some(Token::Operator(operator)) => *operator,
+            Some(Token::Number(number)) => {
+                self.position += 1;
+                return Ok(*number)
+            }
+            Some(Token::Name(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                return if evaluate { variable_value(&name) } else { Ok(0) }
+            }
+            None => return Err(self.syntax_error()),
+        };
+        self.position += 1;
+        match operator {
+            "(" => {
+                let value = self.assignment(evaluate)?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "+" => self.unary(evaluate),
+            "-" => Ok(self.unary(evaluate)?.wrapping_neg()),
+            "!" => Ok((self.unary(evaluate)? == 0) as i64),
+            "~" => Ok(!self.unary(evaluate)?),
+            _ => Err(self.syntax_error()),
+        }
+    }
+
+    fn apply(&self, operator: &str, left: i64, right: i64, evaluate: bool) -> Result<i64> {
+        let value = match operator {
+            "||" => (left != 0 || right != 0) as i64,
+            "&&" => (left != 0 && right != 0) as i64,
+            "|" => left | right,
+            "^" => left ^ right,
+            "&" => left & right,
+            "==" => (left == right) as i64,
+            "!=" => (left != right) as i64,
+            "<" => (left < right) as i64,
+            "<=" => (left <= right) as i64,
+            ">" => (left > right) as i64,
+            ">=" => (left >= right) as i64,
+            "<<" => left.wrapping_shl(right as u32),
+            ">>" => left.wrapping_shr(right as u32),
+            "+" => left.wrapping_add(right),
+            "-" => left.wrapping_sub(right),
+            "*" => left.wrapping_mul(right),
+            "/" | "%" if right == 0 => {
+                if evaluate {
+                    return Err(format!("{}: division by zero", self.expression))
+                }
+                0
+            }
+            "/" => left.wrapping_div(right),
+            "%" => left.wrapping_rem(right),
+            _ => return Err(self.syntax_error()),
+        };
+        Ok(value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate()
+    {
+        assert_eq!(evaluate("1 + 2 * 3"), Ok(7));
+        assert_eq!(evaluate("(1 + 2) * 3 - -4"), Ok(13));
+        assert_eq!(evaluate("7 / 2 + 7 % 2 + (1 << 4) + 0x10 + 010"), Ok(44));
+        assert_eq!(evaluate("1 < 2 && 2 <= 1 || !0"), Ok(1));
+        assert_eq!(evaluate("0 ? 1 : 2 ? 3 : 4"), Ok(3));
+        assert_eq!(evaluate(""), Ok(0));
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("1 +").is_err());
+        assert!(evaluate("1 2").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_variables()
+    {
+        set_variable("OXIDE_TEST_ARITHMETIC", "5").unwrap();
+        assert_eq!(evaluate("OXIDE_TEST_ARITHMETIC * 2"), Ok(10));
+        assert_eq!(evaluate("OXIDE_TEST_ARITHMETIC += 3"), Ok(8));
+        assert_eq!(get_variable("OXIDE_TEST_ARITHMETIC").as_deref(), Some("8"));
+        // The side of && that isn't needed is skipped
+        assert_eq!(evaluate("0 && (OXIDE_TEST_ARITHMETIC = 1 / 0)"), Ok(0));
+        assert_eq!(evaluate("OXIDE_TEST_UNSET_ARITHMETIC + 1"), Ok(1));
+    }
+}
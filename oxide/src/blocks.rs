@@ -0,0 +1,103 @@
+use std::sync::Mutex;
+
+// Oldest blocks are dropped past this many
+const MAX_BLOCKS: usize = 100;
+
+// The recorded output of one foreground command
+#[derive(Clone)]
+pub struct Block {
+    pub id: usize,
+    pub command: String,
+    pub status: i32,
+    // As the command wrote it, colours and all
+    pub output: Vec<u8>,
+}
+
+impl Block {
+    pub fn line_count(&self) -> usize {
+        let lines = self.output.iter().filter(|byte| **byte == b'\n').count();
+        if self.output.last().is_some_and(|byte| *byte != b'\n') { lines + 1 } else { lines }
+    }
+}
+
+lazy_static! {
+    static ref BLOCKS: Mutex<Vec<Block>> = Mutex::new(Vec::new());
+}
+
+pub fn add_block(command: &str, status: i32, output: Vec<u8>)
+{
+    let mut blocks = BLOCKS.lock().unwrap();
+    let id = blocks.last().map_or(1, |block| block.id + 1);
+    blocks.push(Block { id, command: command.to_string(), status, output });
+    if blocks.len() > MAX_BLOCKS {
+        blocks.remove(0);
+    }
+}
+
+pub fn block_list() -> Vec<Block>
+{
+    BLOCKS.lock().unwrap().clone()
+}
+
+// The block with the given index, or the most recent one
+pub fn find_block(id: Option<usize>) -> Option<Block>
+{
+    let blocks = BLOCKS.lock().unwrap();
+    match id {
+        Some(id) => blocks.iter().find(|block| block.id == id).cloned(),
+        None => blocks.last().cloned(),
+    }
+}
+
+// Output with terminal escape sequences and carriage returns taken out, for
+// saving to a file
+pub fn plain_text(output: &[u8]) -> Vec<u8>
+{
+    let mut plain = Vec::with_capacity(output.len());
+    let mut bytes = output.iter().copied().peekable();
+    while let Some(byte) = bytes.next()
+    {
+        match byte {
+            0x1b => match bytes.next() {
+                // CSI, such as colours: parameters up to a final byte
+                Some(b'[') => {
+                    for byte in bytes.by_ref() {
+                        if (0x40..=0x7e).contains(&byte) {
+                            break
+                        }
+                    }
+                }
+                // OSC, such as window titles: up to BEL or ESC \
+                Some(b']') => {
+                    while let Some(byte) = bytes.next() {
+                        if byte == 0x07 {
+                            break
+                        }
+                        if byte == 0x1b && bytes.peek() == Some(&b'\\') {
+                            bytes.next();
+                            break
+                        }
+                    }
+                }
+                _ => (),
+            },
+            b'\r' if bytes.peek() == Some(&b'\n') => (),
+            _ => plain.push(byte),
+        }
+    }
+    plain
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text()
+    {
+        assert_eq!(plain_text(b"\x1b[1;31mred\x1b[0m text\r\n"), b"red text\n".to_vec());
+        assert_eq!(plain_text(b"\x1b]0;title\x07done"), b"done".to_vec());
+        assert_eq!(plain_text(b"plain"), b"plain".to_vec());
+    }
+}
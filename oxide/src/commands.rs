@@ -1,9 +1,41 @@
+use std::io::{Read, Write};
+
+// Write errors, such as the reader of a pipe exiting early, are ignored
+macro_rules! outln {
+    ($io:expr, $($arg:tt)*) => {{
+        let _ = ::std::io::Write::write_fmt(&mut $io.stdout, format_args!("{}\n", format_args!($($arg)*)));
+    }};
+}
+
+macro_rules! errln {
+    ($io:expr, $($arg:tt)*) => {{
+        let _ = ::std::io::Write::write_fmt(&mut $io.stderr, format_args!("{}\n", format_args!($($arg)*)));
+    }};
+}
+
+pub mod alias;
+pub mod blocks;
 pub mod change_folder;
+pub mod control_flow;
 pub mod create;
+pub mod exec;
 pub mod exit;
+pub mod history;
+#[cfg(target_family = "unix")]
+pub mod jobs;
 pub mod list;
+pub mod lookup;
+pub mod read;
 pub mod remove;
 pub mod show;
+pub mod source;
+pub mod status;
+#[cfg(target_family = "unix")]
+pub mod timeout;
+pub mod trap;
+#[cfg(target_family = "unix")]
+pub mod ulimit;
+pub mod variables;
 
 #[cfg(target_family = "windows")]
 pub mod clear_windows;
@@ -11,4 +43,57 @@ pub mod clear_windows;
 #[cfg(target_family = "unix")]
 pub mod clear;
 
+// The standard streams of a builtin. Builtins use these rather than the
+// shell's own streams so they work in pipelines and with redirections.
+pub struct BuiltinIo {
+    pub stdin: Box<dyn Read + Send>,
+    pub stdout: Box<dyn Write + Send>,
+    pub stderr: Box<dyn Write + Send>,
+    // Output meant for people, such as colours, is only written to a terminal
+    pub stdout_is_terminal: bool,
+}
+
+// Builtins take their arguments and streams and return an exit status
+pub type Builtin = fn(&[String], &mut BuiltinIo) -> i32;
+
+// Splits leading single-letter flags such as -a or -pt from the operands,
+// reporting any flag not in `allowed`. A lone "-" is an operand and "--" ends
+// the flags.
+pub fn parse_flags<'a>(name: &str, arguments: &'a [String], allowed: &str, io: &mut BuiltinIo) -> Option<(String, &'a [String])> {
+    let mut flags = String::new();
+    let mut index = 0;
+    while index < arguments.len() && arguments[index].starts_with('-') && arguments[index].len() > 1 {
+        if arguments[index] == "--" {
+            index += 1;
+            break;
+        }
+        for flag in arguments[index][1..].chars() {
+            if !allowed.contains(flag) {
+                errln!(io, "{}: -{}: invalid option", name, flag);
+                return None;
+            }
+            flags.push(flag);
+        }
+        index += 1;
+    }
+    Some((flags, &arguments[index..]))
+}
+
+// Asks a yes or no question on stderr and reads the answer from stdin. Running
+// out of input counts as no.
+pub fn confirm(question: &str, io: &mut BuiltinIo) -> bool {
+    let _ = write!(io.stderr, "{} [y/N] ", question);
+    let _ = io.stderr.flush();
 
+    // One byte at a time so nothing after the answer is consumed
+    let mut answer = Vec::new();
+    let mut byte = [0; 1];
+    while let Ok(1) = io.stdin.read(&mut byte) {
+        if byte[0] == b'\n' {
+            break;
+        }
+        answer.push(byte[0]);
+    }
+    let answer = String::from_utf8_lossy(&answer);
+    answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes")
+}
@@ -0,0 +1,69 @@
+use crate::alias::{define_alias, format_alias, save_aliases, ALIASES};
+use crate::commands::BuiltinIo;
+
+pub fn alias(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.is_empty() {
+        let aliases = ALIASES.lock().unwrap();
+        let mut names: Vec<&String> = aliases.keys().collect();
+        names.sort();
+        for name in names {
+            outln!(io, "{}", format_alias(name, &aliases[name]));
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    let mut changed = false;
+    for argument in arguments {
+        if argument.contains('=') {
+            match define_alias(argument) {
+                Ok(_) => changed = true,
+                Err(err) => {
+                    errln!(io, "alias: {}", err);
+                    status = 1;
+                }
+            }
+        } else {
+            match ALIASES.lock().unwrap().get(argument) {
+                Some(value) => {
+                    outln!(io, "{}", format_alias(argument, value));
+                }
+                None => {
+                    errln!(io, "alias: {}: not found", argument);
+                    status = 1;
+                }
+            }
+        }
+    }
+
+    if changed {
+        _save(io);
+    }
+    status
+}
+
+pub fn unalias(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.is_empty() {
+        errln!(io, "unalias: usage: unalias [-a] name [name ...]");
+        return 2;
+    }
+
+    let mut status = 0;
+    for argument in arguments {
+        if argument == "-a" {
+            ALIASES.lock().unwrap().clear();
+        } else if ALIASES.lock().unwrap().remove(argument).is_none() {
+            errln!(io, "unalias: {}: not found", argument);
+            status = 1;
+        }
+    }
+
+    _save(io);
+    status
+}
+
+fn _save(io: &mut BuiltinIo) {
+    if let Err(err) = save_aliases() {
+        errln!(io, "Could not save aliases: {}", err);
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::Write;
+
+use crate::blocks::{block_list, find_block, plain_text, Block};
+use crate::commands::BuiltinIo;
+use crate::encoding::{display, to_path};
+use crate::options;
+
+// blocks                  list recorded blocks
+// blocks show [n]         print a block's output again, the last by default
+// blocks save [n] file    save a block's output to a file as plain text
+pub fn blocks(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    match arguments.first().map(String::as_str) {
+        None | Some("list") => _list(io),
+        Some("show") => match arguments.len() {
+            1 | 2 => match _find(arguments.get(1), io) {
+                Some(block) => {
+                    let _ = io.stdout.write_all(&block.output);
+                    0
+                }
+                None => 1,
+            },
+            _ => _usage(io),
+        },
+        Some("save") => {
+            let (index, path) = match arguments.len() {
+                2 => (None, &arguments[1]),
+                3 => (Some(&arguments[1]), &arguments[2]),
+                _ => return _usage(io),
+            };
+            let block = match _find(index, io) {
+                Some(block) => block,
+                None => return 1,
+            };
+            match File::create(to_path(path)).and_then(|mut file| file.write_all(&plain_text(&block.output))) {
+                Ok(_) => 0,
+                Err(err) => {
+                    errln!(io, "blocks: {}: {}", display(path), err);
+                    1
+                }
+            }
+        }
+        Some(_) => _usage(io),
+    }
+}
+
+fn _list(io: &mut BuiltinIo) -> i32 {
+    let blocks = block_list();
+    if blocks.is_empty() && !options::blocks() {
+        errln!(io, "blocks: no blocks recorded, turn recording on with `set -o blocks`");
+        return 1;
+    }
+    for block in blocks {
+        let status = match block.status {
+            0 => String::new(),
+            status => format!(" [{}]", status),
+        };
+        let lines = match block.line_count() {
+            1 => String::from("1 line"),
+            count => format!("{} lines", count),
+        };
+        outln!(io, "{:5}  {}{}  ({})", block.id, block.command, status, lines);
+    }
+    0
+}
+
+fn _find(index: Option<&String>, io: &mut BuiltinIo) -> Option<Block> {
+    let id = match index.map(|index| index.parse::<usize>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            errln!(io, "blocks: {}: invalid block number", index.unwrap());
+            return None;
+        }
+        None => None,
+    };
+    let block = find_block(id);
+    if block.is_none() {
+        match index {
+            Some(index) => errln!(io, "blocks: {}: no such block", index),
+            None => errln!(io, "blocks: no blocks recorded"),
+        }
+    }
+    block
+}
+
+fn _usage(io: &mut BuiltinIo) -> i32 {
+    errln!(io, "blocks: usage: blocks [list | show [n] | save [n] file]");
+    2
+}
@@ -1,34 +1,37 @@
 use std::path::Path;
 
-pub fn change_folder(filepaths: Vec<&Path>) {
+use crate::commands::BuiltinIo;
+use crate::encoding::{display_os, to_path};
+
+pub fn change_folder(arguments: &[String], io: &mut BuiltinIo) -> i32 {
 
     // Copy bash's behaviour
-    if filepaths.len() == 0 {
-        return;
+    if arguments.is_empty() {
+        return 0;
     }
 
-    if filepaths.len() > 1 {
-        println!("cd: too many arguments");
-        return;
+    if arguments.len() > 1 {
+        errln!(io, "cd: too many arguments");
+        return 1;
     }
 
 
-    _change_folder(Path::new(filepaths.get(0).unwrap()))
+    _change_folder(&to_path(&arguments[0]), io)
 }
 
-fn _change_folder(filepath: &Path) {
-    let path_string = filepath.to_str().unwrap();
+fn _change_folder(filepath: &Path, io: &mut BuiltinIo) -> i32 {
+    let path_string = display_os(filepath.as_os_str());
 
     if filepath.is_dir() {
         match std::env::set_current_dir(filepath) 
         {
-            Ok(_) => (),
-            Err(err) => println!("Failed to change folder with error: {}", err), 
+            Ok(_) => return 0,
+            Err(err) => errln!(io, "Failed to change folder with error: {}", err), 
         }
     } else if filepath.is_file() {
-        println!("\"{}\" is a file not a directory", path_string);
+        errln!(io, "\"{}\" is a file not a directory", path_string);
     } else {
-        println!("\"{}\" no such file or directory", path_string);
+        errln!(io, "\"{}\" no such file or directory", path_string);
     }
+    1
 }
-
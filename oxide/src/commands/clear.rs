@@ -1,9 +1,20 @@
-use std::path::Path;
+use std::io::Write;
 
-pub fn clear(_filepaths: Vec<&Path>) {
-    _clear();
+use crate::commands::BuiltinIo;
+
+pub fn clear(_arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    _clear(io)
 }
 
-fn _clear() {
-    std::process::Command::new("clear").status().unwrap();
-}
\ No newline at end of file
+fn _clear(io: &mut BuiltinIo) -> i32 {
+    match std::process::Command::new("clear").output() {
+        Ok(output) => {
+            let _ = io.stdout.write_all(&output.stdout);
+            output.status.code().unwrap_or(1)
+        }
+        Err(err) => {
+            errln!(io, "clear: {}", err);
+            1
+        }
+    }
+}
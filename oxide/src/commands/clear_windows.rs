@@ -1,8 +1,6 @@
 extern crate kernel32;
 extern crate winapi;
 
-use std::path::Path;
-
 use winapi::DWORD;
 use winapi::HANDLE;
 use winapi::wincon::CONSOLE_SCREEN_BUFFER_INFO;
@@ -10,10 +8,13 @@ use winapi::wincon::COORD;
 use winapi::wincon::SMALL_RECT;
 use winapi::WORD;
 
+use crate::commands::BuiltinIo;
+
 static mut CONSOLE_HANDLE: Option<HANDLE> = None;
 
-pub fn clear(filepaths: Vec<&Path>) {
+pub fn clear(_arguments: &[String], _io: &mut BuiltinIo) -> i32 {
     clear_screen_windows();
+    0
 }
 
 pub fn clear_screen_windows() {
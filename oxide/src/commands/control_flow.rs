@@ -0,0 +1,62 @@
+use crate::commands::BuiltinIo;
+use crate::logic::{request_control_flow, ControlFlow};
+use crate::status::last_status;
+
+pub fn break_loop(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    match _levels("break", arguments, io) {
+        Some(levels) => _request("break", ControlFlow::Break(levels), io),
+        None => 1,
+    }
+}
+
+pub fn continue_loop(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    match _levels("continue", arguments, io) {
+        Some(levels) => _request("continue", ControlFlow::Continue(levels), io),
+        None => 1,
+    }
+}
+
+pub fn return_function(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let status = match arguments.first() {
+        Some(argument) => match argument.parse::<i32>() {
+            Ok(status) => status,
+            Err(_) => {
+                errln!(io, "return: {}: numeric argument required", argument);
+                2
+            }
+        },
+        None => last_status(),
+    };
+    match _request("return", ControlFlow::Return(status), io) {
+        0 => status,
+        failed => failed,
+    }
+}
+
+// `:` does nothing
+pub fn null(_arguments: &[String], _io: &mut BuiltinIo) -> i32 {
+    0
+}
+
+fn _levels(name: &str, arguments: &[String], io: &mut BuiltinIo) -> Option<usize> {
+    match arguments.first() {
+        Some(argument) => match argument.parse::<usize>() {
+            Ok(levels) => Some(levels),
+            Err(_) => {
+                errln!(io, "{}: {}: numeric argument required", name, argument);
+                None
+            }
+        },
+        None => Some(1),
+    }
+}
+
+fn _request(name: &str, control_flow: ControlFlow, io: &mut BuiltinIo) -> i32 {
+    match request_control_flow(control_flow) {
+        Ok(_) => 0,
+        Err(err) => {
+            errln!(io, "{}: {}", name, err);
+            1
+        }
+    }
+}
@@ -1,78 +1,235 @@
 use std::path::Path;
-use std::fs::File;
-use std::io;
+use std::fs::{File, FileTimes, OpenOptions};
+use std::io::ErrorKind;
+use std::time::SystemTime;
 
-pub fn create(filepaths: Vec<&Path>) {
-    if filepaths.len() == 0 {
-        println!("Please provide a filename to create!");
-    }
-    else {
-        for path in filepaths {
-            if path.is_file() {
-                println!("File: {} already exists. Would you like to overwrite the file? (y/n)", path.display());
-                let mut overwrite_file_confirm = String::new();
-                match io::stdin().read_line(&mut overwrite_file_confirm) {
-                    Ok(_) => {
-                        if overwrite_file_confirm.trim() == "y"
-                        {
-                            _create(path);
-                        }
-                    }
-                    Err(err) => println!("Error while reading input: {}", err)
-                }
-            }
-            else if path.is_dir() {
-                println!("Folder: {} already exists!", path.display());
-            }
-            else {
-                _create(path);
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::commands::{confirm, parse_flags, BuiltinIo};
+use crate::encoding::{display_os, to_path};
+
+// create [-f] file...
+// Asks before overwriting an existing file, unless -f is given
+pub fn create(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (flags, operands) = match parse_flags("create", arguments, "f", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    if operands.is_empty() {
+        errln!(io, "create: Please provide a filename to create!");
+        return 2;
+    }
+
+    let mut status = 0;
+    for operand in operands {
+        let path = to_path(operand);
+        let path_string = display_os(path.as_os_str());
+        if path.is_dir() {
+            errln!(io, "create: Folder: {} already exists!", path_string);
+            status = 1;
+        } else if path.is_file() && !flags.contains('f') {
+            let question = format!("File: {} already exists. Would you like to overwrite the file?", path_string);
+            if confirm(&question, io) && _create(&path, io) != 0 {
+                status = 1;
             }
-            
+        } else if _create(&path, io) != 0 {
+            status = 1;
         }
     }
+    status
 }
 
-pub fn touch(filepaths: Vec<&Path>) {
-    if filepaths.len() == 0 {
-        println!("Please provide a filename to create!");
-    }
-    else {
-        for path in filepaths {
-            if path.exists() {
-                match File::open(path) {
-                    Ok(_) => println!("File: {} times updated.", path.display()),
-                    Err(err) => println!("Error when updating file {}", err),
+// touch [-acm] [-t STAMP | -d DATE | -r FILE] file...
+// Like touch(1) this is quiet, creating missing files, unless -c is given,
+// and setting the access and modification times of the rest to now or the
+// time given. -a or -m sets only that one.
+pub fn touch(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let mut no_create = false;
+    let (mut access, mut modification) = (false, false);
+    let mut times: Option<(SystemTime, SystemTime)> = None;
+    let mut index = 0;
+    while let Some(argument) = arguments.get(index).filter(|argument| argument.starts_with('-') && argument.len() > 1) {
+        index += 1;
+        if argument == "--" {
+            break;
+        }
+        for (position, flag) in argument[1..].char_indices() {
+            match flag {
+                'c' => no_create = true,
+                'a' => access = true,
+                'm' => modification = true,
+                't' | 'd' | 'r' => {
+                    // The value is the rest of the argument or the one after it
+                    let value = match &argument[position + 2..] {
+                        "" => {
+                            index += 1;
+                            match arguments.get(index - 1) {
+                                Some(value) => value.as_str(),
+                                None => {
+                                    errln!(io, "touch: -{}: option requires an argument", flag);
+                                    return 2;
+                                }
+                            }
+                        }
+                        rest => rest,
+                    };
+                    times = match _touch_times(flag, value) {
+                        Ok(times) => Some(times),
+                        Err(err) => {
+                            errln!(io, "touch: {}", err);
+                            return 1;
+                        }
+                    };
+                    break;
+                }
+                _ => {
+                    errln!(io, "touch: -{}: invalid option", flag);
+                    return 2;
                 }
             }
-            else {
-                _create(path);
-            }
         }
     }
+    let operands = &arguments[index..];
+    if operands.is_empty() {
+        errln!(io, "touch: Please provide a filename to create!");
+        return 2;
+    }
+
+    let (accessed, modified) = times.unwrap_or_else(|| (SystemTime::now(), SystemTime::now()));
+    let mut file_times = FileTimes::new();
+    if access || !modification {
+        file_times = file_times.set_accessed(accessed);
+    }
+    if modification || !access {
+        file_times = file_times.set_modified(modified);
+    }
+
+    let mut status = 0;
+    for operand in operands {
+        let path = to_path(operand);
+        // Folders can only be opened for reading
+        let file = match path.is_dir() {
+            true => File::open(&path),
+            false => OpenOptions::new().create(!no_create).append(true).open(&path),
+        };
+        let result = match file {
+            Err(err) if no_create && err.kind() == ErrorKind::NotFound => Ok(()),
+            file => file.and_then(|file| file.set_times(file_times)),
+        };
+        if let Err(err) = result {
+            errln!(io, "touch: {}: {}", display_os(path.as_os_str()), err);
+            status = 1;
+        }
+    }
+    status
+}
+
+// The access and modification times for -t STAMP, -d DATE or -r FILE
+fn _touch_times(flag: char, value: &str) -> Result<(SystemTime, SystemTime), String> {
+    let time = match flag {
+        't' => _parse_stamp(value).ok_or_else(|| format!("invalid date format '{}'", value))?,
+        'd' => _parse_date(value).ok_or_else(|| format!("invalid date format '{}'", value))?,
+        _ => {
+            let metadata = std::fs::metadata(to_path(value)).map_err(|err| format!("{}: {}", value, err))?;
+            let modified = metadata.modified().map_err(|err| format!("{}: {}", value, err))?;
+            return Ok((metadata.accessed().unwrap_or(modified), modified));
+        }
+    };
+    Ok((time, time))
+}
+
+// [[CC]YY]MMDDhhmm[.ss] in local time, as POSIX touch -t takes. A two digit
+// year from 69 is in the 1900s.
+fn _parse_stamp(stamp: &str) -> Option<SystemTime> {
+    let (digits, seconds) = match stamp.split_once('.') {
+        Some((digits, seconds)) if seconds.len() == 2 => (digits, seconds.parse().ok()?),
+        Some(_) => return None,
+        None => (stamp, 0),
+    };
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| digits[range].parse::<u32>().ok();
+    let (year, rest) = match digits.len() {
+        8 => (Local::now().year(), 0),
+        10 => match number(0..2)? {
+            year if year >= 69 => (1900 + year as i32, 2),
+            year => (2000 + year as i32, 2),
+        },
+        12 => (number(0..4)? as i32, 4),
+        _ => return None,
+    };
+    let date = NaiveDate::from_ymd_opt(year, number(rest..rest + 2)?, number(rest + 2..rest + 4)?)?;
+    let time = date.and_hms_opt(number(rest + 4..rest + 6)?, number(rest + 6..rest + 8)?, seconds)?;
+    Local.from_local_datetime(&time).earliest().map(SystemTime::from)
 }
 
-fn _create(filepath: &Path) {
+// The forms of GNU touch -d that scripts mostly use: now, @SECONDS, RFC 3339
+// and a local YYYY-MM-DD with an optional time
+fn _parse_date(date: &str) -> Option<SystemTime> {
+    if date == "now" {
+        return Some(SystemTime::now());
+    }
+    if let Some(seconds) = date.strip_prefix('@') {
+        return DateTime::from_timestamp(seconds.parse().ok()?, 0).map(SystemTime::from);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+        return Some(SystemTime::from(time));
+    }
+    let time = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))?;
+    Local.from_local_datetime(&time).earliest().map(SystemTime::from)
+}
+
+fn _create(filepath: &Path, io: &mut BuiltinIo) -> i32 {
+    let path_string = display_os(filepath.as_os_str());
     match File::create(filepath) {
-        Ok(_) => println!("File: {} has been created.", filepath.display()),
-        Err(err) => println!("Could not create file. Error: {}", err),
+        Ok(_) => {
+            outln!(io, "File: {} has been created.", path_string);
+            0
+        }
+        Err(err) => {
+            errln!(io, "create: Could not create file {}. Error: {}", path_string, err);
+            1
+        }
     }
 }
 
-pub fn create_folder(filepaths: Vec<&Path>) {
-    for path in filepaths {
-        _create_folder(path);
+// createf folder...
+// Creates any missing parent folders too
+pub fn create_folder(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (_, operands) = match parse_flags("createf", arguments, "", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    if operands.is_empty() {
+        errln!(io, "createf: Please provide a folder name to create!");
+        return 2;
     }
+
+    let mut status = 0;
+    for operand in operands {
+        if _create_folder(&to_path(operand), io) != 0 {
+            status = 1;
+        }
+    }
+    status
 }
 
-fn _create_folder(path: &Path) {
+fn _create_folder(path: &Path, io: &mut BuiltinIo) -> i32 {
+    let path_string = display_os(path.as_os_str());
     if path.exists() {
-        println!("{} already exists", path.display());
-        return;
+        errln!(io, "createf: {} already exists", path_string);
+        return 1;
     }
-    let result = std::fs::create_dir_all(path);
 
-    if result.is_err() {
-        println!("Failed to create folder: {}", path.display());
-        println!("{}", result.err().unwrap());
+    match std::fs::create_dir_all(path) {
+        Ok(_) => 0,
+        Err(err) => {
+            errln!(io, "createf: Failed to create folder: {}. Error: {}", path_string, err);
+            1
+        }
     }
-}
\ No newline at end of file
+}
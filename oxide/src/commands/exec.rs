@@ -0,0 +1,12 @@
+use crate::commands::BuiltinIo;
+
+// `exec name args` and `exec` with redirections are handled by the executor,
+// since they change the shell itself. This only runs when exec is part of a
+// pipeline, where there is no shell of its own to replace.
+pub fn exec(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.is_empty() {
+        return 0;
+    }
+    errln!(io, "oxide: exec: can't replace the shell from within a pipeline");
+    1
+}
@@ -1,5 +1,35 @@
-use std::path::Path;
+use std::io::Write;
 
-pub fn exit(_filepaths: Vec<&Path>) {
-    std::process::exit(0)
-}
\ No newline at end of file
+use crate::commands::BuiltinIo;
+use crate::logic::run_exit_trap;
+use crate::status::last_status;
+
+pub fn exit(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    #[cfg(target_family = "unix")]
+    {
+        use crate::jobs::{has_jobs, exit_warned, set_exit_warned, hang_up_jobs};
+        use crate::options::interactive;
+
+        // Like bash, warn once and exit if asked again straight away
+        if interactive() && has_jobs() && !exit_warned() {
+            errln!(io, "There are stopped or running jobs.");
+            set_exit_warned(true);
+            return 1;
+        }
+        hang_up_jobs();
+    }
+
+    let status = match arguments.first() {
+        Some(argument) => match argument.parse::<i32>() {
+            Ok(status) => status,
+            Err(_) => {
+                errln!(io, "exit: {}: numeric argument required", argument);
+                2
+            }
+        },
+        None => last_status(),
+    };
+    let _ = io.stdout.flush();
+    run_exit_trap(status);
+    std::process::exit(status)
+}
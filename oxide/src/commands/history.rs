@@ -0,0 +1,33 @@
+use rustyline::history::History;
+
+use crate::commands::BuiltinIo;
+use crate::config::OxideHistory;
+
+// Lists the saved history, numbered so entries can be recalled with !n
+pub fn history(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let count = match arguments.first() {
+        Some(argument) => match argument.parse::<usize>() {
+            Ok(count) => Some(count),
+            Err(_) => {
+                errln!(io, "history: {}: numeric argument required", argument);
+                return 1;
+            }
+        },
+        None => None,
+    };
+
+    let oxide_history = OxideHistory::new();
+    let mut history = History::new();
+    if oxide_history.exists && history.load(&oxide_history.path).is_err() {
+        errln!(io, "history: could not read {}", oxide_history.path.display());
+        return 1;
+    }
+
+    let start = history.len().saturating_sub(count.unwrap_or(history.len()));
+    for index in start..history.len() {
+        if let Some(entry) = history.get(index) {
+            outln!(io, "{:5}  {}", index + 1, entry);
+        }
+    }
+    0
+}
@@ -0,0 +1,107 @@
+use crate::commands::BuiltinIo;
+use crate::jobs::{
+    all_job_ids, background_job, disown_job, find_job, foreground_job, format_job, format_job_long, job_list, job_with_pid,
+    wait_for_job,
+};
+
+pub fn jobs(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let show_pids = arguments.iter().any(|argument| argument == "-l");
+    let only_pids = arguments.iter().any(|argument| argument == "-p");
+
+    for (job, marker) in job_list() {
+        if only_pids {
+            outln!(io, "{}", job.pgid);
+        } else if show_pids {
+            outln!(io, "{}", format_job_long(&job, marker));
+        } else {
+            outln!(io, "{}", format_job(&job, marker));
+        }
+    }
+    0
+}
+
+pub fn fg(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    match find_job(arguments.first().map(|spec| &spec[..])) {
+        Ok(id) => foreground_job(id),
+        Err(err) => {
+            errln!(io, "fg: {}", err);
+            1
+        }
+    }
+}
+
+pub fn bg(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let specs: Vec<Option<&str>> = if arguments.is_empty() {
+        vec!(None)
+    } else {
+        arguments.iter().map(|spec| Some(&spec[..])).collect()
+    };
+
+    let mut status = 0;
+    for spec in specs {
+        match find_job(spec).and_then(background_job) {
+            Ok(notice) => {
+                outln!(io, "{}", notice);
+            }
+            Err(err) => {
+                errln!(io, "bg: {}", err);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+// Waits for the given jobs or process ids, or for every job
+pub fn wait(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.is_empty() {
+        for id in all_job_ids() {
+            wait_for_job(id);
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for argument in arguments {
+        let id = if argument.starts_with('%') {
+            find_job(Some(argument)).ok()
+        } else {
+            argument.parse().ok().and_then(job_with_pid)
+        };
+        status = match id {
+            Some(id) => wait_for_job(id),
+            None => {
+                errln!(io, "wait: {}: no such job", argument);
+                127
+            }
+        };
+    }
+    status
+}
+
+pub fn disown(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.iter().any(|argument| argument == "-a") {
+        for id in all_job_ids() {
+            disown_job(id);
+        }
+        return 0;
+    }
+
+    let specs: Vec<Option<&str>> = if arguments.is_empty() {
+        vec!(None)
+    } else {
+        arguments.iter().map(|spec| Some(&spec[..])).collect()
+    };
+
+    let mut status = 0;
+    for spec in specs {
+        match find_job(spec) {
+            Ok(id) => disown_job(id),
+            Err(err) => {
+                errln!(io, "disown: {}", err);
+                status = 1;
+            }
+        }
+    }
+    status
+}
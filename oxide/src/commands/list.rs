@@ -1,74 +1,737 @@
-use std::path::Path;
-use std::fs::{Metadata, DirEntry, read_dir};
+use std::path::{Path, PathBuf};
+use std::fs::{Metadata, metadata, read_dir, read_link, symlink_metadata};
 use std::io;
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::time::SystemTime;
 use chrono::DateTime;
 use chrono::Local;
+use unicode_width::UnicodeWidthStr;
 
-pub fn list(filepaths: Vec<&Path>) {
-    if filepaths.len() == 0 {
-        match _list(&std::env::current_dir().unwrap()) {
-            Ok(_) => {},
-            Err(err) => println!("Error in list operation {}", err),
-        }
-        return;
+use crate::commands::BuiltinIo;
+use crate::encoding::{display, display_os, from_os_str, to_path};
+#[cfg(target_family = "unix")]
+use crate::file_info::{format_mode, group_name, user_name};
+use crate::file_info::{format_age, format_size, grid_layout, is_valid_time_format, Units, COLUMN_GAP};
+use crate::ignore::Ignores;
+use crate::ls_colors::{paint, FileKind, Theme};
+use crate::variables::get_variable;
+
+const USAGE: &str = "usage: list [-1aCliRrStUX] [-L depth] [--long|--grid|--oneline] [--sort=name|size|time|extension|none] \
+[--dirs-first] [--tree] [--gitignore] \
+[--units=si|iec|bytes] [--time-style=default|iso|long-iso|full-iso|relative|+FORMAT] [--color=auto|always|never] [path...]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    Size,
+    Time,
+    Extension,
+    Unsorted,
+}
+
+// How modification times are shown
+enum TimeStyle {
+    Default,
+    Iso,
+    FullIso,
+    // Like "3 hours ago"
+    Relative,
+    // A strftime format
+    Custom(String),
+}
+
+// How the entries of a folder are laid out
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    // A row each with the permissions, links, owner, group, size and time
+    Long,
+    // Names in columns filling the width of the terminal, like ls -C
+    Grid,
+    OneLine,
+}
+
+struct ListOptions {
+    all: bool,
+    layout: Layout,
+    #[cfg(target_family = "unix")]
+    inode: bool,
+    units: Units,
+    time_style: TimeStyle,
+    sort: SortKey,
+    reverse: bool,
+    dirs_first: bool,
+    recursive: bool,
+    tree: bool,
+    // How many levels of folders are walked, the entries of the folder
+    // listed being the first level
+    depth: Option<usize>,
+    gitignore: bool,
+    // Colours for names, when they are shown
+    colors: Option<Theme>,
+}
+
+// A folder entry and the details it is sorted by
+struct Entry {
+    path: PathBuf,
+    name: String,
+    metadata: Option<Metadata>,
+}
+
+impl Entry {
+    // Links to folders aren't followed, so walks can't loop
+    fn is_folder(&self) -> bool {
+        self.metadata.as_ref().is_some_and(|metadata| metadata.is_dir())
     }
 
-    if filepaths.len() == 1 {
-        match _list(filepaths[0]) {
-            Ok(_) => {},
-            Err(err) => println!("Error in list operation {}", err),
-        }
-        return;
+    fn size(&self) -> u64 {
+        self.metadata.as_ref().map_or(0, |metadata| metadata.len())
     }
 
-    for path in filepaths {
-        println!("{}", path.display());
-        match _list(path) {
-            Ok(_) => {},
-            Err(err) => println!("Error in list operation {}", err),
+    fn modified(&self) -> Option<SystemTime> {
+        self.metadata.as_ref().and_then(|metadata| metadata.modified().ok())
+    }
+
+    fn extension(&self) -> String {
+        Path::new(&self.name).extension().map(from_os_str).unwrap_or_default()
+    }
+}
+
+// A walk of the folders below one operand
+struct Walk<'a> {
+    root: &'a Path,
+    options: &'a ListOptions,
+    ignores: Option<Ignores>,
+}
+
+impl Walk<'_> {
+    // Whether folders found at this depth are walked into
+    fn descends(&self, depth: usize) -> bool {
+        self.options.depth.is_none_or(|max| depth < max)
+    }
+}
+
+// list [-1aCliRrStUX] [-L depth] [--long|--grid|--oneline] [--sort=key] [--dirs-first] [--tree] [--gitignore] [--units=units] [--time-style=style] [--color=when] [path...]
+pub fn list(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (options, operands) = match _parse_options(arguments, io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    let paths: Vec<PathBuf> = match operands.is_empty() {
+        true => vec!(PathBuf::from(".")),
+        false => operands.iter().map(|operand| to_path(operand)).collect(),
+    };
+
+    let mut status = 0;
+    // Folders are set apart from whatever was listed before them, which
+    // paths that failed don't count as
+    let mut printed = false;
+    for path in paths.iter() {
+        let path_string = display_os(path.as_os_str());
+        let metadata = match symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                errln!(io, "list: {}: {}", path_string, err);
+                status = 1;
+                continue;
+            }
+        };
+
+        // A file is listed on its own, like an entry of its folder
+        if !metadata.is_dir() {
+            let entry = Entry { path: path.clone(), name: from_os_str(path.as_os_str()), metadata: Some(metadata) };
+            match options.tree {
+                true => outln!(io, "{}{}", _display_name(&entry, options.colors.as_ref()), _link_target(&entry)),
+                false => _print_entries(&[entry], &options, io),
+            }
+            printed = true;
+            continue;
+        }
+
+        if printed && (paths.len() > 1 || options.recursive || options.tree) {
+            outln!(io, "");
+        }
+        printed = true;
+        let mut walk = Walk {
+            root: path,
+            options: &options,
+            ignores: if options.gitignore { Some(Ignores::new(path)) } else { None },
+        };
+        let result = if options.tree {
+            _tree(&mut walk, io)
+        } else if options.recursive {
+            _list_recursive(path, Path::new(""), 1, &mut walk, io)
+        } else {
+            // Several folders are each headed by their name
+            if paths.len() > 1 {
+                outln!(io, "{}:", path_string);
+            }
+            _list(path, &mut walk, io)
+        };
+        if result != 0 {
+            status = result;
         }
     }
+    status
 }
 
-fn _list(filepath: &Path) -> io::Result<()> {
-    if filepath.is_dir() {
-        for entry in read_dir(filepath)? {
-            let entry = entry?;
-            match entry.file_name().to_str() {
-                None => {
-                    println!("Could not convert path to utf-8 string. What funky OS are you using?");
+fn _parse_options<'a>(arguments: &'a [String], io: &mut BuiltinIo) -> Option<(ListOptions, &'a [String])> {
+    // Like GNU ls, TIME_STYLE sets the style --time-style leaves out, and a
+    // style list doesn't know is ignored rather than an error
+    let time_style = get_variable("TIME_STYLE")
+        .and_then(|style| _parse_time_style(&style))
+        .unwrap_or(TimeStyle::Default);
+    let mut options = ListOptions {
+        all: false,
+        layout: Layout::Grid,
+        #[cfg(target_family = "unix")]
+        inode: false,
+        units: Units::Si,
+        time_style,
+        sort: SortKey::Name,
+        reverse: false,
+        dirs_first: false,
+        recursive: false,
+        tree: false,
+        depth: None,
+        gitignore: false,
+        colors: None,
+    };
+    let mut layout = None;
+    let mut color = "auto";
+
+    let mut index = 0;
+    while index < arguments.len() {
+        let argument = &arguments[index];
+        if argument == "--" {
+            index += 1;
+            break;
+        }
+
+        if let Some(long) = argument.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            match (name, value) {
+                ("all", None) => options.all = true,
+                ("long", None) => layout = Some(Layout::Long),
+                ("grid", None) => layout = Some(Layout::Grid),
+                ("oneline", None) => layout = Some(Layout::OneLine),
+                ("reverse", None) => options.reverse = true,
+                ("recursive", None) => options.recursive = true,
+                ("tree", None) => options.tree = true,
+                ("dirs-first", None) => options.dirs_first = true,
+                ("gitignore", None) => options.gitignore = true,
+                ("color", Some(when @ "auto")) | ("color", Some(when @ "always")) | ("color", Some(when @ "never")) => color = when,
+                ("sort", Some(key)) => options.sort = _sort_key(key, io)?,
+                ("depth", Some(depth)) => options.depth = Some(_depth(depth, io)?),
+                ("units", Some(units)) => options.units = _units(units, io)?,
+                ("time-style", Some(style)) => options.time_style = _time_style(style, io)?,
+                _ => {
+                    errln!(io, "list: {}: invalid option\n{}", argument, USAGE);
+                    return None;
                 }
-                Some(filename) => {
-                    println!("{}", _form_list_string(&entry, &filename));
+            }
+        } else if argument.starts_with('-') && argument.len() > 1 {
+            for (position, flag) in argument.char_indices().skip(1) {
+                match flag {
+                    'a' => options.all = true,
+                    'l' => layout = Some(Layout::Long),
+                    'C' => layout = Some(Layout::Grid),
+                    '1' => layout = Some(Layout::OneLine),
+                    #[cfg(target_family = "unix")]
+                    'i' => options.inode = true,
+                    'r' => options.reverse = true,
+                    'R' => options.recursive = true,
+                    'S' => options.sort = SortKey::Size,
+                    't' => options.sort = SortKey::Time,
+                    'X' => options.sort = SortKey::Extension,
+                    'U' => options.sort = SortKey::Unsorted,
+                    // The depth follows, in the same argument or the next
+                    'L' => {
+                        let rest = &argument[position + 1..];
+                        let depth = if !rest.is_empty() {
+                            rest
+                        } else {
+                            index += 1;
+                            match arguments.get(index) {
+                                Some(depth) => depth,
+                                None => {
+                                    errln!(io, "list: -L: option requires an argument\n{}", USAGE);
+                                    return None;
+                                }
+                            }
+                        };
+                        options.depth = Some(_depth(depth, io)?);
+                        break;
+                    }
+                    flag => {
+                        errln!(io, "list: -{}: invalid option\n{}", flag, USAGE);
+                        return None;
+                    }
                 }
             }
+        } else {
+            break;
         }
-    } else if filepath.is_file() {
-        println!("Can't list a file! But the file name is: {}", filepath.display());
-    } else {
-        println!("Not a valid directory: {}", filepath.display());
+        index += 1;
+    }
+
+    // A depth limit is only useful when walking
+    if options.depth.is_some() && !options.tree {
+        options.recursive = true;
     }
-    Ok(())
+
+    // Like ls, names go in a grid for people and a line each for other programs
+    options.layout = match layout {
+        Some(layout) => layout,
+        None if io.stdout_is_terminal => Layout::Grid,
+        None => Layout::OneLine,
+    };
+
+    // Colours are left out of files and pipes, and for anyone who sets NO_COLOR
+    let no_color = get_variable("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if color == "always" || (color == "auto" && io.stdout_is_terminal && !no_color) {
+        options.colors = Some(Theme::new(get_variable("LS_COLORS").as_deref()));
+    }
+    Some((options, &arguments[index..]))
 }
 
-fn _form_list_string(file: &DirEntry, filename: &str) -> String {
-    let metadata_result = file.metadata();
+fn _sort_key(key: &str, io: &mut BuiltinIo) -> Option<SortKey> {
+    match key {
+        "name" => Some(SortKey::Name),
+        "size" => Some(SortKey::Size),
+        "time" => Some(SortKey::Time),
+        "extension" => Some(SortKey::Extension),
+        "none" => Some(SortKey::Unsorted),
+        _ => {
+            errln!(io, "list: {}: invalid sort, expected name, size, time, extension or none", key);
+            None
+        }
+    }
+}
 
-    if metadata_result.is_ok() {
-        let metadata = metadata_result.unwrap();
-        return format!("{0: <6}  {1}  {2: <9}  {3}", _get_file_type_string(file), _get_file_modified(&metadata), _get_file_size(&metadata, &file.path()), filename);
+fn _units(units: &str, io: &mut BuiltinIo) -> Option<Units> {
+    match units {
+        "si" => Some(Units::Si),
+        "iec" => Some(Units::Iec),
+        "bytes" => Some(Units::Bytes),
+        _ => {
+            errln!(io, "list: {}: invalid units, expected si, iec or bytes", units);
+            None
+        }
+    }
+}
+
+fn _time_style(style: &str, io: &mut BuiltinIo) -> Option<TimeStyle> {
+    let time_style = _parse_time_style(style);
+    if time_style.is_none() {
+        errln!(io, "list: {}: invalid time style, expected default, locale, iso, long-iso, full-iso, relative or +FORMAT", style);
+    }
+    time_style
+}
+
+// Takes GNU ls's names as well. A posix- style only applies outside the POSIX
+// locale, which list doesn't look at, so the prefix is dropped.
+fn _parse_time_style(style: &str) -> Option<TimeStyle> {
+    let style = style.strip_prefix("posix-").unwrap_or(style);
+    match style {
+        "default" | "locale" => Some(TimeStyle::Default),
+        "iso" | "long-iso" => Some(TimeStyle::Iso),
+        "full-iso" => Some(TimeStyle::FullIso),
+        "relative" => Some(TimeStyle::Relative),
+        // A second line gives GNU ls a format for recent files, which list doesn't use
+        _ => match style.strip_prefix('+').and_then(|format| format.lines().next()) {
+            Some(format) if is_valid_time_format(format) => Some(TimeStyle::Custom(format.to_string())),
+            _ => None,
+        },
+    }
+}
+
+fn _depth(depth: &str, io: &mut BuiltinIo) -> Option<usize> {
+    match depth.parse::<usize>() {
+        Ok(depth) if depth > 0 => Some(depth),
+        _ => {
+            errln!(io, "list: {}: invalid depth, expected a number from 1", depth);
+            None
+        }
+    }
+}
+
+// The entries of a folder, relative to the root of the walk, left out and
+// sorted as the options say
+fn _entries(folder: &Path, relative: &Path, walk: &mut Walk) -> io::Result<Vec<Entry>> {
+    if let Some(ignores) = &mut walk.ignores {
+        ignores.load(walk.root, relative);
+    }
+
+    let mut entries = Vec::new();
+    for entry in read_dir(folder)? {
+        let entry = entry?;
+        let name = from_os_str(&entry.file_name());
+        if name.starts_with('.') && !walk.options.all {
+            continue;
+        }
+        let entry = Entry { path: entry.path(), name, metadata: entry.metadata().ok() };
+        if let Some(ignores) = &walk.ignores {
+            if ignores.is_ignored(&relative.join(entry.path.file_name().unwrap()), entry.is_folder()) {
+                continue;
+            }
+        }
+        entries.push(entry);
+    }
+    _sort(&mut entries, walk.options);
+    Ok(entries)
+}
+
+// Sizes and times are largest and newest first. Folders listed first stay
+// first when reversed.
+fn _sort(entries: &mut [Entry], options: &ListOptions) {
+    let by_name = |a: &Entry, b: &Entry| a.name.to_lowercase().cmp(&b.name.to_lowercase()).then_with(|| a.name.cmp(&b.name));
+    match options.sort {
+        SortKey::Name => entries.sort_by(by_name),
+        SortKey::Size => entries.sort_by(|a, b| b.size().cmp(&a.size()).then_with(|| by_name(a, b))),
+        SortKey::Time => entries.sort_by(|a, b| b.modified().cmp(&a.modified()).then_with(|| by_name(a, b))),
+        SortKey::Extension => entries.sort_by(|a, b| a.extension().cmp(&b.extension()).then_with(|| by_name(a, b))),
+        SortKey::Unsorted => {}
+    }
+    if options.reverse {
+        entries.reverse();
+    }
+    if options.dirs_first {
+        entries.sort_by_key(|entry| !entry.is_folder());
+    }
+}
+
+fn _list(folder: &Path, walk: &mut Walk, io: &mut BuiltinIo) -> i32 {
+    match _entries(folder, Path::new(""), walk) {
+        Ok(entries) => {
+            _print_entries(&entries, walk.options, io);
+            0
+        }
+        Err(err) => {
+            errln!(io, "list: {}: {}", display_os(folder.as_os_str()), err);
+            1
+        }
+    }
+}
+
+// Lists a folder under its name, then each folder inside it the same way
+fn _list_recursive(folder: &Path, relative: &Path, depth: usize, walk: &mut Walk, io: &mut BuiltinIo) -> i32 {
+    outln!(io, "{}:", display_os(folder.as_os_str()));
+    let entries = match _entries(folder, relative, walk) {
+        Ok(entries) => entries,
+        Err(err) => {
+            errln!(io, "list: {}: {}", display_os(folder.as_os_str()), err);
+            return 1;
+        }
+    };
+    _print_entries(&entries, walk.options, io);
+
+    let mut status = 0;
+    if walk.descends(depth) {
+        for entry in entries.iter().filter(|entry| entry.is_folder()) {
+            outln!(io, "");
+            let relative = relative.join(entry.path.file_name().unwrap());
+            if _list_recursive(&entry.path, &relative, depth + 1, walk, io) != 0 {
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+// Draws the folders below the root as a tree, followed by how many folders and files it holds
+fn _tree(walk: &mut Walk, io: &mut BuiltinIo) -> i32 {
+    let root = walk.root;
+    outln!(io, "{}", display_os(root.as_os_str()));
+    let mut counts = (0, 0);
+    let status = _tree_branch(root, Path::new(""), "", 1, walk, &mut counts, io);
+
+    let (folders, files) = counts;
+    outln!(io, "");
+    outln!(io, "{} {}, {} {}",
+        folders, if folders == 1 { "directory" } else { "directories" },
+        files, if files == 1 { "file" } else { "files" });
+    status
+}
+
+fn _tree_branch(
+    folder: &Path,
+    relative: &Path,
+    prefix: &str,
+    depth: usize,
+    walk: &mut Walk,
+    counts: &mut (usize, usize),
+    io: &mut BuiltinIo,
+) -> i32 {
+    let entries = match _entries(folder, relative, walk) {
+        Ok(entries) => entries,
+        Err(err) => {
+            errln!(io, "list: {}: {}", display_os(folder.as_os_str()), err);
+            return 1;
+        }
+    };
+
+    let mut status = 0;
+    for (index, entry) in entries.iter().enumerate() {
+        let is_last = index + 1 == entries.len();
+        let branch = if is_last { "└── " } else { "├── " };
+        outln!(io, "{}{}{}{}", prefix, branch, _display_name(entry, walk.options.colors.as_ref()), _link_target(entry));
+        if !entry.is_folder() {
+            counts.1 += 1;
+            continue;
+        }
+
+        counts.0 += 1;
+        if walk.descends(depth) {
+            let prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            let relative = relative.join(entry.path.file_name().unwrap());
+            if _tree_branch(&entry.path, &relative, &prefix, depth + 1, walk, counts, io) != 0 {
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+// A cell of a row and whether it lines up on the right, as numbers do
+type Cell = (String, bool);
+
+// Each entry as a row of its details, with the columns lined up and the name last
+fn _form_rows(entries: &[Entry], options: &ListOptions) -> Vec<String> {
+    let rows: Vec<(Vec<Cell>, String)> = entries.iter()
+        .map(|entry| (_details(entry, options), _display_name(entry, options.colors.as_ref()) + &_link_target(entry)))
+        .collect();
+    let columns = rows.iter().map(|(cells, _)| cells.len()).max().unwrap_or(0);
+    let mut widths = vec!(0; columns);
+    for (cells, _) in &rows {
+        for (index, (text, _)) in cells.iter().enumerate() {
+            widths[index] = widths[index].max(text.width());
+        }
+    }
+
+    // Padded by display width, as owner names can hold wide characters
+    rows.into_iter().map(|(cells, name)| {
+        let mut row = String::new();
+        for (index, width) in widths.iter().enumerate() {
+            let (text, right) = match cells.get(index) {
+                Some((text, right)) => (&text[..], *right),
+                None => ("", false),
+            };
+            let padding = " ".repeat(width - text.width());
+            match right {
+                true => row.push_str(&format!("{}{}  ", padding, text)),
+                false => row.push_str(&format!("{}{}  ", text, padding)),
+            }
+        }
+        row.push_str(&name);
+        row
+    }).collect()
+}
+
+// Each entry's name, after its inode when asked for, and how wide it shows
+fn _name_cells(entries: &[Entry], options: &ListOptions) -> Vec<(String, usize)> {
+    #[allow(unused_mut)]
+    let mut cells: Vec<(String, usize)> = entries.iter()
+        .map(|entry| (_display_name(entry, options.colors.as_ref()), display(&entry.name).width()))
+        .collect();
+
+    #[cfg(target_family = "unix")]
+    if options.inode {
+        let inodes: Vec<String> = entries.iter()
+            .map(|entry| entry.metadata.as_ref().map_or(String::from("?"), |metadata| metadata.ino().to_string()))
+            .collect();
+        let inode_width = inodes.iter().map(|inode| inode.len()).max().unwrap_or(0);
+        for ((text, width), inode) in cells.iter_mut().zip(inodes) {
+            *text = format!("{:>2$} {}", inode, text, inode_width);
+            *width += inode_width + 1;
+        }
+    }
+    cells
+}
+
+// Lays the names out in columns, reading down each column in turn
+fn _form_grid(entries: &[Entry], options: &ListOptions) -> Vec<String> {
+    let cells = _name_cells(entries, options);
+    let widths: Vec<usize> = cells.iter().map(|(_, width)| *width).collect();
+    let (rows, column_widths) = grid_layout(&widths, _line_width());
+
+    (0..rows).map(|row| {
+        let mut line = String::new();
+        for (column, column_width) in column_widths.iter().enumerate() {
+            let index = column * rows + row;
+            let (text, width) = match cells.get(index) {
+                Some(cell) => cell,
+                None => break,
+            };
+            line.push_str(text);
+            // Padded out to the next column when this row has one
+            if index + rows < cells.len() {
+                line.push_str(&" ".repeat(column_width - width + COLUMN_GAP));
+            }
+        }
+        line
+    }).collect()
+}
+
+// How wide a grid can be: COLUMNS when it is set, otherwise the terminal's
+// width, or 80 when there's no terminal to ask
+fn _line_width() -> usize {
+    if let Some(columns) = get_variable("COLUMNS").and_then(|columns| columns.parse::<usize>().ok()) {
+        if columns > 0 {
+            return columns;
+        }
+    }
+    #[cfg(target_family = "unix")]
+    if let Ok((columns, _)) = termion::terminal_size() {
+        if columns > 0 {
+            return columns as usize;
+        }
+    }
+    80
+}
+
+fn _print_entries(entries: &[Entry], options: &ListOptions, io: &mut BuiltinIo) {
+    let lines = match options.layout {
+        Layout::Long => _form_rows(entries, options),
+        Layout::Grid => _form_grid(entries, options),
+        Layout::OneLine => _name_cells(entries, options).into_iter().map(|(text, _)| text).collect(),
+    };
+    for line in lines {
+        outln!(io, "{}", line);
+    }
+}
+
+// Links are described rather than followed
+fn _details(entry: &Entry, options: &ListOptions) -> Vec<Cell> {
+    let metadata = match &entry.metadata {
+        Some(metadata) => metadata,
+        None => return Vec::new(),
+    };
+
+    let mut cells = Vec::new();
+    #[cfg(target_family = "unix")]
+    if options.inode {
+        cells.push((metadata.ino().to_string(), true));
+    }
+    cells.extend(_get_permissions(metadata));
+    cells.push((_get_file_size(metadata, &entry.path, options.units), true));
+    cells.push((_get_file_modified(metadata, &options.time_style), false));
+    cells
+}
+
+// Names that aren't valid UTF-8 are shown with the bad bytes escaped
+fn _display_name(entry: &Entry, colors: Option<&Theme>) -> String {
+    let name = display(&entry.name);
+    match colors {
+        Some(theme) => paint(&name, theme.code(_file_kind(entry, theme), &entry.name)),
+        None => name,
+    }
+}
+
+// Where a link points and whether anything is there, to follow its name
+fn _link_target(entry: &Entry) -> String {
+    if !entry.metadata.as_ref().is_some_and(|metadata| metadata.file_type().is_symlink()) {
+        return String::new();
+    }
+    match read_link(&entry.path) {
+        Ok(target) => {
+            let broken = if metadata(&entry.path).is_err() { " (broken)" } else { "" };
+            format!(" -> {}{}", display_os(target.as_os_str()), broken)
+        }
+        Err(_) => String::new(),
+    }
+}
+
+fn _file_kind(entry: &Entry, theme: &Theme) -> FileKind {
+    let link_metadata = match &entry.metadata {
+        Some(metadata) => metadata,
+        None => return FileKind::File,
+    };
+    if !link_metadata.file_type().is_symlink() {
+        return _kind_of(link_metadata);
+    }
+    match metadata(&entry.path) {
+        Ok(target_metadata) if theme.links_as_targets() => _kind_of(&target_metadata),
+        Ok(_) => FileKind::Link,
+        Err(_) => FileKind::BrokenLink,
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn _kind_of(metadata: &Metadata) -> FileKind {
+    let file_type = metadata.file_type();
+    let mode = metadata.mode();
+    if file_type.is_dir() {
+        return match (mode & 0o1000 != 0, mode & 0o002 != 0) {
+            (true, true) => FileKind::StickyOtherWritable,
+            (true, false) => FileKind::Sticky,
+            (false, true) => FileKind::OtherWritable,
+            (false, false) => FileKind::Folder,
+        };
+    }
+
+    if file_type.is_symlink() {
+        FileKind::Link
+    } else if file_type.is_fifo() {
+        FileKind::Fifo
+    } else if file_type.is_socket() {
+        FileKind::Socket
+    } else if file_type.is_block_device() {
+        FileKind::BlockDevice
+    } else if file_type.is_char_device() {
+        FileKind::CharDevice
+    } else if mode & 0o4000 != 0 {
+        FileKind::Setuid
+    } else if mode & 0o2000 != 0 {
+        FileKind::Setgid
+    } else if mode & 0o111 != 0 {
+        FileKind::Executable
+    } else {
+        FileKind::File
+    }
+}
+
+// Windows has no execute permission or special files to colour
+#[cfg(target_family = "windows")]
+fn _kind_of(metadata: &Metadata) -> FileKind {
+    if metadata.is_dir() {
+        FileKind::Folder
+    } else if metadata.file_type().is_symlink() {
+        FileKind::Link
     } else {
-        return format!("{}", filename);
+        FileKind::File
     }
 }
 
+// The mode, link count, owner and group
+#[cfg(target_family = "unix")]
+fn _get_permissions(metadata: &Metadata) -> Vec<Cell> {
+    vec!(
+        (format_mode(metadata.mode()), false),
+        (metadata.nlink().to_string(), true),
+        (user_name(metadata.uid()), false),
+        (group_name(metadata.gid()), false),
+    )
+}
+
+// Windows files only have a read-only attribute to show
+#[cfg(target_family = "windows")]
+fn _get_permissions(metadata: &Metadata) -> Vec<Cell> {
+    let file_type = if metadata.is_dir() { 'd' } else if metadata.file_type().is_symlink() { 'l' } else { '-' };
+    let access = if metadata.permissions().readonly() { "r-" } else { "rw" };
+    vec!((format!("{}{}", file_type, access), false))
+}
+
 #[cfg(target_family = "unix")]
-fn _get_file_size(metadata: &Metadata, _path: &Path) -> String {
-    return _convert_bytes_to_string(metadata.len());
+fn _get_file_size(metadata: &Metadata, _path: &Path, units: Units) -> String {
+    return format_size(metadata.len(), units);
 }
 
 #[cfg(target_family = "windows")]
-fn _get_file_size(metadata: &Metadata, path: &Path) -> String {
+fn _get_file_size(metadata: &Metadata, path: &Path, units: Units) -> String {
     let total_size = walkdir::WalkDir::new(path)
         .into_iter()
         .filter_map(|entry| entry.ok())
@@ -76,35 +739,28 @@ fn _get_file_size(metadata: &Metadata, path: &Path) -> String {
         .filter(|metadata| metadata.is_file())
         .fold(0, |acc, m| acc + m.len());
 
-    return _convert_bytes_to_string(total_size);
-}
-
-fn _convert_bytes_to_string(size: u64) -> String {
-    // TODO: decide on system for displaying sizes in what units
-    let mut size: f64 = size as f64;
-    size /= 1e3;
-    return format!("{:.2} KB", size);
-}
-
-fn _get_file_modified(metadata: &Metadata) -> String {
-    if metadata.modified().is_ok() {
-        let time = DateTime::<Local>::from(metadata.modified().unwrap());
-        return format!("{}", time.format("%d/%m/%Y %H:%M"));
-    }
-
-    return format!("");
+    return format_size(total_size, units);
 }
 
-fn _get_file_type_string(file: &DirEntry) -> &str {
-    let file_type = file.file_type().unwrap();
-    let mut string = "";
+fn _get_file_modified(metadata: &Metadata, time_style: &TimeStyle) -> String {
+    let modified = match metadata.modified() {
+        Ok(modified) => modified,
+        Err(_) => return String::new(),
+    };
+    let format = match time_style {
+        TimeStyle::Default => "%d/%m/%Y %H:%M",
+        TimeStyle::Iso => "%Y-%m-%d %H:%M",
+        TimeStyle::FullIso => "%Y-%m-%d %H:%M:%S %z",
+        TimeStyle::Custom(format) => format,
+        TimeStyle::Relative => {
+            let age = match SystemTime::now().duration_since(modified) {
+                Ok(age) => age.as_secs() as i64,
+                Err(err) => -(err.duration().as_secs() as i64),
+            };
+            return format_age(age);
+        }
+    };
 
-    if file_type.is_file() {
-        string = "<file>";
-    } else if file_type.is_dir() {
-        string = "<dir>";
-    } else if file_type.is_symlink() {
-        string = "<sym>";
-    }
-    return string;
+    let time = DateTime::<Local>::from(modified);
+    return format!("{}", time.format(format));
 }
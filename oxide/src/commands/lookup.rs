@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use crate::alias::{format_alias, ALIASES};
+use crate::commands::{parse_flags, BuiltinIo};
+use crate::encoding::{display, display_os, to_path};
+use crate::logic::{is_builtin, is_function};
+use crate::parser::is_reserved_word;
+use crate::path_cache::{
+    cached_command, cached_commands, clear_cache, find_command, forget_command, hash_command, is_executable,
+    is_path, search_path,
+};
+
+// What a command name runs, in the order the shell looks
+enum Resolution {
+    Alias(String),
+    Keyword,
+    Function,
+    Builtin,
+    // A file, and whether it was found in the PATH cache
+    File(PathBuf, bool),
+}
+
+// Everything a name could run when `all` is set, otherwise what it does run.
+// `path_only` skips straight to the PATH search, and `skip_functions` leaves
+// functions out as if they weren't defined.
+fn _resolve(name: &str, all: bool, path_only: bool, skip_functions: bool) -> Vec<Resolution> {
+    let mut resolutions = Vec::new();
+    if !path_only {
+        if let Some(value) = ALIASES.lock().unwrap().get(name) {
+            resolutions.push(Resolution::Alias(value.clone()));
+        }
+        if is_reserved_word(name) {
+            resolutions.push(Resolution::Keyword);
+        }
+        if !skip_functions && is_function(name) {
+            resolutions.push(Resolution::Function);
+        }
+        if is_builtin(name) {
+            resolutions.push(Resolution::Builtin);
+        }
+        if !all && !resolutions.is_empty() {
+            resolutions.truncate(1);
+            return resolutions;
+        }
+    }
+
+    if is_path(name) {
+        if is_executable(&to_path(name)) {
+            resolutions.push(Resolution::File(to_path(name), false));
+        }
+    } else if all {
+        resolutions.extend(search_path(name).into_iter().map(|path| Resolution::File(path, false)));
+    } else if let Some(entry) = cached_command(name) {
+        resolutions.push(Resolution::File(entry.path, true));
+    } else if let Some(path) = search_path(name).into_iter().next() {
+        resolutions.push(Resolution::File(path, false));
+    }
+    resolutions
+}
+
+fn _describe(name: &str, resolution: &Resolution) -> String {
+    match resolution {
+        Resolution::Alias(value) => format!("{} is aliased to `{}'", name, value),
+        Resolution::Keyword => format!("{} is a shell keyword", name),
+        Resolution::Function => format!("{} is a function", name),
+        Resolution::Builtin => format!("{} is a shell builtin", name),
+        Resolution::File(path, true) => format!("{} is hashed ({})", display(name), display_os(path.as_os_str())),
+        Resolution::File(path, false) => format!("{} is {}", display(name), display_os(path.as_os_str())),
+    }
+}
+
+fn _kind(resolution: &Resolution) -> &'static str {
+    match resolution {
+        Resolution::Alias(_) => "alias",
+        Resolution::Keyword => "keyword",
+        Resolution::Function => "function",
+        Resolution::Builtin => "builtin",
+        Resolution::File(_, _) => "file",
+    }
+}
+
+// type [-afptP] name...
+pub fn type_command(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (flags, names) = match parse_flags("type", arguments, "afptP", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    let all = flags.contains('a');
+    let path_only = flags.contains('P');
+
+    let mut status = 0;
+    for name in names {
+        let resolutions = _resolve(name, all, path_only, flags.contains('f'));
+        if resolutions.is_empty() {
+            if !flags.contains('t') && !flags.contains('p') {
+                errln!(io, "oxide: type: {}: not found", display(name));
+            }
+            status = 1;
+            continue;
+        }
+        for resolution in &resolutions {
+            if flags.contains('t') {
+                outln!(io, "{}", _kind(resolution));
+            } else if flags.contains('p') || path_only {
+                if let Resolution::File(path, _) = resolution {
+                    outln!(io, "{}", display_os(path.as_os_str()));
+                }
+            } else {
+                outln!(io, "{}", _describe(name, resolution));
+            }
+        }
+    }
+    status
+}
+
+// which [-a] name...
+pub fn which(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (flags, names) = match parse_flags("which", arguments, "a", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+
+    let mut status = 0;
+    for name in names {
+        let resolutions = _resolve(name, flags.contains('a'), false, false);
+        if resolutions.is_empty() {
+            errln!(io, "{} not found", display(name));
+            status = 1;
+        }
+        for resolution in resolutions {
+            match resolution {
+                Resolution::Alias(value) => outln!(io, "{}: aliased to {}", name, value),
+                Resolution::Keyword => outln!(io, "{}: shell reserved word", name),
+                Resolution::Function => outln!(io, "{}: shell function", name),
+                Resolution::Builtin => outln!(io, "{}: shell built-in command", name),
+                Resolution::File(path, _) => outln!(io, "{}", display_os(path.as_os_str())),
+            }
+        }
+    }
+    status
+}
+
+// hash [-r] [-d name] [-t name] [name...]
+pub fn hash(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (flags, names) = match parse_flags("hash", arguments, "rdt", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    if flags.contains('r') {
+        clear_cache();
+    }
+
+    if names.is_empty() {
+        if flags.contains('r') {
+            return 0;
+        }
+        let entries = cached_commands();
+        if entries.is_empty() {
+            outln!(io, "hash: hash table empty");
+            return 0;
+        }
+        outln!(io, "hits\tcommand");
+        for (_, entry) in entries {
+            outln!(io, "{:4}\t{}", entry.hits, display_os(entry.path.as_os_str()));
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        if flags.contains('d') {
+            if !forget_command(name) {
+                errln!(io, "hash: {}: not found", display(name));
+                status = 1;
+            }
+        } else if flags.contains('t') {
+            match cached_command(name).map(|entry| entry.path).or_else(|| find_command(name)) {
+                Some(path) => outln!(io, "{}", display_os(path.as_os_str())),
+                None => {
+                    errln!(io, "hash: {}: not found", display(name));
+                    status = 1;
+                }
+            }
+        } else if !is_builtin(name) && !is_function(name) && !hash_command(name) {
+            errln!(io, "hash: {}: not found", display(name));
+            status = 1;
+        }
+    }
+    status
+}
+
+// command -v or -V name... The form that runs a command, skipping functions,
+// is handled by the executor.
+pub fn command(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (flags, names) = match parse_flags("command", arguments, "pvV", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    if !flags.contains('v') && !flags.contains('V') {
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        let resolution = match _resolve(name, false, false, false).into_iter().next() {
+            Some(resolution) => resolution,
+            None => {
+                if flags.contains('V') {
+                    errln!(io, "oxide: command: {}: not found", display(name));
+                }
+                status = 1;
+                continue;
+            }
+        };
+        if flags.contains('V') {
+            outln!(io, "{}", _describe(name, &resolution));
+            continue;
+        }
+        match resolution {
+            Resolution::Alias(value) => outln!(io, "{}", format_alias(name, &value)),
+            Resolution::File(path, _) => outln!(io, "{}", display_os(path.as_os_str())),
+            _ => outln!(io, "{}", name),
+        }
+    }
+    status
+}
+
+// `builtin name args` is handled by the executor, so only a bare `builtin`
+// gets here
+pub fn builtin(_arguments: &[String], _io: &mut BuiltinIo) -> i32 {
+    0
+}
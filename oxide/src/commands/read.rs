@@ -0,0 +1,87 @@
+use crate::commands::BuiltinIo;
+use crate::encoding::from_bytes;
+use crate::variables::{get_variable, is_valid_name, set_variable};
+
+const DEFAULT_IFS: &str = " \t\n";
+
+// Reads a line from stdin and splits it on IFS into the named variables, the
+// last variable taking the rest of the line. Without names the line goes in REPLY.
+pub fn read(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let raw = arguments.first().is_some_and(|argument| argument == "-r");
+    let names: Vec<&String> = arguments.iter().skip(if raw { 1 } else { 0 }).collect();
+    if let Some(name) = names.iter().find(|name| !is_valid_name(name)) {
+        errln!(io, "read: `{}': not a valid identifier", name);
+        return 2;
+    }
+
+    let (line, found_newline) = match _read_line(io, raw) {
+        Ok(line) => line,
+        Err(err) => {
+            errln!(io, "read: {}", err);
+            return 1;
+        }
+    };
+
+    let result = if names.is_empty() {
+        set_variable("REPLY", &line)
+    } else {
+        let ifs = get_variable("IFS").unwrap_or_else(|| DEFAULT_IFS.to_string());
+        let fields = _split_fields(&line, &ifs, names.len());
+        names.iter().enumerate().try_for_each(|(index, name)| {
+            set_variable(name, fields.get(index).map(|field| &field[..]).unwrap_or(""))
+        })
+    };
+    if let Err(err) = result {
+        errln!(io, "read: {}", err);
+        return 1;
+    }
+
+    // Like sh, reaching the end of input without a newline is a failure
+    if found_newline { 0 } else { 1 }
+}
+
+// Reads one byte at a time so nothing after the line is consumed
+fn _read_line(io: &mut BuiltinIo, raw: bool) -> std::io::Result<(String, bool)> {
+    let mut line = Vec::new();
+    let mut byte = [0; 1];
+    let mut escaped = false;
+    loop {
+        if io.stdin.read(&mut byte)? == 0 {
+            return Ok((from_bytes(&line), false));
+        }
+        match byte[0] {
+            b'\\' if !raw && !escaped => escaped = true,
+            b'\n' if escaped => escaped = false,
+            b'\n' => return Ok((from_bytes(&line), true)),
+            other => {
+                escaped = false;
+                line.push(other);
+            }
+        }
+    }
+}
+
+// Splits into at most `count` fields, the last one keeping the rest of the line
+fn _split_fields(line: &str, ifs: &str, count: usize) -> Vec<String> {
+    let is_separator = |c: char| ifs.contains(c);
+    let mut fields = Vec::new();
+    let mut rest = line.trim_matches(|c: char| is_separator(c) && c.is_whitespace());
+
+    while fields.len() + 1 < count && !rest.is_empty() {
+        match rest.find(is_separator) {
+            Some(end) => {
+                fields.push(rest[..end].to_string());
+                let separator_length = rest[end..].chars().next().unwrap().len_utf8();
+                rest = rest[end + separator_length..].trim_start_matches(|c: char| is_separator(c) && c.is_whitespace());
+            }
+            None => {
+                fields.push(rest.to_string());
+                rest = "";
+            }
+        }
+    }
+    if !rest.is_empty() {
+        fields.push(rest.to_string());
+    }
+    fields
+}
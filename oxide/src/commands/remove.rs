@@ -1,61 +1,118 @@
-use std::fs::{read_dir, remove_dir, remove_dir_all, remove_file};
-use std::io;
+use std::fs::{read_dir, remove_dir, remove_dir_all, remove_file, symlink_metadata};
 use std::path::Path;
 
+use crate::commands::{confirm, parse_flags, BuiltinIo};
+use crate::encoding::{display_os, to_path};
 
-pub fn remove(filepaths: Vec<&Path>) {
-    for path in filepaths {
-        _remove(path);
+// remove [-f] path...
+// Asks before removing a folder that isn't empty. -f removes it without
+// asking and ignores paths that don't exist.
+pub fn remove(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (flags, operands) = match parse_flags("remove", arguments, "f", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    if operands.is_empty() {
+        errln!(io, "remove: Please provide a path to remove!");
+        return 2;
     }
-}
 
-fn _remove(path: &Path) {
-    if !path.exists() {
-        println!("Could not find the file: {}", path.display());
-        return
+    let force = flags.contains('f');
+    let mut status = 0;
+    for operand in operands {
+        if _remove(&to_path(operand), force, io) != 0 {
+            status = 1;
+        }
     }
+    status
+}
+
+fn _remove(path: &Path, force: bool, io: &mut BuiltinIo) -> i32 {
+    let path_string = display_os(path.as_os_str());
+    // A link is removed itself, even when it points at a folder
+    let metadata = match symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) if force => return 0,
+        Err(_) => {
+            errln!(io, "remove: Could not find the file: {}", path_string);
+            return 1;
+        }
+    };
 
-    if path.is_dir() {
-        _remove_folder(path)
+    if metadata.is_dir() {
+        return _remove_folder(path, force, io);
     }
 
-    else {
-        match remove_file(path) {
-            Ok(_) => println!("Removed file: {}", path.display()),
-            Err(err) => println!("Failed to remove file with error: {}", err)
+    match remove_file(path) {
+        Ok(_) => {
+            outln!(io, "Removed file: {}", path_string);
+            0
+        }
+        Err(err) => {
+            errln!(io, "remove: Failed to remove file {} with error: {}", path_string, err);
+            1
         }
     }
 }
 
-pub fn remove_folder(filepaths: Vec<&Path>) {
-    for path in filepaths {
-        _remove_folder(path);
+// removef [-f] folder...
+pub fn remove_folder(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (flags, operands) = match parse_flags("removef", arguments, "f", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    if operands.is_empty() {
+        errln!(io, "removef: Please provide a folder to remove!");
+        return 2;
     }
-}
 
-pub fn _remove_folder(path: &Path) {
-    let num_files_in_dir = read_dir(path).unwrap().count();
+    let force = flags.contains('f');
+    let mut status = 0;
+    for operand in operands {
+        let path = to_path(operand);
+        if !symlink_metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+            if !force || path.exists() {
+                errln!(io, "removef: Not a folder: {}", display_os(path.as_os_str()));
+                status = 1;
+            }
+            continue;
+        }
+        if _remove_folder(&path, force, io) != 0 {
+            status = 1;
+        }
+    }
+    status
+}
 
-    if num_files_in_dir == 0 {
-        match remove_dir(path) {
-            Ok(_) => println!("Removed directory: {}", path.display()),
-            Err(err) => println!("Failed to remove directory with error: {}", err)
+fn _remove_folder(path: &Path, force: bool, io: &mut BuiltinIo) -> i32 {
+    let path_string = display_os(path.as_os_str());
+    let is_empty = match read_dir(path) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(err) => {
+            errln!(io, "remove: Failed to read directory {} with error: {}", path_string, err);
+            return 1;
         }
+    };
+
+    let result = if is_empty {
+        remove_dir(path)
     } else {
-        println!("This directory is not empty, would you like to remove all contents in: \n {} ? (y,n)", path.display());
-        let mut remove_dir_confirm = String::new();
-
-        match io::stdin().read_line(&mut remove_dir_confirm) {
-            Ok(_) => {
-                if remove_dir_confirm.trim() == "y" {
-                    match remove_dir_all(path)
-                    {
-                        Ok(_) => println!("Removed directory: {}", path.display()),
-                        Err(err) => println!("Failed to remove directory with error: {}", err),
-                    };
-                }
-            }
-            Err(error) => println!("Error reading input: {}", error),
+        let question = format!("This directory is not empty, would you like to remove all contents in:\n {} ?", path_string);
+        // Like rm -i, saying no isn't a failure
+        if !force && !confirm(&question, io) {
+            return 0;
+        }
+        remove_dir_all(path)
+    };
+
+    match result {
+        Ok(_) => {
+            outln!(io, "Removed directory: {}", path_string);
+            0
+        }
+        Err(err) => {
+            errln!(io, "remove: Failed to remove directory {} with error: {}", path_string, err);
+            1
         }
     }
 }
@@ -1,47 +1,61 @@
-use std::fs::{read_to_string};
+use std::fs::File;
+use std::io;
 use std::path::Path;
 
-pub fn show(filepaths: Vec<&Path>) {
-    if filepaths.len() == 0 {
-        println!("Pass in a file and I will SHOW you the contents");
-        return;
+use crate::commands::{parse_flags, BuiltinIo};
+use crate::encoding::{display_os, to_path};
+
+// show [file...]
+// Without files, or for "-", stdin is shown instead so show works in pipelines
+pub fn show(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let (_, operands) = match parse_flags("show", arguments, "", io) {
+        Some(parsed) => parsed,
+        None => return 2,
+    };
+    if operands.is_empty() {
+        return _show_stdin(io);
     }
 
-    for path in &filepaths {
-        _show(path);
+    let mut status = 0;
+    for operand in operands {
+        let result = match &operand[..] {
+            "-" => _show_stdin(io),
+            _ => _show(&to_path(operand), io),
+        };
+        if result != 0 {
+            status = result;
+        }
     }
+    status
 }
-fn _show(filepath: &Path) {
-    if !filepath.is_file() {
-        println!("Cannot print out non-file contents.");
-        return;
+
+// Copies the file as it is, so binary files and those that aren't valid UTF-8 come through unchanged
+fn _show(filepath: &Path, io: &mut BuiltinIo) -> i32 {
+    let path_string = display_os(filepath.as_os_str());
+    if filepath.is_dir() {
+        errln!(io, "show: {}: Cannot show the contents of a folder", path_string);
+        return 1;
     }
-    println!("Showing file {}", filepath.display());
-    let file_data = read_to_string(filepath);
-    match file_data {
-        Err(error) => { println!("Could not read file {} \n Error {}", filepath.display(), error); }
-        Ok(file_text) => { println!("{}", file_text); }
+
+    let result = File::open(filepath).and_then(|mut file| io::copy(&mut file, &mut io.stdout));
+    match result {
+        Ok(_) => 0,
+        // As with outln!, the reader of a pipe exiting early isn't an error
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => 0,
+        Err(err) => {
+            errln!(io, "show: {}: {}", path_string, err);
+            1
+        }
     }
 }
 
-/*
-_show_buffer may be faster for large files? 
-On small files has been tested to be slower than _show
-
-fn _show_buffer(filepath: &Path) -> io::Result<()> {
-    if !filepath.is_file() {
-        println!("Cannot print out non-file contents.");
-        return Ok(());
+fn _show_stdin(io: &mut BuiltinIo) -> i32 {
+    match io::copy(&mut io.stdin, &mut io.stdout) {
+        Ok(_) => 0,
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => 0,
+        Err(err) => {
+            errln!(io, "show: -: {}", err);
+            1
+        }
     }
-    println!("Showing file {}", filepath.display());
-    let file = File::open(filepath)?;
-    let file = BufReader::new(file);
-    for line in file.lines() {
-        println!("{}", line.unwrap());
-    }
-
-    Ok(())
 }
-
-
-*/
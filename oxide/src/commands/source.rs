@@ -0,0 +1,56 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::commands::BuiltinIo;
+use crate::encoding::{from_bytes, to_path};
+use crate::logic::{run_script, source_file};
+use crate::options::posix_mode;
+
+// `. file [arguments]` runs the commands in a file in the current shell, with
+// the arguments as the positional parameters while it runs
+pub fn dot(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let name = match arguments.first() {
+        Some(name) => name,
+        None => {
+            errln!(io, ".: filename argument required");
+            return 2;
+        }
+    };
+    let path = match _find_file(name) {
+        Some(path) => path,
+        None => {
+            errln!(io, ".: {}: file not found", name);
+            return 1;
+        }
+    };
+    let source = match fs::read(&path) {
+        Ok(source) => from_bytes(&source),
+        Err(err) => {
+            errln!(io, ".: {}: {}", name, err);
+            return 1;
+        }
+    };
+    let parameters = (arguments.len() > 1).then(|| arguments[1..].to_vec());
+    source_file(&source, parameters)
+}
+
+// Joins its arguments with spaces and runs them as commands in the current shell
+pub fn eval(arguments: &[String], _io: &mut BuiltinIo) -> i32 {
+    run_script(&arguments.join(" "))
+}
+
+// A name without a slash is looked for in PATH, then outside POSIX mode in
+// the current directory, as bash does
+fn _find_file(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(to_path(name));
+    }
+    let mut directories: Vec<PathBuf> = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect())
+        .unwrap_or_default();
+    if !posix_mode() {
+        directories.push(PathBuf::from("."));
+    }
+    directories.into_iter().map(|directory| directory.join(name)).find(|path| path.is_file())
+}
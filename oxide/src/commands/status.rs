@@ -0,0 +1,12 @@
+use crate::commands::BuiltinIo;
+use crate::status::{last_status, pipe_status, status_report};
+
+// Prints the status of the previous command as JSON
+pub fn status(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if !arguments.is_empty() {
+        errln!(io, "status: usage: status");
+        return 2;
+    }
+    outln!(io, "{}", status_report(last_status(), &pipe_status()));
+    0
+}
@@ -0,0 +1,12 @@
+use crate::commands::BuiltinIo;
+use crate::timeout::parse_timeout_arguments;
+
+// `timeout [-s signal] [-k grace] duration command [args]` is handled by the executor, so
+// this only runs when the arguments are wrong
+pub fn timeout(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    match parse_timeout_arguments(arguments) {
+        Some(_) => errln!(io, "timeout: a command to run is required"),
+        None => errln!(io, "timeout: usage: timeout [-s signal] [-k grace] duration command [args]"),
+    }
+    2
+}
@@ -0,0 +1,78 @@
+use crate::commands::BuiltinIo;
+use crate::lexer::quote;
+use crate::status::signal_name;
+use crate::traps::{condition_name, parse_condition, reset_trap, set_trap, signal_numbers, traps, TrapCondition};
+
+pub fn trap(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let arguments = match arguments.first().map(String::as_str) {
+        Some("--") => &arguments[1..],
+        _ => arguments,
+    };
+
+    match arguments.first().map(String::as_str) {
+        None => return _print(&[], io),
+        Some("-p") => return _print(&arguments[1..], io),
+        Some("-l") => {
+            for signal in signal_numbers() {
+                outln!(io, "{:2}) {}", signal, signal_name(128 + signal).unwrap_or_default());
+            }
+            return 0;
+        }
+        _ => (),
+    }
+
+    // `trap - SIG`, a lone condition, or conditions starting with a number
+    // put the conditions back to their defaults
+    let first = &arguments[0];
+    let (action, conditions) = if first == "-" {
+        (None, &arguments[1..])
+    } else if arguments.len() == 1 || (!first.is_empty() && first.chars().all(|c| c.is_ascii_digit())) {
+        (None, arguments)
+    } else {
+        (Some(first.as_str()), &arguments[1..])
+    };
+
+    let mut status = 0;
+    for spec in conditions {
+        let condition = match parse_condition(spec) {
+            Some(condition) => condition,
+            None => {
+                errln!(io, "trap: {}: invalid signal specification", spec);
+                status = 1;
+                continue;
+            }
+        };
+        match action {
+            Some(action) => {
+                if let Err(err) = set_trap(condition, action) {
+                    errln!(io, "trap: {}: {}", spec, err);
+                    status = 1;
+                }
+            }
+            None => reset_trap(condition),
+        }
+    }
+    status
+}
+
+// Prints traps so they can be read back in, every one or only those given
+fn _print(specs: &[String], io: &mut BuiltinIo) -> i32 {
+    let mut wanted: Vec<TrapCondition> = Vec::new();
+    let mut status = 0;
+    for spec in specs {
+        match parse_condition(spec) {
+            Some(condition) => wanted.push(condition),
+            None => {
+                errln!(io, "trap: {}: invalid signal specification", spec);
+                status = 1;
+            }
+        }
+    }
+
+    for (condition, action) in traps() {
+        if specs.is_empty() || wanted.contains(&condition) {
+            outln!(io, "trap -- {} {}", quote(&action), condition_name(condition));
+        }
+    }
+    status
+}
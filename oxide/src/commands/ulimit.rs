@@ -0,0 +1,168 @@
+use std::io;
+
+use crate::commands::BuiltinIo;
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+struct Limit {
+    flag: char,
+    description: &'static str,
+    // Shown after the description, such as kbytes
+    unit: &'static str,
+    resource: Resource,
+    // Bytes or seconds per unit the limit is given in
+    scale: libc::rlim_t,
+}
+
+const LIMITS: [Limit; 7] = [
+    Limit { flag: 'c', description: "core file size", unit: "blocks", resource: libc::RLIMIT_CORE, scale: 1024 },
+    Limit { flag: 'f', description: "file size", unit: "blocks", resource: libc::RLIMIT_FSIZE, scale: 1024 },
+    Limit { flag: 'n', description: "open files", unit: "", resource: libc::RLIMIT_NOFILE, scale: 1 },
+    Limit { flag: 's', description: "stack size", unit: "kbytes", resource: libc::RLIMIT_STACK, scale: 1024 },
+    Limit { flag: 't', description: "cpu time", unit: "seconds", resource: libc::RLIMIT_CPU, scale: 1 },
+    Limit { flag: 'u', description: "max user processes", unit: "", resource: libc::RLIMIT_NPROC, scale: 1 },
+    Limit { flag: 'v', description: "virtual memory", unit: "kbytes", resource: libc::RLIMIT_AS, scale: 1024 },
+];
+
+// ulimit [-SHa] [-cfnstuv] [limit]
+// Without -S or -H a new limit sets both the soft and hard limits, and the
+// soft one is shown. The file size limit is used when no resource is named.
+// Limits apply to the shell and so to every command it starts.
+pub fn ulimit(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let mut soft = false;
+    let mut hard = false;
+    let mut all = false;
+    let mut limits: Vec<&Limit> = Vec::new();
+    let mut index = 0;
+    while index < arguments.len() && arguments[index].starts_with('-') && arguments[index].len() > 1 {
+        if arguments[index] == "--" {
+            index += 1;
+            break;
+        }
+        for flag in arguments[index][1..].chars() {
+            match flag {
+                'S' => soft = true,
+                'H' => hard = true,
+                'a' => all = true,
+                _ => match LIMITS.iter().find(|limit| limit.flag == flag) {
+                    Some(limit) => limits.push(limit),
+                    None => {
+                        errln!(io, "ulimit: -{}: invalid option", flag);
+                        errln!(io, "ulimit: usage: ulimit [-SHa] [-cfnstuv] [limit]");
+                        return 2;
+                    }
+                },
+            }
+        }
+        index += 1;
+    }
+    let values = &arguments[index..];
+
+    if all {
+        for limit in LIMITS.iter() {
+            if !_show(limit, hard && !soft, true, io) {
+                return 1;
+            }
+        }
+        return 0;
+    }
+    if limits.is_empty() {
+        limits.push(&LIMITS[1]);
+    }
+
+    match values {
+        [] => {
+            let labelled = limits.len() > 1;
+            for limit in limits {
+                if !_show(limit, hard && !soft, labelled, io) {
+                    return 1;
+                }
+            }
+            0
+        }
+        [value] if limits.len() == 1 => {
+            // Neither flag sets both
+            let (soft, hard) = if soft || hard { (soft, hard) } else { (true, true) };
+            _set(limits[0], value, soft, hard, io)
+        }
+        _ => {
+            errln!(io, "ulimit: too many arguments");
+            2
+        }
+    }
+}
+
+fn _get(limit: &Limit) -> io::Result<libc::rlimit> {
+    let mut rlimit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(limit.resource, &mut rlimit) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(rlimit)
+}
+
+fn _format(value: libc::rlim_t, limit: &Limit) -> String {
+    if value == libc::RLIM_INFINITY {
+        String::from("unlimited")
+    } else {
+        (value / limit.scale).to_string()
+    }
+}
+
+// Prints a soft or hard limit, with its description when `labelled`
+fn _show(limit: &Limit, hard: bool, labelled: bool, io: &mut BuiltinIo) -> bool {
+    let rlimit = match _get(limit) {
+        Ok(rlimit) => rlimit,
+        Err(err) => {
+            errln!(io, "ulimit: {}: cannot get limit: {}", limit.description, err);
+            return false;
+        }
+    };
+    let value = _format(if hard { rlimit.rlim_max } else { rlimit.rlim_cur }, limit);
+    if labelled {
+        let flag = match limit.unit {
+            "" => format!("(-{})", limit.flag),
+            unit => format!("({}, -{})", unit, limit.flag),
+        };
+        outln!(io, "{:<20}{:>16} {}", limit.description, flag, value);
+    } else {
+        outln!(io, "{}", value);
+    }
+    true
+}
+
+fn _set(limit: &Limit, value: &str, soft: bool, hard: bool, io: &mut BuiltinIo) -> i32 {
+    let mut rlimit = match _get(limit) {
+        Ok(rlimit) => rlimit,
+        Err(err) => {
+            errln!(io, "ulimit: {}: cannot get limit: {}", limit.description, err);
+            return 1;
+        }
+    };
+    // `soft` and `hard` stand for the current limits, as in bash
+    let new_value = match value {
+        "unlimited" => libc::RLIM_INFINITY,
+        "soft" => rlimit.rlim_cur,
+        "hard" => rlimit.rlim_max,
+        _ => match value.parse::<libc::rlim_t>().ok().and_then(|value| value.checked_mul(limit.scale)) {
+            Some(new_value) => new_value,
+            None => {
+                errln!(io, "ulimit: {}: invalid number", value);
+                return 1;
+            }
+        },
+    };
+    if soft {
+        rlimit.rlim_cur = new_value;
+    }
+    if hard {
+        rlimit.rlim_max = new_value;
+    }
+    if unsafe { libc::setrlimit(limit.resource, &rlimit) } == -1 {
+        errln!(io, "ulimit: {}: cannot modify limit: {}", limit.description, io::Error::last_os_error());
+        return 1;
+    }
+    0
+}
@@ -0,0 +1,180 @@
+use crate::commands::BuiltinIo;
+use crate::encoding::{display, from_os_str};
+use crate::expansion::split_assignment;
+use crate::lexer::quote;
+use crate::options::{flag_option, option_states, set_option};
+use crate::variables::{
+    export_variable, get_variable, is_valid_name, make_local, mark_readonly, readonly_variables, set_variable, shell_variables,
+    unset_variable, positional_parameters, set_positional_parameters,
+};
+
+pub fn export(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.is_empty() || arguments[0] == "-p" {
+        let mut variables: Vec<(String, String)> = std::env::vars_os()
+            .map(|(name, value)| (from_os_str(&name), from_os_str(&value)))
+            .collect();
+        variables.sort();
+        for (name, value) in variables {
+            outln!(io, "export {}={}", display(&name), quote(&display(&value)));
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for argument in arguments {
+        let result = match split_assignment(argument) {
+            Some((name, value)) => export_variable(name, Some(value)),
+            None if is_valid_name(argument) => export_variable(argument, None),
+            None => Err(format!("`{}': not a valid identifier", argument)),
+        };
+        if let Err(err) = result {
+            errln!(io, "export: {}", err);
+            status = 1;
+        }
+    }
+    status
+}
+
+pub fn unset(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let mut status = 0;
+    for argument in arguments {
+        if argument == "-v" {
+            continue;
+        }
+        let result = match is_valid_name(argument) {
+            true => unset_variable(argument),
+            false => Err(format!("`{}': not a valid identifier", argument)),
+        };
+        if let Err(err) = result {
+            errln!(io, "unset: {}", err);
+            status = 1;
+        }
+    }
+    status
+}
+
+// Lists unexported shell variables, or sets options such as `-e`,
+// `+u` or `-o pipefail` and then the positional parameters from the first
+// argument that isn't an option, or every one after `--`
+pub fn set(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.is_empty() {
+        for (name, value) in shell_variables() {
+            outln!(io, "{}={}", name, quote(&display(&value)));
+        }
+        return 0;
+    }
+
+    let mut index = 0;
+    while let Some(argument) = arguments.get(index) {
+        index += 1;
+        let value = argument.starts_with('-');
+        match argument.as_str() {
+            "--" | "-" => {
+                set_positional_parameters(arguments[index..].to_vec());
+                return 0;
+            }
+            "-o" | "+o" => match arguments.get(index) {
+                Some(name) => {
+                    index += 1;
+                    if let Err(err) = set_option(name, value) {
+                        errln!(io, "set: {}", err);
+                        return 2;
+                    }
+                }
+                None => {
+                    for (name, state) in option_states() {
+                        outln!(io, "{:<15}{}", name, state);
+                    }
+                }
+            },
+            flags if flags.len() > 1 && (value || flags.starts_with('+')) => {
+                for flag in flags[1..].chars() {
+                    match flag_option(flag) {
+                        Some(name) => set_option(name, value).unwrap(),
+                        None => {
+                            errln!(io, "set: {}{}: invalid option", &flags[..1], flag);
+                            return 2;
+                        }
+                    }
+                }
+            }
+            _ => {
+                set_positional_parameters(arguments[index - 1..].to_vec());
+                return 0;
+            }
+        }
+    }
+    0
+}
+
+// Marks variables read-only, first setting any given a value. Without names,
+// or with -p, lists them.
+pub fn readonly(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    if arguments.is_empty() || arguments[0] == "-p" {
+        for name in readonly_variables() {
+            match get_variable(&name) {
+                Some(value) => outln!(io, "readonly {}={}", name, quote(&display(&value))),
+                None => outln!(io, "readonly {}", name),
+            }
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for argument in arguments {
+        let result = match split_assignment(argument) {
+            Some((name, value)) => set_variable(name, value).map(|_| name),
+            None if is_valid_name(argument) => Ok(argument.as_str()),
+            None => Err(format!("`{}': not a valid identifier", argument)),
+        };
+        match result {
+            Ok(name) => mark_readonly(name),
+            Err(err) => {
+                errln!(io, "readonly: {}", err);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+// Makes variables local to the function being run, setting any given a value
+pub fn local(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let mut status = 0;
+    for argument in arguments {
+        let (name, value) = match split_assignment(argument) {
+            Some((name, value)) => (name, Some(value)),
+            None => (argument.as_str(), None),
+        };
+        let result = match is_valid_name(name) {
+            true => make_local(name).and_then(|_| value.map_or(Ok(()), |value| set_variable(name, value))),
+            false => Err(format!("`{}': not a valid identifier", argument)),
+        };
+        if let Err(err) = result {
+            errln!(io, "local: {}", err);
+            status = 1;
+        }
+    }
+    status
+}
+
+pub fn shift(arguments: &[String], io: &mut BuiltinIo) -> i32 {
+    let count = match arguments.first() {
+        Some(argument) => match argument.parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => {
+                errln!(io, "shift: {}: numeric argument required", argument);
+                return 1;
+            }
+        },
+        None => 1,
+    };
+
+    let parameters = positional_parameters();
+    if count > parameters.len() {
+        errln!(io, "shift: shift count out of range");
+        return 1;
+    }
+    set_positional_parameters(parameters[count..].to_vec());
+    0
+}
@@ -13,7 +13,7 @@ impl OxideHistory {
     pub fn new() -> OxideHistory {
         match dirs::config_dir() {
             Some(config_folder) => {
-                let oxide_history_path: PathBuf = [config_folder.to_str().unwrap(), "oxide", "history.txt"].iter().collect();
+                let oxide_history_path: PathBuf = config_folder.join("oxide").join("history.txt");
 
                 if oxide_history_path.exists() {
                     return OxideHistory { exists: true, path: oxide_history_path }
@@ -41,11 +41,17 @@ impl OxideHistory {
 }
 
 fn create_oxide_history(config_folder_path: PathBuf) -> io::Result<()> {
-    let oxide_conf_folder: PathBuf = [config_folder_path.to_str().unwrap(), "oxide"].iter().collect();
+    let oxide_conf_folder: PathBuf = config_folder_path.join("oxide");
     create_dir_all(oxide_conf_folder.as_path())?;
-    let history_file_path: PathBuf = [oxide_conf_folder.to_str().unwrap(), "history.txt"].iter().collect();
+    let history_file_path: PathBuf = oxide_conf_folder.join("history.txt");
     File::create(history_file_path.as_path())?;
     Ok(())      
 }
-        
-   
+
+// Path of a file in the oxide configuration folder, creating the folder if needed
+pub fn config_file_path(filename: &str) -> Option<PathBuf> {
+    let config_folder = dirs::config_dir()?;
+    let oxide_conf_folder: PathBuf = config_folder.join("oxide");
+    create_dir_all(oxide_conf_folder.as_path()).ok()?;
+    Some(oxide_conf_folder.join(filename))
+}
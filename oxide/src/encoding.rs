@@ -0,0 +1,131 @@
+// Text that isn't valid UTF-8, such as a Latin-1 file name, is carried through
+// the shell's strings with each invalid byte stood in for by a character from
+// a private use block. The bytes are put back when the text leaves the shell
+// as a path, argument or environment variable, and shown as \xNN escapes.
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+// Byte b is carried as the character U+10FE00 + b. Only bytes from 0x80 up
+// can be invalid. Input that really holds one of these characters has its
+// bytes carried the same way, so it can't be mistaken for an escaped byte.
+const ESCAPE_BASE: u32 = 0x10FE00;
+
+fn escape_byte(byte: u8) -> char
+{
+    char::from_u32(ESCAPE_BASE + byte as u32).unwrap()
+}
+
+fn escaped_byte(c: char) -> Option<u8>
+{
+    match (c as u32).checked_sub(ESCAPE_BASE) {
+        Some(byte @ 0x80..=0xFF) => Some(byte as u8),
+        _ => None,
+    }
+}
+
+pub fn from_bytes(bytes: &[u8]) -> String
+{
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks()
+    {
+        for c in chunk.valid().chars()
+        {
+            match escaped_byte(c) {
+                Some(_) => text.extend(c.encode_utf8(&mut [0; 4]).bytes().map(escape_byte)),
+                None => text.push(c),
+            }
+        }
+        text.extend(chunk.invalid().iter().map(|byte| escape_byte(*byte)));
+    }
+    text
+}
+
+pub fn to_bytes(text: &str) -> Vec<u8>
+{
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars()
+    {
+        match escaped_byte(c) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+#[cfg(target_family = "unix")]
+pub fn from_os_str(value: &OsStr) -> String
+{
+    use std::os::unix::ffi::OsStrExt;
+    from_bytes(value.as_bytes())
+}
+
+#[cfg(target_family = "unix")]
+pub fn to_os_string(text: &str) -> OsString
+{
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(to_bytes(text))
+}
+
+// Windows names are UTF-16, where unpaired surrogates are all that can go wrong
+#[cfg(target_family = "windows")]
+pub fn from_os_str(value: &OsStr) -> String
+{
+    value.to_string_lossy().into_owned()
+}
+
+#[cfg(target_family = "windows")]
+pub fn to_os_string(text: &str) -> OsString
+{
+    OsString::from(text)
+}
+
+pub fn to_path(text: &str) -> PathBuf
+{
+    PathBuf::from(to_os_string(text))
+}
+
+// The text with any invalid bytes shown as \xNN, for printing
+pub fn display(text: &str) -> String
+{
+    let mut shown = String::with_capacity(text.len());
+    for c in text.chars()
+    {
+        match escaped_byte(c) {
+            Some(byte) => shown.push_str(&format!("\\x{:02X}", byte)),
+            None => shown.push(c),
+        }
+    }
+    shown
+}
+
+// A path or other OS string for printing, invalid bytes escaped
+pub fn display_os(value: &OsStr) -> String
+{
+    display(&from_os_str(value))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip()
+    {
+        let bytes = b"caf\xe9 \xff\xfe ok \xc3\xa9";
+        let text = from_bytes(bytes);
+        assert_eq!(to_bytes(&text), bytes.to_vec());
+        assert_eq!(display(&text), "caf\\xE9 \\xFF\\xFE ok \u{e9}");
+        assert_eq!(from_bytes(b"plain"), "plain");
+    }
+
+    #[test]
+    fn test_escape_characters_in_input()
+    {
+        let bytes = "x\u{10FE80}y\u{10FEFF}".as_bytes();
+        assert_eq!(to_bytes(&from_bytes(bytes)), bytes.to_vec());
+        // The characters just below the escaped bytes are never used
+        assert_eq!(from_bytes("\u{10FE7F}".as_bytes()), "\u{10FE7F}");
+    }
+}
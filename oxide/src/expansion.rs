@@ -0,0 +1,735 @@
+use std::fs;
+
+use glob::{MatchOptions, Pattern};
+
+use crate::arithmetic;
+use crate::encoding::{from_os_str, to_path};
+use crate::logic::substitute_command;
+use crate::options::{nounset, posix_mode};
+use crate::status::{last_status, pipe_status};
+use crate::variables::{get_variable, is_valid_name, last_background_pid, positional_parameter, positional_parameters, set_variable};
+
+type Result<T> = std::result::Result<T, String>;
+
+const DEFAULT_IFS: &str = " \t\n";
+
+// A word being built during expansion
+#[derive(Default)]
+struct Field {
+    // Text with quotes removed
+    text: String,
+    // The same text as a glob pattern, with quoted characters escaped
+    pattern: String,
+    // Contains unquoted glob characters, so is subject to pathname expansion
+    has_glob: bool,
+    // Contains quoted text, so the field is kept even when it is empty
+    quoted: bool,
+}
+
+struct Expander {
+    fields: Vec<Field>,
+    current: Field,
+    // Split unquoted expansions on IFS, as POSIX requires
+    split_fields: bool,
+}
+
+impl Expander {
+    fn new(split_fields: bool) -> Expander {
+        Expander {
+            fields: Vec::new(),
+            current: Field::default(),
+            split_fields,
+        }
+    }
+
+    fn push_literal(&mut self, text: &str) {
+        self.current.text.push_str(text);
+        self.current.pattern.push_str(&Pattern::escape(text));
+    }
+
+    fn push_unquoted(&mut self, c: char) {
+        self.current.text.push(c);
+        self.current.pattern.push(c);
+        if c == '*' || c == '?' || c == '[' {
+            self.current.has_glob = true;
+        }
+    }
+
+    // Adds the value of a parameter, splitting it into fields when it is unquoted
+    fn push_expansion(&mut self, value: &str, quoted: bool) {
+        if quoted {
+            self.current.quoted = true;
+            self.push_literal(value);
+            return;
+        }
+        if !self.split_fields {
+            self.push_literal(value);
+            return;
+        }
+
+        let ifs = get_variable("IFS").unwrap_or_else(|| DEFAULT_IFS.to_string());
+        for c in value.chars()
+        {
+            if ifs.contains(c) {
+                // Whitespace delimiters collapse, other delimiters always end a field
+                if !c.is_whitespace() || !self.current.text.is_empty() || self.current.quoted {
+                    self.end_field(!c.is_whitespace());
+                }
+            } else {
+                self.push_unquoted(c);
+            }
+        }
+    }
+
+    fn end_field(&mut self, keep_empty: bool) {
+        let field = std::mem::take(&mut self.current);
+        if keep_empty || !field.text.is_empty() || field.quoted {
+            self.fields.push(field);
+        }
+    }
+}
+
+// Expands raw words from the lexer into the arguments to run: tilde,
+// parameter, command and arithmetic expansion, field splitting (POSIX mode
+// only), pathname expansion and quote removal.
+pub fn expand_words(raw_words: &[String]) -> Result<Vec<String>>
+{
+    let mut words = Vec::new();
+    for raw_word in raw_words
+    {
+        if raw_word == "\"$@\"" && positional_parameters().is_empty() {
+            continue
+        }
+
+        let mut expander = Expander::new(posix_mode());
+        expand_into(raw_word, &mut expander)?;
+        expander.end_field(false);
+
+        for field in expander.fields
+        {
+            words.extend(expand_pathname(field));
+        }
+    }
+    Ok(words)
+}
+
+// Expands a single word without field splitting or pathname expansion, as used
+// for variable assignments and redirection targets
+pub fn expand_string(raw_word: &str) -> Result<String>
+{
+    let mut expander = Expander::new(false);
+    expand_into(raw_word, &mut expander)?;
+    expander.end_field(true);
+
+    let fields: Vec<String> = expander.fields.into_iter().map(|field| field.text).collect();
+    Ok(fields.join(" "))
+}
+
+// Expands a case pattern into a glob pattern, escaping any quoted characters
+// so they only match themselves
+pub fn expand_pattern(raw_word: &str) -> Result<String>
+{
+    let mut expander = Expander::new(false);
+    expand_into(raw_word, &mut expander)?;
+    expander.end_field(true);
+
+    let patterns: Vec<String> = expander.fields.into_iter().map(|field| field.pattern).collect();
+    Ok(patterns.join(" "))
+}
+
+// Splits a `NAME=value` assignment word into the name and the raw value
+pub fn split_assignment(raw_word: &str) -> Option<(&str, &str)>
+{
+    let index = raw_word.find('=')?;
+    let name = &raw_word[..index];
+    if is_valid_name(name) {
+        Some((name, &raw_word[index + 1..]))
+    } else {
+        None
+    }
+}
+
+fn expand_pathname(field: Field) -> Vec<String>
+{
+    if !field.has_glob {
+        return vec!(field.text)
+    }
+
+    let matches = glob_paths(&field.pattern);
+
+    if matches.is_empty() {
+        // Patterns that match nothing are left as they were
+        vec!(field.text)
+    } else {
+        matches
+    }
+}
+
+// Matches a glob pattern against the file system one path component at a
+// time, in sorted order. Names that aren't valid UTF-8 are matched in their
+// escaped form, which the glob crate's own walker can't do.
+fn glob_paths(pattern: &str) -> Vec<String>
+{
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: true,
+    };
+    let mut paths = vec!(String::from(if pattern.starts_with('/') { "/" } else { "" }));
+    let components: Vec<&str> = pattern.split('/').filter(|component| !component.is_empty()).collect();
+    for (index, component) in components.iter().enumerate()
+    {
+        let is_last = index + 1 == components.len();
+        let join = |parent: &str, name: &str| match parent {
+            "" => name.to_string(),
+            "/" => format!("/{}", name),
+            parent => format!("{}/{}", parent, name),
+        };
+        // Directory listings leave out . and ..
+        if *component == "." || *component == ".."
+        {
+            paths = paths.iter().map(|parent| join(parent, component)).collect();
+            continue
+        }
+        let component_pattern = match Pattern::new(component) {
+            Ok(component_pattern) => component_pattern,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut matches = Vec::new();
+        for parent in &paths
+        {
+            let entries = match fs::read_dir(to_path(if parent.is_empty() { "." } else { parent })) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| is_last || entry.path().is_dir())
+                .map(|entry| from_os_str(&entry.file_name()))
+                .filter(|name| component_pattern.matches_with(name, options))
+                .collect();
+            names.sort();
+            matches.extend(names.iter().map(|name| join(parent, name)));
+        }
+        paths = matches;
+    }
+
+    // A trailing slash only matches directories, and keeps the slash
+    if pattern.ends_with('/')
+    {
+        return paths.into_iter()
+            .filter(|path| to_path(path).is_dir())
+            .map(|path| format!("{}/", path))
+            .collect()
+    }
+    paths
+}
+
+fn expand_into(raw_word: &str, expander: &mut Expander) -> Result<()>
+{
+    let chars: Vec<char> = raw_word.chars().collect();
+    let mut index = 0;
+
+    if chars.first() == Some(&'~') && (chars.len() == 1 || chars[1] == '/') {
+        if let Some(home) = dirs::home_dir() {
+            expander.push_literal(&from_os_str(home.as_os_str()));
+            index = 1;
+        }
+    }
+
+    while index < chars.len()
+    {
+        match chars[index] {
+            '\'' => {
+                expander.current.quoted = true;
+                index += 1;
+                let mut quoted = String::new();
+                while index < chars.len() && chars[index] != '\''
+                {
+                    quoted.push(chars[index]);
+                    index += 1;
+                }
+                expander.push_literal(&quoted);
+                index += 1;
+            }
+            '"' => {
+                expander.current.quoted = true;
+                index += 1;
+                while index < chars.len() && chars[index] != '"'
+                {
+                    match chars[index] {
+                        '\\' if index + 1 < chars.len() => {
+                            // A backslash and newline join the lines
+                            match chars[index + 1] {
+                                '\n' => (),
+                                escaped if "$`\"\\".contains(escaped) => expander.push_literal(&escaped.to_string()),
+                                escaped => expander.push_literal(&format!("\\{}", escaped)),
+                            }
+                            index += 2;
+                        }
+                        '$' => expand_parameter(&chars, &mut index, true, expander)?,
+                        '`' => expand_backquoted(&chars, &mut index, true, expander),
+                        c => {
+                            expander.push_literal(&c.to_string());
+                            index += 1;
+                        }
+                    }
+                }
+                index += 1;
+            }
+            '\\' => {
+                if let Some(escaped) = chars.get(index + 1) {
+                    expander.push_literal(&escaped.to_string());
+                }
+                index += 2;
+            }
+            '$' => expand_parameter(&chars, &mut index, false, expander)?,
+            '`' => expand_backquoted(&chars, &mut index, false, expander),
+            c => {
+                expander.push_unquoted(c);
+                index += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Expands the parameter starting at the '$' at `index`
+fn expand_parameter(chars: &[char], index: &mut usize, quoted: bool, expander: &mut Expander) -> Result<()>
+{
+    let start = *index;
+    *index += 1;
+
+    match chars.get(*index) {
+        Some('{') => {
+            let close = match matching_close(chars, *index) {
+                Some(close) => close,
+                None => return Err(String::from("bad substitution: missing '}'")),
+            };
+            let inner: String = chars[*index + 1..close].iter().collect();
+            *index = close + 1;
+            if let Some((name, subscript)) = split_subscript(&inner) {
+                expand_array(name, subscript, quoted, expander)?;
+            } else if inner == "@" || inner == "*" {
+                push_all_values(&positional_parameters(), inner == "@", quoted, expander);
+            } else {
+                let value = expand_braced_parameter(&inner)?;
+                expander.push_expansion(&value, quoted);
+            }
+        }
+        Some('(') => {
+            let close = match matching_close(chars, *index) {
+                Some(close) => close,
+                None => return Err(String::from("bad substitution: missing ')'")),
+            };
+            // $((...)) is arithmetic, unless the inner parentheses close
+            // before the end, as in the command $((cd /); ls)
+            let arithmetic = chars.get(*index + 1) == Some(&'(')
+                && matching_close(chars, *index + 1) == Some(close - 1);
+            if arithmetic {
+                let expression: String = chars[*index + 2..close - 1].iter().collect();
+                let value = arithmetic::evaluate(&expand_string(&expression)?)?;
+                expander.push_expansion(&value.to_string(), quoted);
+            } else {
+                let source: String = chars[*index + 1..close].iter().collect();
+                expander.push_expansion(&substitute_command(&source), quoted);
+            }
+            *index = close + 1;
+        }
+        Some('\'') if !quoted && !posix_mode() => {
+            *index += 1;
+            let value = read_ansi_c_string(chars, index);
+            expander.current.quoted = true;
+            expander.push_literal(&value);
+        }
+        Some('@') | Some('*') => {
+            let all_separate = chars[*index] == '@';
+            *index += 1;
+            push_all_values(&positional_parameters(), all_separate, quoted, expander);
+        }
+        Some(c) if c.is_ascii_digit() || "#$?!".contains(*c) => {
+            let name = c.to_string();
+            *index += 1;
+            let value = required_value(&name)?;
+            expander.push_expansion(&value, quoted);
+        }
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let mut name = String::new();
+            while *index < chars.len() && (chars[*index].is_ascii_alphanumeric() || chars[*index] == '_')
+            {
+                name.push(chars[*index]);
+                *index += 1;
+            }
+            let value = required_value(&name)?;
+            expander.push_expansion(&value, quoted);
+        }
+        _ => {
+            // A lone '$' is literal
+            *index = start + 1;
+            if quoted {
+                expander.push_literal("$");
+            } else {
+                expander.push_unquoted('$');
+            }
+        }
+    }
+    Ok(())
+}
+
+// The index of the parenthesis or brace that closes the one at `open`,
+// skipping over quoted text
+fn matching_close(chars: &[char], open: usize) -> Option<usize>
+{
+    let (opening, closing) = (chars[open], if chars[open] == '{' { '}' } else { ')' });
+    let mut depth = 0;
+    let mut index = open;
+    while index < chars.len()
+    {
+        match chars[index] {
+            '\\' => index += 1,
+            '\'' => index += chars[index + 1..].iter().position(|c| *c == '\'')? + 1,
+            '"' => {
+                index += 1;
+                while index < chars.len() && chars[index] != '"'
+                {
+                    if chars[index] == '\\' {
+                        index += 1;
+                    }
+                    index += 1;
+                }
+            }
+            c if c == opening => depth += 1,
+            c if c == closing => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index)
+                }
+            }
+            _ => (),
+        }
+        index += 1;
+    }
+    None
+}
+
+// Runs the command substitution `...` starting at `index`. Backslashes only
+// escape $, ` and \, and " as well inside double quotes.
+fn expand_backquoted(chars: &[char], index: &mut usize, quoted: bool, expander: &mut Expander)
+{
+    let mut source = String::new();
+    *index += 1;
+    while *index < chars.len() && chars[*index] != '`'
+    {
+        if chars[*index] == '\\' && *index + 1 < chars.len() {
+            let escaped = chars[*index + 1];
+            if "$`\\".contains(escaped) || (quoted && escaped == '"') {
+                *index += 1;
+            }
+        }
+        source.push(chars[*index]);
+        *index += 1;
+    }
+    *index += 1;
+    expander.push_expansion(&substitute_command(&source), quoted);
+}
+
+// The value of a parameter expanded as it is, which set -u makes an error
+// when the parameter isn't set
+fn required_value(name: &str) -> Result<String>
+{
+    match parameter_value(name) {
+        Some(value) => Ok(value),
+        None if nounset() => Err(format!("{}: unbound variable", name)),
+        None => Ok(String::new()),
+    }
+}
+
+fn parameter_value(name: &str) -> Option<String>
+{
+    match name {
+        "#" => Some(positional_parameters().len().to_string()),
+        "$" => Some(std::process::id().to_string()),
+        "?" => Some(last_status().to_string()),
+        "!" => last_background_pid().map(|pid| pid.to_string()),
+        "PIPESTATUS" => pipe_status().first().map(|status| status.to_string()),
+        "@" | "*" => Some(positional_parameters().join(" ")),
+        _ if name.chars().all(|c| c.is_ascii_digit()) => {
+            name.parse().ok().and_then(positional_parameter)
+        }
+        _ => get_variable(name),
+    }
+}
+
+// $@, $* and ${name[@]}. Quoted "$@" gives one field per value and quoted "$*"
+// joins them with the first character of IFS.
+fn push_all_values(parameters: &[String], all_separate: bool, quoted: bool, expander: &mut Expander)
+{
+    if quoted && !all_separate {
+        let ifs = get_variable("IFS").unwrap_or_else(|| DEFAULT_IFS.to_string());
+        let separator: String = ifs.chars().take(1).collect();
+        expander.push_expansion(&parameters.join(&separator), true);
+        return;
+    }
+
+    for (position, parameter) in parameters.iter().enumerate()
+    {
+        if position > 0 {
+            expander.end_field(quoted);
+            expander.current.quoted = quoted;
+        }
+        expander.push_expansion(parameter, quoted);
+    }
+}
+
+// Splits `name[subscript]` or `#name[subscript]`
+fn split_subscript(inner: &str) -> Option<(&str, &str)>
+{
+    let open = inner.find('[')?;
+    if !inner.ends_with(']') || !is_valid_name(inner[..open].trim_start_matches('#')) {
+        return None
+    }
+    Some((&inner[..open], &inner[open + 1..inner.len() - 1]))
+}
+
+// PIPESTATUS is the only array, other variables act as one element arrays
+fn array_values(name: &str) -> Vec<String>
+{
+    match name {
+        "PIPESTATUS" => pipe_status().iter().map(|status| status.to_string()).collect(),
+        _ => get_variable(name).into_iter().collect(),
+    }
+}
+
+// ${name[n]}, ${name[@]}, ${name[*]} and their ${#name[...]} lengths
+fn expand_array(name: &str, subscript: &str, quoted: bool, expander: &mut Expander) -> Result<()>
+{
+    let element = |values: &[String]| -> Result<String> {
+        let index: usize = subscript.parse().map_err(|_| format!("{}: bad array subscript", subscript))?;
+        Ok(values.get(index).cloned().unwrap_or_default())
+    };
+
+    if let Some(name) = name.strip_prefix('#') {
+        let values = array_values(name);
+        let length = match subscript {
+            "@" | "*" => values.len(),
+            _ => element(&values)?.chars().count(),
+        };
+        expander.push_expansion(&length.to_string(), quoted);
+        return Ok(())
+    }
+
+    let values = array_values(name);
+    match subscript {
+        "@" | "*" => push_all_values(&values, subscript == "@", quoted, expander),
+        _ => expander.push_expansion(&element(&values)?, quoted),
+    }
+    Ok(())
+}
+
+// ${name}, ${#name}, the ${name:-word}, ${name:=word}, ${name:+word} and
+// ${name:?word} forms, with or without the colon, and ${name%pattern},
+// ${name%%pattern}, ${name#pattern} and ${name##pattern}
+fn expand_braced_parameter(inner: &str) -> Result<String>
+{
+    if inner.len() > 1 && inner.starts_with('#') {
+        let value = required_value(&inner[1..])?;
+        return Ok(value.chars().count().to_string())
+    }
+
+    let name_length = match inner.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            inner.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(inner.len())
+        }
+        Some(c) if c.is_ascii_digit() => {
+            inner.find(|c: char| !c.is_ascii_digit()).unwrap_or(inner.len())
+        }
+        Some('#') | Some('$') | Some('?') | Some('!') => 1,
+        _ => return Err(format!("${{{}}}: bad substitution", inner)),
+    };
+    let name = &inner[..name_length];
+    let operation = &inner[name_length..];
+    let value = parameter_value(name);
+
+    if operation.is_empty() {
+        return required_value(name)
+    }
+    for operator in ["%%", "%", "##", "#"]
+    {
+        if let Some(pattern) = operation.strip_prefix(operator) {
+            return remove_pattern(&required_value(name)?, &expand_pattern(pattern)?, operator)
+        }
+    }
+
+    let check_null = operation.starts_with(':');
+    let operation = operation.trim_start_matches(':');
+    let unset = match &value {
+        None => true,
+        Some(value) => check_null && value.is_empty(),
+    };
+    let operator = operation.chars().next().unwrap_or(' ');
+    let word = &operation[operator.len_utf8().min(operation.len())..];
+
+    match operator {
+        '-' => if unset { expand_string(word) } else { Ok(value.unwrap_or_default()) },
+        '+' => if unset { Ok(String::new()) } else { expand_string(word) },
+        '=' => {
+            if unset {
+                if !is_valid_name(name) {
+                    return Err(format!("${}: cannot assign in this way", name))
+                }
+                let word = expand_string(word)?;
+                set_variable(name, &word)?;
+                Ok(word)
+            } else {
+                Ok(value.unwrap_or_default())
+            }
+        }
+        '?' => {
+            if unset {
+                let message = expand_string(word)?;
+                if message.is_empty() {
+                    Err(format!("{}: parameter null or not set", name))
+                } else {
+                    Err(format!("{}: {}", name, message))
+                }
+            } else {
+                Ok(value.unwrap_or_default())
+            }
+        }
+        _ => Err(format!("${{{}}}: bad substitution", inner)),
+    }
+}
+
+// Removes the shortest (% and #) or longest (%% and ##) match of a glob
+// pattern from the end (%) or start (#) of a value
+fn remove_pattern(value: &str, pattern: &str, operator: &str) -> Result<String>
+{
+    let pattern = Pattern::new(pattern).map_err(|err| format!("{}: {}", pattern, err))?;
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+    let mut boundaries: Vec<usize> = value.char_indices().map(|(index, _)| index).chain([value.len()]).collect();
+    // Tried from the shortest match to the longest
+    if operator == "%" || operator == "##" {
+        boundaries.reverse();
+    }
+    for boundary in boundaries
+    {
+        let (start, end) = value.split_at(boundary);
+        let (matched, rest) = if operator.starts_with('%') { (end, start) } else { (start, end) };
+        if pattern.matches_with(matched, options) {
+            return Ok(rest.to_string())
+        }
+    }
+    Ok(value.to_string())
+}
+
+// Reads the body of $'...', decoding backslash escapes, and moves past the quote
+fn read_ansi_c_string(chars: &[char], index: &mut usize) -> String
+{
+    let mut value = String::new();
+    while *index < chars.len() && chars[*index] != '\''
+    {
+        let c = chars[*index];
+        *index += 1;
+        if c != '\\' || *index == chars.len() {
+            value.push(c);
+            continue
+        }
+
+        let escaped = chars[*index];
+        *index += 1;
+        match escaped {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            'a' => value.push('\x07'),
+            'b' => value.push('\x08'),
+            'e' | 'E' => value.push('\x1b'),
+            'f' => value.push('\x0c'),
+            'v' => value.push('\x0b'),
+            'x' | '0'..='7' => {
+                let (radix, max_digits) = if escaped == 'x' { (16, 2) } else { (8, 3) };
+                let mut digits = String::new();
+                if escaped != 'x' {
+                    digits.push(escaped);
+                }
+                while digits.len() < max_digits && *index < chars.len() && chars[*index].is_digit(radix)
+                {
+                    digits.push(chars[*index]);
+                    *index += 1;
+                }
+                match u32::from_str_radix(&digits, radix).ok().and_then(std::char::from_u32) {
+                    Some(decoded) => value.push(decoded),
+                    None => {
+                        value.push('\\');
+                        value.push(escaped);
+                    }
+                }
+            }
+            '\\' | '\'' | '"' | '?' => value.push(escaped),
+            other => {
+                value.push('\\');
+                value.push(other);
+            }
+        }
+    }
+    *index += 1;
+    value
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(raw_words: &[&str]) -> Vec<String>
+    {
+        let raw_words: Vec<String> = raw_words.iter().map(|word| word.to_string()).collect();
+        expand_words(&raw_words).unwrap()
+    }
+
+    #[test]
+    fn test_quote_removal()
+    {
+        assert_eq!(words(&["'my file'", "\"a \\\"b\\\" \\n\"", "x\\ y", "\"\"", "''"]),
+                   vec!("my file", "a \"b\" \\n", "x y", "", ""));
+        assert_eq!(words(&["$'tab\\there'"]), vec!("tab\there"));
+    }
+
+    #[test]
+    fn test_parameter_expansion()
+    {
+        set_variable("OXIDE_TEST_WORDS", "one  two").unwrap();
+        assert_eq!(words(&["$OXIDE_TEST_WORDS", "\"${OXIDE_TEST_WORDS}\"", "'$OXIDE_TEST_WORDS'"]),
+                   vec!("one  two", "one  two", "$OXIDE_TEST_WORDS"));
+        assert_eq!(words(&["${OXIDE_TEST_UNSET:-default value}", "${#OXIDE_TEST_WORDS}", "$OXIDE_TEST_UNSET"]),
+                   vec!("default value", "8"));
+        assert!(expand_string("${OXIDE_TEST_UNSET:?missing}").is_err());
+        assert_eq!(expand_string("${OXIDE_TEST_UNSET:-${OXIDE_TEST_WORDS}}"), Ok(String::from("one  two")));
+        assert_eq!(split_assignment("NAME='a b'"), Some(("NAME", "'a b'")));
+        assert_eq!(split_assignment("'NAME'=x"), None);
+    }
+
+    #[test]
+    fn test_pattern_removal()
+    {
+        set_variable("OXIDE_TEST_PATH", "dir/archive.tar.gz").unwrap();
+        assert_eq!(words(&["${OXIDE_TEST_PATH%.*}", "${OXIDE_TEST_PATH%%.*}", "${OXIDE_TEST_PATH#*/}", "${OXIDE_TEST_PATH##*.}"]),
+                   vec!("dir/archive.tar", "dir/archive", "archive.tar.gz", "gz"));
+        assert_eq!(words(&["${OXIDE_TEST_PATH%'.gz'}", "${OXIDE_TEST_PATH#x}", "\"${OXIDE_TEST_PATH%\"*\"}\""]),
+                   vec!("dir/archive.tar", "dir/archive.tar.gz", "dir/archive.tar.gz"));
+    }
+
+    #[test]
+    fn test_array_expansion()
+    {
+        crate::status::set_pipe_status(vec!(1, 0, 141));
+        assert_eq!(words(&["\"${PIPESTATUS[@]}\"", "${PIPESTATUS[2]}", "${#PIPESTATUS[@]}", "$PIPESTATUS"]),
+                   vec!("1", "0", "141", "141", "3", "1"));
+        assert!(expand_string("${PIPESTATUS[x]}").is_err());
+    }
+}
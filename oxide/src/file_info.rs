@@ -0,0 +1,256 @@
+// How file details are shown by list
+#[cfg(target_family = "unix")]
+use std::collections::HashMap;
+#[cfg(target_family = "unix")]
+use std::ffi::CStr;
+#[cfg(target_family = "unix")]
+use std::sync::Mutex;
+
+use chrono::format::{Item, StrftimeItems};
+
+#[cfg(target_family = "unix")]
+use crate::encoding::from_bytes;
+
+#[cfg(target_family = "unix")]
+lazy_static! {
+    // Looking names up can mean reading /etc/passwd or asking a directory
+    // service, so each is only looked up once
+    static ref USER_NAMES: Mutex<HashMap<u32, String>> = Mutex::new(HashMap::new());
+    static ref GROUP_NAMES: Mutex<HashMap<u32, String>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Units {
+    // Powers of 1000: kB, MB, ...
+    Si,
+    // Powers of 1024: KiB, MiB, ...
+    Iec,
+    Bytes,
+}
+
+// Scales a size to the largest unit it reaches, with a decimal place for
+// single digits, like 4.1 kB or 512 MiB
+pub fn format_size(size: u64, units: Units) -> String
+{
+    let (base, names) = match units {
+        Units::Si => (1000.0, ["B", "kB", "MB", "GB", "TB", "PB", "EB"]),
+        Units::Iec => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+        Units::Bytes => return size.to_string(),
+    };
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    // Move up a unit before rounding would show a whole one, so 999.7 kB is 1.0 MB
+    while value >= base - 0.5 && unit + 1 < names.len()
+    {
+        value /= base;
+        unit += 1;
+    }
+    match (unit, value < 9.95) {
+        (0, _) => format!("{} B", size),
+        (_, true) => format!("{:.1} {}", value, names[unit]),
+        (_, false) => format!("{:.0} {}", value, names[unit]),
+    }
+}
+
+// The type and permissions in a mode as ls shows them, like drwxr-xr-x
+#[cfg(target_family = "unix")]
+pub fn format_mode(mode: u32) -> String
+{
+    let file_type = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o010000 => 'p',
+        0o140000 => 's',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        _ => '-',
+    };
+    let mut string = String::from(file_type);
+
+    // setuid, setgid and sticky bits replace the execute permission they go
+    // with, in capitals when it isn't set
+    let special = [(0o4000, 's'), (0o2000, 's'), (0o1000, 't')];
+    for (index, (special_bit, special_char)) in special.iter().enumerate()
+    {
+        let shift = 6 - index * 3;
+        let bits = (mode >> shift) & 0o7;
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 0o1 != 0, mode & special_bit != 0) {
+            (true, true) => *special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+// How long ago something happened in the largest whole unit, like
+// "3 hours ago", or how long until it does for times in the future
+pub fn format_age(seconds: i64) -> String
+{
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    let (amount, unit) = match seconds.abs() {
+        age if age < MINUTE => return String::from("just now"),
+        age if age < HOUR => (age / MINUTE, "minute"),
+        age if age < DAY => (age / HOUR, "hour"),
+        age if age < 7 * DAY => (age / DAY, "day"),
+        age if age < 30 * DAY => (age / (7 * DAY), "week"),
+        age if age < 365 * DAY => (age / (30 * DAY), "month"),
+        age => (age / (365 * DAY), "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    match seconds < 0 {
+        true => format!("in {} {}{}", amount, unit, plural),
+        false => format!("{} {}{} ago", amount, unit, plural),
+    }
+}
+
+// Space between the columns of a grid
+pub const COLUMN_GAP: usize = 2;
+
+// Fits names of the given display widths into as many columns as the line
+// has room for, filled top to bottom like ls -C. Returns the number of rows
+// and the width of each column.
+pub fn grid_layout(widths: &[usize], line_width: usize) -> (usize, Vec<usize>)
+{
+    if widths.is_empty()
+    {
+        return (0, Vec::new())
+    }
+
+    // Every name is at least one wide, which bounds how many columns could fit
+    let most_columns = widths.len().min((line_width + COLUMN_GAP) / (1 + COLUMN_GAP)).max(1);
+    for columns in (1..=most_columns).rev()
+    {
+        let rows = widths.len().div_ceil(columns);
+        let column_widths: Vec<usize> = widths.chunks(rows)
+            .map(|column| column.iter().copied().max().unwrap_or(0))
+            .collect();
+        let total = column_widths.iter().sum::<usize>() + COLUMN_GAP * (column_widths.len() - 1);
+        if total <= line_width || columns == 1
+        {
+            return (rows, column_widths)
+        }
+    }
+    unreachable!()
+}
+
+// chrono fails part way through writing a format it doesn't understand, so
+// formats from the user are checked first
+pub fn is_valid_time_format(format: &str) -> bool
+{
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+// The name of a user, or their id when they have none
+#[cfg(target_family = "unix")]
+pub fn user_name(uid: u32) -> String
+{
+    USER_NAMES.lock().unwrap()
+        .entry(uid)
+        .or_insert_with(|| lookup_user(uid).unwrap_or_else(|| uid.to_string()))
+        .clone()
+}
+
+#[cfg(target_family = "unix")]
+pub fn group_name(gid: u32) -> String
+{
+    GROUP_NAMES.lock().unwrap()
+        .entry(gid)
+        .or_insert_with(|| lookup_group(gid).unwrap_or_else(|| gid.to_string()))
+        .clone()
+}
+
+// The reentrant lookups, which keep their results in a buffer of our own
+#[cfg(target_family = "unix")]
+fn lookup_user(uid: u32) -> Option<String>
+{
+    let mut buffer = vec!(0 as libc::c_char; 16384);
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let status = unsafe { libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+    if status != 0 || result.is_null()
+    {
+        return None
+    }
+    let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+    Some(from_bytes(name.to_bytes()))
+}
+
+#[cfg(target_family = "unix")]
+fn lookup_group(gid: u32) -> Option<String>
+{
+    let mut buffer = vec!(0 as libc::c_char; 16384);
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let status = unsafe { libc::getgrgid_r(gid, &mut group, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+    if status != 0 || result.is_null()
+    {
+        return None
+    }
+    let name = unsafe { CStr::from_ptr(group.gr_name) };
+    Some(from_bytes(name.to_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size()
+    {
+        assert_eq!(format_size(512, Units::Si), "512 B");
+        assert_eq!(format_size(4096, Units::Si), "4.1 kB");
+        assert_eq!(format_size(4096, Units::Iec), "4.0 KiB");
+        assert_eq!(format_size(123_456_789, Units::Si), "123 MB");
+        assert_eq!(format_size(999_700, Units::Si), "1.0 MB");
+        assert_eq!(format_size(4096, Units::Bytes), "4096");
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_format_mode()
+    {
+        assert_eq!(format_mode(0o100644), "-rw-r--r--");
+        assert_eq!(format_mode(0o040755), "drwxr-xr-x");
+        assert_eq!(format_mode(0o120777), "lrwxrwxrwx");
+        assert_eq!(format_mode(0o104755), "-rwsr-xr-x");
+        assert_eq!(format_mode(0o041777), "drwxrwxrwt");
+        assert_eq!(format_mode(0o102644), "-rw-r-Sr--");
+    }
+
+    #[test]
+    fn test_format_age()
+    {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(60), "1 minute ago");
+        assert_eq!(format_age(3 * 3600 + 5), "3 hours ago");
+        assert_eq!(format_age(10 * 86400), "1 week ago");
+        assert_eq!(format_age(800 * 86400), "2 years ago");
+        assert_eq!(format_age(-7200), "in 2 hours");
+    }
+
+    #[test]
+    fn test_grid_layout()
+    {
+        // Three names of width 5 need 5 + 2 + 5 + 2 + 5 = 19 columns to share a line
+        assert_eq!(grid_layout(&[5, 5, 5], 19), (1, vec!(5, 5, 5)));
+        assert_eq!(grid_layout(&[5, 5, 5], 18), (2, vec!(5, 5)));
+        assert_eq!(grid_layout(&[3, 9, 2, 4, 1], 15), (3, vec!(9, 4)));
+        assert_eq!(grid_layout(&[3, 9, 2, 4, 1], 14), (5, vec!(9)));
+        assert_eq!(grid_layout(&[40], 10), (1, vec!(40)));
+        assert_eq!(grid_layout(&[], 80), (0, Vec::new()));
+    }
+
+    #[test]
+    fn test_is_valid_time_format()
+    {
+        assert!(is_valid_time_format("%Y-%m-%d %H:%M"));
+        assert!(!is_valid_time_format("%Q"));
+    }
+}
@@ -0,0 +1,374 @@
+use rustyline::history::History;
+
+type Result<T> = std::result::Result<T, String>;
+
+#[derive(Debug, PartialEq)]
+pub struct HistoryExpansion {
+    pub line: String,
+    // Whether any history expansion took place, in which case the line is echoed
+    pub expanded: bool,
+    // Set by the :p modifier, the line is printed and saved but not run
+    pub print_only: bool,
+}
+
+// Expands history references (!!, !n, !-n, !str, !?str?, ^old^new) using the
+// previously entered lines. Runs on the raw line before it is lexed.
+pub fn expand_history(line: &str, history: &History) -> Result<HistoryExpansion>
+{
+    // Lines read when stdin is not a terminal keep their line ending
+    let chars: Vec<char> = line.trim_end_matches(&['\r', '\n'][..]).chars().collect();
+    let mut expansion = HistoryExpansion {
+        line: String::new(),
+        expanded: false,
+        print_only: false,
+    };
+
+    if chars.first() == Some(&'^') {
+        expand_quick_substitution(&chars, history, &mut expansion)?;
+        return Ok(expansion)
+    }
+
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut index = 0;
+    while index < chars.len()
+    {
+        let c = chars[index];
+        match c {
+            '\\' if !in_single_quote => {
+                expansion.line.push(c);
+                if let Some(escaped) = chars.get(index + 1) {
+                    expansion.line.push(*escaped);
+                }
+                index += 2;
+                continue
+            }
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            '!' if !in_single_quote && starts_event(chars.get(index + 1), in_double_quote) => {
+                let text = expand_event(&chars, &mut index, history, &mut expansion)?;
+                expansion.line.push_str(&text);
+                expansion.expanded = true;
+                continue
+            }
+            _ => (),
+        }
+        expansion.line.push(c);
+        index += 1;
+    }
+
+    return Ok(expansion)
+}
+
+fn starts_event(next: Option<&char>, in_double_quote: bool) -> bool
+{
+    match next {
+        None => false,
+        Some(c) if c.is_whitespace() || *c == '=' || *c == '(' => false,
+        Some('"') => !in_double_quote,
+        Some(_) => true,
+    }
+}
+
+// ^old^new^ is shorthand for !!:s/old/new/
+fn expand_quick_substitution(chars: &[char], history: &History, expansion: &mut HistoryExpansion) -> Result<()>
+{
+    let mut index = 1;
+    let old = read_until(chars, &mut index, '^');
+    let new = read_until(chars, &mut index, '^');
+    let rest: String = chars[index..].iter().collect();
+
+    let previous = history.last().ok_or_else(|| String::from("!!: event not found"))?;
+    let substituted = substitute(previous, &old, &new, false)
+        .ok_or_else(|| format!("^{}^{}: substitution failed", old, new))?;
+
+    expansion.line = substituted + &rest;
+    expansion.expanded = true;
+    Ok(())
+}
+
+// Reads characters up to and past the delimiter, or to the end of the line
+fn read_until(chars: &[char], index: &mut usize, delimiter: char) -> String
+{
+    let mut text = String::new();
+    while *index < chars.len()
+    {
+        let c = chars[*index];
+        *index += 1;
+        if c == delimiter {
+            break;
+        }
+        text.push(c);
+    }
+    text
+}
+
+// Expands one history reference starting at the '!' at `index`, leaving `index`
+// just after the reference.
+fn expand_event(
+    chars: &[char],
+    index: &mut usize,
+    history: &History,
+    expansion: &mut HistoryExpansion
+) -> Result<String>
+{
+    let start = *index;
+    *index += 1;
+
+    let event = match chars[*index] {
+        '!' => {
+            *index += 1;
+            history.last()
+        }
+        '$' | '*' | '^' | ':' => history.last(),
+        '-' | '0'..='9' => {
+            let mut number = String::new();
+            number.push(chars[*index]);
+            *index += 1;
+            while *index < chars.len() && chars[*index].is_ascii_digit()
+            {
+                number.push(chars[*index]);
+                *index += 1;
+            }
+            match number.parse::<isize>() {
+                Ok(offset) if offset < 0 => {
+                    let offset = (-offset) as usize;
+                    if offset <= history.len() { history.get(history.len() - offset) } else { None }
+                }
+                Ok(position) if position > 0 => history.get(position as usize - 1),
+                _ => None,
+            }
+        }
+        '?' => {
+            *index += 1;
+            let search = read_until(chars, index, '?');
+            find_event(history, |entry| entry.contains(&search[..]))
+        }
+        _ => {
+            let mut prefix = String::new();
+            while *index < chars.len() && !chars[*index].is_whitespace() && chars[*index] != ':'
+            {
+                prefix.push(chars[*index]);
+                *index += 1;
+            }
+            find_event(history, |entry| entry.starts_with(&prefix[..]))
+        }
+    };
+
+    let reference: String = chars[start..*index].iter().collect();
+    let event = event.ok_or_else(|| format!("{}: event not found", reference))?;
+
+    let mut text = select_words(chars, index, event)?;
+    apply_modifiers(chars, index, &mut text, expansion)?;
+
+    return Ok(text)
+}
+
+fn find_event<F>(history: &History, matches: F) -> Option<&String>
+    where F: Fn(&str) -> bool
+{
+    (0..history.len()).rev()
+        .filter_map(|index| history.get(index))
+        .find(|entry| matches(entry))
+}
+
+// Applies a word designator (:0, :n, :^, :$, :*, :x-y) if one follows the event
+fn select_words(chars: &[char], index: &mut usize, event: &str) -> Result<String>
+{
+    let designator_start = match chars.get(*index) {
+        Some(':') if chars.get(*index + 1).is_some_and(|c| "^$*-".contains(*c) || c.is_ascii_digit()) => *index + 1,
+        Some('^') | Some('$') | Some('*') => *index,
+        _ => return Ok(event.to_string()),
+    };
+    *index = designator_start;
+
+    let words = split_words(event);
+    let last = words.len().saturating_sub(1);
+    let bad_specifier = || format!("{}: bad word specifier", event);
+
+    let (first, end) = match chars[*index] {
+        '^' => {
+            *index += 1;
+            (1, Some(1))
+        }
+        '$' => {
+            *index += 1;
+            (last, Some(last))
+        }
+        '*' => {
+            *index += 1;
+            if words.len() < 2 {
+                return Ok(String::new())
+            }
+            (1, Some(last))
+        }
+        _ => {
+            let first = read_number(chars, index).unwrap_or(0);
+            match chars.get(*index) {
+                Some('*') => {
+                    *index += 1;
+                    (first, Some(last))
+                }
+                Some('-') => {
+                    *index += 1;
+                    if chars.get(*index) == Some(&'$') {
+                        *index += 1;
+                        (first, Some(last))
+                    } else {
+                        // x- abbreviates x-$ without the last word
+                        (first, read_number(chars, index).or_else(|| last.checked_sub(1)))
+                    }
+                }
+                _ => (first, Some(first)),
+            }
+        }
+    };
+
+    match end {
+        Some(end) if first <= end && end < words.len() => Ok(words[first..=end].join(" ")),
+        _ => Err(bad_specifier()),
+    }
+}
+
+fn read_number(chars: &[char], index: &mut usize) -> Option<usize>
+{
+    let mut number = String::new();
+    while *index < chars.len() && chars[*index].is_ascii_digit()
+    {
+        number.push(chars[*index]);
+        *index += 1;
+    }
+    number.parse().ok()
+}
+
+// Applies :s/old/new/, :gs/old/new/ and :p modifiers
+fn apply_modifiers(
+    chars: &[char],
+    index: &mut usize,
+    text: &mut String,
+    expansion: &mut HistoryExpansion
+) -> Result<()>
+{
+    while chars.get(*index) == Some(&':')
+    {
+        match (chars.get(*index + 1), chars.get(*index + 2)) {
+            (Some('p'), _) => {
+                expansion.print_only = true;
+                *index += 2;
+            }
+            (Some('s'), Some(_)) | (Some('g'), Some('s')) => {
+                let global = chars[*index + 1] == 'g';
+                *index += if global { 2 } else { 1 };
+                let delimiter = *chars.get(*index + 1).ok_or_else(|| String::from(":gs: missing delimiter"))?;
+                *index += 2;
+                let old = read_until(chars, index, delimiter);
+                let new = read_until(chars, index, delimiter);
+                *text = substitute(text, &old, &new, global)
+                    .ok_or_else(|| format!(":s{0}{1}{0}{2}{0}: substitution failed", delimiter, old, new))?;
+            }
+            _ => return Ok(()),
+        }
+    }
+    Ok(())
+}
+
+fn substitute(text: &str, old: &str, new: &str, global: bool) -> Option<String>
+{
+    if old.is_empty() || !text.contains(old) {
+        return None
+    }
+    if global {
+        Some(text.replace(old, new))
+    } else {
+        Some(text.replacen(old, new, 1))
+    }
+}
+
+// Splits a history line into words, keeping quoted sections together
+fn split_words(line: &str) -> Vec<String>
+{
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+
+    for c in line.chars()
+    {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+                word.push(c);
+            }
+            None if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            None => {
+                if c == '\'' || c == '"' {
+                    quote = Some(c);
+                }
+                word.push(c);
+            }
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_history() -> History
+    {
+        let mut history = History::new();
+        history.add("cargo tset --release");
+        history.add("git commit -m 'first message'");
+        history.add("ls -la src target");
+        history
+    }
+
+    fn expand(line: &str) -> Result<String>
+    {
+        expand_history(line, &test_history()).map(|expansion| expansion.line)
+    }
+
+    #[test]
+    fn test_event_designators()
+    {
+        assert_eq!(expand("sudo !!").unwrap(), "sudo ls -la src target");
+        assert_eq!(expand("!1").unwrap(), "cargo tset --release");
+        assert_eq!(expand("!-2").unwrap(), "git commit -m 'first message'");
+        assert_eq!(expand("!car").unwrap(), "cargo tset --release");
+        assert_eq!(expand("!?commit?").unwrap(), "git commit -m 'first message'");
+        assert!(expand("!nothing").is_err());
+        assert_eq!(expand("echo '!!' hi!").unwrap(), "echo '!!' hi!");
+    }
+
+    #[test]
+    fn test_word_designators()
+    {
+        assert_eq!(expand("cd !$").unwrap(), "cd target");
+        assert_eq!(expand("!!:0").unwrap(), "ls");
+        assert_eq!(expand("echo !git:$").unwrap(), "echo 'first message'");
+        assert_eq!(expand("echo !!:*").unwrap(), "echo -la src target");
+        assert_eq!(expand("echo !!:1-2").unwrap(), "echo -la src");
+    }
+
+    #[test]
+    fn test_modifiers_and_quick_substitution()
+    {
+        assert_eq!(expand("^src^lib").unwrap(), "ls -la lib target");
+        assert_eq!(expand("!1:s/tset/test/").unwrap(), "cargo test --release");
+        assert!(expand("^nothing^test").is_err());
+
+        let expansion = expand_history("!!:p", &test_history()).unwrap();
+        assert!(expansion.print_only);
+        assert_eq!(expansion.line, "ls -la src target");
+    }
+}
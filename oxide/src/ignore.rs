@@ -0,0 +1,227 @@
+// .gitignore rules, for leaving ignored files out of recursive listings
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::encoding::{from_bytes, from_os_str};
+
+const IGNORE_FILE: &str = ".gitignore";
+
+struct Rule {
+    pattern: Pattern,
+    negated: bool,
+    // A pattern ending in / only matches folders
+    folder_only: bool,
+    // A pattern containing / is matched against the whole path from its
+    // .gitignore's folder, otherwise against the name alone
+    anchored: bool,
+    // Where the .gitignore is, as the folder below the root it applies to,
+    // or the path from the folder above the root down to the root
+    within: PathBuf,
+    lead: PathBuf,
+}
+
+// Paths are given relative to the root of the walk, and rules read later
+// override earlier ones, so ignore files are added from the top down
+pub struct Ignores
+{
+    rules: Vec<Rule>,
+}
+
+impl Ignores
+{
+    // Starts with the rules of the folders above the root, up to the top of
+    // the git repository it is in
+    pub fn new(root: &Path) -> Ignores
+    {
+        let mut ignores = Ignores { rules: Vec::new() };
+        let root = match fs::canonicalize(root) {
+            Ok(root) => root,
+            Err(_) => return ignores,
+        };
+        if root.join(".git").exists()
+        {
+            return ignores
+        }
+
+        let mut ancestors = Vec::new();
+        for ancestor in root.ancestors().skip(1)
+        {
+            ancestors.push(ancestor);
+            if ancestor.join(".git").exists()
+            {
+                for ancestor in ancestors.iter().rev()
+                {
+                    if let Ok(text) = fs::read(ancestor.join(IGNORE_FILE))
+                    {
+                        let lead = root.strip_prefix(ancestor).unwrap_or(&root);
+                        ignores.add_rules(&from_bytes(&text), Path::new(""), lead);
+                    }
+                }
+                break
+            }
+        }
+        ignores
+    }
+
+    // Adds the rules of a folder's .gitignore, the folder being relative to the root
+    pub fn load(&mut self, root: &Path, folder: &Path)
+    {
+        if let Ok(text) = fs::read(root.join(folder).join(IGNORE_FILE))
+        {
+            self.add_rules(&from_bytes(&text), folder, Path::new(""));
+        }
+    }
+
+    pub fn add_rules(&mut self, text: &str, within: &Path, lead: &Path)
+    {
+        for line in text.lines()
+        {
+            if let Some(rule) = parse_rule(line, within, lead)
+            {
+                self.rules.push(rule);
+            }
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_folder: bool) -> bool
+    {
+        // Git's own folder is never listed
+        if is_folder && path.file_name().is_some_and(|name| name == ".git")
+        {
+            return true
+        }
+
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let name = path.file_name().map(from_os_str).unwrap_or_default();
+        for rule in self.rules.iter().rev()
+        {
+            let relative = match path.strip_prefix(&rule.within) {
+                Ok(relative) => rule.lead.join(relative),
+                Err(_) => continue,
+            };
+            if rule.folder_only && !is_folder
+            {
+                continue
+            }
+            let matched = match rule.anchored {
+                true => rule.pattern.matches_with(&slash_path(&relative), options),
+                false => rule.pattern.matches_with(&name, options),
+            };
+            if matched
+            {
+                return !rule.negated
+            }
+        }
+        false
+    }
+}
+
+// Joins the components of a path with /, whatever the platform
+fn slash_path(path: &Path) -> String
+{
+    let components: Vec<String> = path.iter().map(from_os_str).collect();
+    components.join("/")
+}
+
+fn parse_rule(line: &str, within: &Path, lead: &Path) -> Option<Rule>
+{
+    // Trailing spaces are dropped unless escaped with a backslash
+    let mut line = line.trim_end_matches('\r');
+    while line.ends_with(' ') && !line.ends_with("\\ ")
+    {
+        line = &line[..line.len() - 1];
+    }
+    if line.is_empty() || line.starts_with('#')
+    {
+        return None
+    }
+
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (folder_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let anchored = line.contains('/');
+    let line = line.strip_prefix('/').unwrap_or(line);
+
+    // A backslash makes the next character literal, which glob spells [c]
+    let mut pattern = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next()
+    {
+        match c {
+            '\\' => if let Some(escaped) = chars.next() {
+                pattern.push_str(&Pattern::escape(&escaped.to_string()));
+            },
+            c => pattern.push(c),
+        }
+    }
+
+    match Pattern::new(&pattern) {
+        Ok(pattern) => Some(Rule {
+            pattern,
+            negated,
+            folder_only,
+            anchored,
+            within: within.to_path_buf(),
+            lead: lead.to_path_buf(),
+        }),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignores(text: &str) -> Ignores
+    {
+        let mut ignores = Ignores { rules: Vec::new() };
+        ignores.add_rules(text, Path::new(""), Path::new(""));
+        ignores
+    }
+
+    #[test]
+    fn test_names_and_paths()
+    {
+        let ignores = ignores("# build output\n*.o\n/target\ndocs/*.html\nlogs/\n");
+        assert!(ignores.is_ignored(Path::new("main.o"), false));
+        assert!(ignores.is_ignored(Path::new("src/deep/lib.o"), false));
+        assert!(ignores.is_ignored(Path::new("target"), true));
+        assert!(!ignores.is_ignored(Path::new("src/target"), true));
+        assert!(ignores.is_ignored(Path::new("docs/index.html"), false));
+        assert!(!ignores.is_ignored(Path::new("docs/api/index.html"), false));
+        assert!(ignores.is_ignored(Path::new("src/logs"), true));
+        assert!(!ignores.is_ignored(Path::new("logs"), false));
+        assert!(ignores.is_ignored(Path::new(".git"), true));
+        assert!(!ignores.is_ignored(Path::new("main.rs"), false));
+    }
+
+    #[test]
+    fn test_negation_and_nesting()
+    {
+        let mut ignores = ignores("*.log\n!keep.log\n\\#notes\n**/cache\n");
+        ignores.add_rules("/local.txt\n", Path::new("sub"), Path::new(""));
+        assert!(ignores.is_ignored(Path::new("debug.log"), false));
+        assert!(!ignores.is_ignored(Path::new("keep.log"), false));
+        assert!(ignores.is_ignored(Path::new("#notes"), false));
+        assert!(ignores.is_ignored(Path::new("cache"), true));
+        assert!(ignores.is_ignored(Path::new("a/b/cache"), true));
+        assert!(ignores.is_ignored(Path::new("sub/local.txt"), false));
+        assert!(!ignores.is_ignored(Path::new("local.txt"), false));
+
+        // Rules from above the root see the path from their own folder
+        let mut ignores = Ignores { rules: Vec::new() };
+        ignores.add_rules("/project/out\n", Path::new(""), Path::new("project"));
+        assert!(ignores.is_ignored(Path::new("out"), true));
+    }
+}
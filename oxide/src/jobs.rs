@@ -0,0 +1,622 @@
+// Job control: background jobs, process groups and handing the terminal to
+// the foreground job. Only available on unix.
+use std::io::{self, Write};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::options;
+use crate::signals;
+use crate::timer;
+
+// Status of a pipeline stopped by Ctrl-Z, 128 + SIGTSTP
+pub const STOPPED_STATUS: i32 = 128 + libc::SIGTSTP;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+#[derive(Clone, Debug)]
+struct Process {
+    pid: libc::pid_t,
+    // Set once the process has been reaped
+    status: Option<i32>,
+}
+
+#[derive(Clone)]
+pub struct Job {
+    pub id: usize,
+    pub pgid: libc::pid_t,
+    pub command: String,
+    pub state: JobState,
+    processes: Vec<Process>,
+    // Terminal modes saved when the job was stopped, restored by fg
+    tmodes: Option<libc::termios>,
+    // Whether the user has been told about the job's current state
+    notified: bool,
+}
+
+impl Job {
+    pub fn pids(&self) -> Vec<libc::pid_t> {
+        self.processes.iter().map(|process| process.pid).collect()
+    }
+}
+
+// The result of waiting for a single process
+pub enum ProcessState {
+    Exited(i32),
+    // Killed by a signal, and whether it dumped core
+    Killed(libc::c_int, bool),
+    Stopped,
+}
+
+impl ProcessState {
+    // Exit status in the form used for $?
+    pub fn status(&self) -> i32 {
+        match self {
+            ProcessState::Exited(status) => *status,
+            ProcessState::Killed(signal, _) => 128 + signal,
+            ProcessState::Stopped => STOPPED_STATUS,
+        }
+    }
+}
+
+lazy_static! {
+    static ref JOBS: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+
+    // Terminal modes of the shell, restored whenever it takes the terminal back
+    static ref SHELL_TMODES: Mutex<Option<libc::termios>> = Mutex::new(None);
+}
+
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+static EXIT_WARNED: AtomicBool = AtomicBool::new(false);
+
+// Set in a forked copy of the shell, whose job table is emptied the next
+// time it is used
+static FORGET_JOBS: AtomicBool = AtomicBool::new(false);
+
+fn job_table() -> MutexGuard<'static, Vec<Job>>
+{
+    let mut jobs = JOBS.lock().unwrap();
+    if FORGET_JOBS.swap(false, Ordering::SeqCst) {
+        jobs.clear();
+    }
+    jobs
+}
+
+pub fn job_control() -> bool
+{
+    JOB_CONTROL.load(Ordering::SeqCst)
+}
+
+// Background jobs run in a forked shell, which must not manage the terminal
+pub fn disable_job_control()
+{
+    JOB_CONTROL.store(false, Ordering::SeqCst);
+}
+
+// Puts an interactive shell in its own process group in the foreground of
+// the terminal. Job control is left off when stdin is not a terminal.
+pub fn init_job_control()
+{
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) == 0 {
+            return
+        }
+
+        // Wait to be brought to the foreground if started in the background
+        loop {
+            let pgrp = libc::getpgrp();
+            if libc::tcgetpgrp(libc::STDIN_FILENO) == pgrp {
+                break
+            }
+            libc::kill(-pgrp, libc::SIGTTIN);
+        }
+
+        // Left to the foreground job while it owns the terminal
+        for signal in [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU].iter() {
+            signals::ignore_signal(*signal);
+        }
+
+        let pid = libc::getpid();
+        if libc::getpgrp() != pid {
+            libc::setpgid(pid, pid);
+        }
+        libc::tcsetpgrp(libc::STDIN_FILENO, pid);
+        *SHELL_TMODES.lock().unwrap() = terminal_modes();
+    }
+    JOB_CONTROL.store(true, Ordering::SeqCst);
+}
+
+// Makes a command start in the given process group, or a new one led by
+// itself, with the signals the shell ignores put back to their defaults
+pub fn prepare_command(command: &mut Command, pgid: Option<libc::pid_t>)
+{
+    if job_control() {
+        command.process_group(pgid.unwrap_or(0));
+    }
+    if signals::ignoring_signals() {
+        unsafe {
+            command.pre_exec(|| {
+                signals::reset_signals();
+                Ok(())
+            });
+        }
+    }
+}
+
+// Also set from the shell's side, so the group exists before the terminal is
+// handed to it whichever process runs first
+pub fn set_process_group(pid: libc::pid_t, pgid: libc::pid_t)
+{
+    if job_control() {
+        unsafe {
+            libc::setpgid(pid, pgid);
+        }
+    }
+}
+
+fn terminal_modes() -> Option<libc::termios>
+{
+    unsafe {
+        let mut tmodes: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut tmodes) == 0 {
+            Some(tmodes)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn give_terminal(pgid: libc::pid_t, tmodes: Option<&libc::termios>)
+{
+    if !job_control() {
+        return
+    }
+    unsafe {
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        if let Some(tmodes) = tmodes {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, tmodes);
+        }
+    }
+}
+
+// Takes the terminal back for the shell, returning the modes the job left it in
+pub fn take_terminal() -> Option<libc::termios>
+{
+    if !job_control() {
+        return None
+    }
+    let job_tmodes = terminal_modes();
+    unsafe {
+        libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+        if let Some(tmodes) = SHELL_TMODES.lock().unwrap().as_ref() {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, tmodes);
+        }
+    }
+    job_tmodes
+}
+
+// Exit status in the form used for $?, 128 + the signal for killed processes
+pub fn decode_status(status: libc::c_int) -> i32
+{
+    if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        1
+    }
+}
+
+// Waits for a foreground process to exit, or to be stopped when job control
+// is on
+pub fn wait_for_process(pid: libc::pid_t) -> ProcessState
+{
+    let options = if job_control() { libc::WUNTRACED } else { 0 };
+    loop {
+        let mut status = 0;
+        let result = wait_and_record(pid, &mut status, options);
+        if result == -1 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue
+            }
+            return ProcessState::Exited(1)
+        }
+        if libc::WIFSTOPPED(status) {
+            return ProcessState::Stopped
+        }
+        if libc::WIFSIGNALED(status) {
+            return ProcessState::Killed(libc::WTERMSIG(status), libc::WCOREDUMP(status))
+        }
+        return ProcessState::Exited(decode_status(status))
+    }
+}
+
+// waitpid, counting the resources a finished process used towards any
+// pipeline being timed
+fn wait_and_record(pid: libc::pid_t, status: &mut libc::c_int, options: libc::c_int) -> libc::pid_t
+{
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::wait4(pid, status, options, &mut rusage) };
+    if result > 0 && !libc::WIFSTOPPED(*status) {
+        timer::add_child_usage(&timer::usage_from_rusage(&rusage));
+    }
+    result
+}
+
+// Prints how a foreground pipeline was killed. At the prompt an interrupted
+// pipeline also interrupts the loop or list it is part of.
+pub fn report_killed(signal: libc::c_int, core_dumped: bool)
+{
+    if let Some(report) = signals::kill_report(signal, core_dumped) {
+        eprintln!("{}", report);
+    }
+    if signal == libc::SIGINT && options::interactive() {
+        signals::set_interrupted();
+    }
+}
+
+fn next_job_id(jobs: &[Job]) -> usize
+{
+    jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1
+}
+
+// Records a job started in the background, returning its job number
+pub fn add_background_job(pgid: libc::pid_t, pids: Vec<libc::pid_t>, command: &str) -> usize
+{
+    let mut jobs = job_table();
+    let id = next_job_id(&jobs);
+    jobs.push(Job {
+        id,
+        pgid,
+        command: command.to_string(),
+        state: JobState::Running,
+        processes: pids.into_iter().map(|pid| Process { pid, status: None }).collect(),
+        tmodes: None,
+        notified: true,
+    });
+    id
+}
+
+// Records a foreground pipeline that was stopped, given the processes that
+// have not been reaped yet
+pub fn add_stopped_job(pgid: libc::pid_t, pids: Vec<libc::pid_t>, command: &str, tmodes: Option<libc::termios>)
+{
+    let mut jobs = job_table();
+    let id = next_job_id(&jobs);
+    let job = Job {
+        id,
+        pgid,
+        command: command.to_string(),
+        state: JobState::Stopped,
+        processes: pids.into_iter().map(|pid| Process { pid, status: None }).collect(),
+        tmodes,
+        notified: true,
+    };
+    eprintln!("\n{}", format_job(&job, Some('+')));
+    jobs.push(job);
+}
+
+// Checks on every job without blocking, recording any that stopped or finished
+pub fn update_jobs()
+{
+    let mut jobs = job_table();
+    for job in jobs.iter_mut()
+    {
+        if let JobState::Done(_) = job.state {
+            continue
+        }
+        let mut changed = None;
+        for process in job.processes.iter_mut().filter(|process| process.status.is_none())
+        {
+            let mut status = 0;
+            let result = unsafe {
+                libc::waitpid(process.pid, &mut status, libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED)
+            };
+            if result == process.pid {
+                if libc::WIFSTOPPED(status) {
+                    changed = Some(JobState::Stopped);
+                } else if libc::WIFCONTINUED(status) {
+                    changed = Some(JobState::Running);
+                } else {
+                    process.status = Some(decode_status(status));
+                }
+            } else if result == -1 {
+                // Already reaped elsewhere, such as by the wait builtin
+                process.status = Some(process.status.unwrap_or(0));
+            }
+        }
+
+        if job.processes.iter().all(|process| process.status.is_some()) {
+            let status = job.processes.last().and_then(|process| process.status).unwrap_or(0);
+            job.state = JobState::Done(status);
+            job.notified = false;
+        } else if let Some(state) = changed {
+            if state != job.state {
+                job.state = state;
+                job.notified = false;
+            }
+        }
+    }
+}
+
+// Reaps background jobs if any child has changed state, called between
+// commands so finished jobs are not left as zombies
+pub fn reap_jobs()
+{
+    if signals::take_child_changed() {
+        update_jobs();
+    }
+}
+
+// Prints a notice for each job that stopped or finished since the last
+// prompt, forgetting the finished ones
+pub fn notify_jobs()
+{
+    update_jobs();
+    let mut jobs = job_table();
+    let count = jobs.len();
+    for (index, job) in jobs.iter_mut().enumerate()
+    {
+        if !job.notified {
+            eprintln!("{}", format_job(job, current_marker(index, count)));
+            job.notified = true;
+        }
+    }
+    jobs.retain(|job| !matches!(job.state, JobState::Done(_)));
+}
+
+// The most recent job is the current one, %+, and the one before it %-
+fn current_marker(index: usize, count: usize) -> Option<char>
+{
+    if index + 1 == count {
+        Some('+')
+    } else if index + 2 == count {
+        Some('-')
+    } else {
+        None
+    }
+}
+
+pub fn format_job(job: &Job, marker: Option<char>) -> String
+{
+    format!("[{}]{}  {:<24}{}{}", job.id, marker.unwrap_or(' '), format_state(job.state), job.command, background_suffix(job))
+}
+
+// As listed by jobs -l, with the process ids
+pub fn format_job_long(job: &Job, marker: Option<char>) -> String
+{
+    let pids: Vec<String> = job.pids().iter().map(|pid| pid.to_string()).collect();
+    format!(
+        "[{}]{} {} {:<24}{}{}",
+        job.id, marker.unwrap_or(' '), pids.join(" "), format_state(job.state), job.command, background_suffix(job)
+    )
+}
+
+fn format_state(state: JobState) -> String
+{
+    match state {
+        JobState::Running => String::from("Running"),
+        JobState::Stopped => String::from("Stopped"),
+        JobState::Done(0) => String::from("Done"),
+        JobState::Done(status) if status > 128 => {
+            signals::describe_signal(status - 128).map(String::from).unwrap_or_else(|| format!("Exit {}", status))
+        }
+        JobState::Done(status) => format!("Exit {}", status),
+    }
+}
+
+fn background_suffix(job: &Job) -> &'static str
+{
+    if job.state == JobState::Running { " &" } else { "" }
+}
+
+// Lists every job with its current/previous marker, then forgets finished ones
+pub fn job_list() -> Vec<(Job, Option<char>)>
+{
+    update_jobs();
+    let mut jobs = job_table();
+    let count = jobs.len();
+    let list = jobs.iter_mut()
+        .enumerate()
+        .map(|(index, job)| {
+            job.notified = true;
+            (job.clone(), current_marker(index, count))
+        })
+        .collect();
+    jobs.retain(|job| !matches!(job.state, JobState::Done(_)));
+    list
+}
+
+pub fn has_jobs() -> bool
+{
+    update_jobs();
+    job_table().iter().any(|job| !matches!(job.state, JobState::Done(_)))
+}
+
+// Finds a job from a job spec: %n, %+, %%, %, %-, %string or %?string. A
+// plain number is taken as a job number too.
+pub fn find_job(spec: Option<&str>) -> Result<usize, String>
+{
+    let jobs = job_table();
+    let no_such_job = || format!("{}: no such job", spec.unwrap_or("current"));
+    let spec = spec.map(|spec| spec.strip_prefix('%').unwrap_or(spec));
+
+    let job = match spec {
+        None | Some("") | Some("+") | Some("%") => jobs.last(),
+        Some("-") => jobs.iter().rev().nth(1),
+        Some(spec) if spec.chars().all(|c| c.is_ascii_digit()) => {
+            let id: usize = spec.parse().map_err(|_| no_such_job())?;
+            jobs.iter().find(|job| job.id == id)
+        }
+        Some(spec) => {
+            let matching: Vec<&Job> = match spec.strip_prefix('?') {
+                Some(search) => jobs.iter().filter(|job| job.command.contains(search)).collect(),
+                None => jobs.iter().filter(|job| job.command.starts_with(spec)).collect(),
+            };
+            if matching.len() > 1 {
+                return Err(format!("{}: ambiguous job spec", spec))
+            }
+            matching.first().copied()
+        }
+    };
+    job.map(|job| job.id).ok_or_else(no_such_job)
+}
+
+pub fn job_with_pid(pid: libc::pid_t) -> Option<usize>
+{
+    job_table().iter().find(|job| job.pids().contains(&pid)).map(|job| job.id)
+}
+
+fn take_job(id: usize) -> Option<Job>
+{
+    let mut jobs = job_table();
+    let index = jobs.iter().position(|job| job.id == id)?;
+    Some(jobs.remove(index))
+}
+
+// Continues a job in the foreground and waits for it, as fg does
+pub fn foreground_job(id: usize) -> i32
+{
+    let mut job = match take_job(id) {
+        Some(job) => job,
+        None => return 1,
+    };
+    println!("{}", job.command);
+    let _ = io::stdout().flush();
+
+    give_terminal(job.pgid, job.tmodes.as_ref());
+    unsafe {
+        libc::kill(-job.pgid, libc::SIGCONT);
+    }
+    job.state = JobState::Running;
+
+    let mut stopped = false;
+    let mut killed = None;
+    for process in job.processes.iter_mut().filter(|process| process.status.is_none())
+    {
+        match wait_for_process(process.pid) {
+            ProcessState::Stopped => {
+                stopped = true;
+                break
+            }
+            ProcessState::Killed(signal, core_dumped) => {
+                killed.get_or_insert((signal, core_dumped));
+                process.status = Some(128 + signal);
+            }
+            ProcessState::Exited(status) => process.status = Some(status),
+        }
+    }
+    let tmodes = take_terminal();
+    if let Some((signal, core_dumped)) = killed {
+        report_killed(signal, core_dumped);
+    }
+
+    if stopped {
+        job.state = JobState::Stopped;
+        job.tmodes = tmodes;
+        eprintln!("\n{}", format_job(&job, Some('+')));
+        // Back on the end of the list as the current job
+        job_table().push(job);
+        return STOPPED_STATUS
+    }
+    job.processes.last().and_then(|process| process.status).unwrap_or(0)
+}
+
+// Continues a stopped job in the background, as bg does
+pub fn background_job(id: usize) -> Result<String, String>
+{
+    let mut jobs = job_table();
+    let job = jobs.iter_mut().find(|job| job.id == id).ok_or_else(|| format!("%{}: no such job", id))?;
+    if job.state == JobState::Running {
+        return Err(format!("job {} already in background", id))
+    }
+    unsafe {
+        libc::kill(-job.pgid, libc::SIGCONT);
+    }
+    job.state = JobState::Running;
+    job.notified = true;
+    Ok(format!("[{}]+ {} &", job.id, job.command))
+}
+
+// Blocks until a job finishes, as wait does, returning its status
+pub fn wait_for_job(id: usize) -> i32
+{
+    let job = match job_table().iter().find(|job| job.id == id) {
+        Some(job) => job.clone(),
+        None => return 127,
+    };
+
+    let mut status = 0;
+    for process in job.processes.iter()
+    {
+        status = match process.status {
+            Some(status) => status,
+            None => loop {
+                let mut raw_status = 0;
+                let result = wait_and_record(process.pid, &mut raw_status, 0);
+                if result == process.pid {
+                    break decode_status(raw_status)
+                }
+                // Ctrl-C stops the wait but leaves the job running
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    break 127
+                }
+                if signals::interrupted() {
+                    return 128 + libc::SIGINT
+                }
+            },
+        };
+    }
+    take_job(id);
+    status
+}
+
+pub fn all_job_ids() -> Vec<usize>
+{
+    job_table().iter().map(|job| job.id).collect()
+}
+
+// Removes a job from the table without signalling it, as disown does
+pub fn disown_job(id: usize)
+{
+    take_job(id);
+}
+
+// A forked copy of the shell can't wait for the jobs it was copied with.
+// Safe to call between fork and exec.
+pub fn forget_jobs()
+{
+    FORGET_JOBS.store(true, Ordering::SeqCst);
+}
+
+// Sent to stopped jobs when the shell exits, so they are not left behind
+pub fn hang_up_jobs()
+{
+    for job in job_table().iter()
+    {
+        if job.state == JobState::Stopped {
+            unsafe {
+                libc::kill(-job.pgid, libc::SIGHUP);
+                libc::kill(-job.pgid, libc::SIGCONT);
+            }
+        }
+    }
+}
+
+// exit warns once about remaining jobs. Running another command resets this.
+pub fn exit_warned() -> bool
+{
+    EXIT_WARNED.load(Ordering::SeqCst)
+}
+
+pub fn set_exit_warned(warned: bool)
+{
+    EXIT_WARNED.store(warned, Ordering::SeqCst);
+}
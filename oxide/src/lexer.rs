@@ -0,0 +1,468 @@
+use std::fmt;
+
+use crate::options::posix_mode;
+
+type Result<T> = std::result::Result<T, LexError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    message: String,
+    position: usize,
+    // The input ended in the middle of a here-document
+    here_document: bool,
+}
+
+impl LexError {
+    pub fn in_here_document(&self) -> bool {
+        self.here_document
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lex Error at character {0}: {1}", self.position, self.message)
+    }
+}
+
+// Splits input into words and operators. Words are kept raw, with their quotes
+// and escapes, so that expansion can later tell quoted text apart. Operators are
+// split out into their own tokens even when they are not surrounded by
+// whitespace, so `ls>out.txt` lexes the same as `ls > out.txt`. Unquoted
+// newlines become "\n" tokens, which separate commands like ';'. The lines
+// of a here-document follow the newline after its << operator, and replace
+// its delimiter with a word for the text, so `cat <<EOF` becomes `cat << 'text'`.
+pub fn tokenize(input: &str) -> Result<Vec<String>>
+{
+    let mut tokens: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut chars = input.char_indices().peekable();
+    // Indexes of << operators whose documents start after the next newline
+    let mut here_documents: Vec<usize> = Vec::new();
+
+    while let Some((position, c)) = chars.next()
+    {
+        match c {
+            ' ' | '\t' | '\r' => {
+                push_word(&mut tokens, &mut word);
+            }
+            '\n' => {
+                push_word(&mut tokens, &mut word);
+                tokens.push(String::from("\n"));
+                for operator in here_documents.drain(..)
+                {
+                    read_here_document(&mut chars, &mut tokens, operator, position)?;
+                }
+            }
+            '#' if word.is_empty() => {
+                while let Some((_, next)) = chars.peek() {
+                    if *next == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            '\'' => {
+                word.push(c);
+                read_quoted(&mut chars, &mut word, '\'', false, position)?;
+            }
+            '"' => {
+                word.push(c);
+                read_quoted(&mut chars, &mut word, '"', true, position)?;
+            }
+            '\\' => {
+                match chars.next() {
+                    // Line continuation
+                    Some((_, '\n')) => (),
+                    Some((_, escaped)) => {
+                        word.push(c);
+                        word.push(escaped);
+                    }
+                    None => word.push(c),
+                }
+            }
+            '$' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                // Keep ${...} in one word, even if it contains whitespace
+                word.push(c);
+                let mut depth = 0;
+                for (_, next) in chars.by_ref() {
+                    word.push(next);
+                    match next {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => (),
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                if depth != 0 {
+                    return Err(LexError {
+                        message: String::from("missing closing '}'"),
+                        position,
+                        here_document: false,
+                    })
+                }
+            }
+            '$' if chars.peek().map(|(_, next)| *next) == Some('(') => {
+                word.push(c);
+                word.push(chars.next().unwrap().1);
+                read_substitution(&mut chars, &mut word, position)?;
+            }
+            '`' => {
+                word.push(c);
+                read_quoted(&mut chars, &mut word, '`', true, position)?;
+            }
+            '$' if !posix_mode() && chars.peek().map(|(_, next)| *next) == Some('\'') => {
+                // $'...' quoting is not part of POSIX sh, it is handled during expansion
+                word.push(c);
+                let (_, quote) = chars.next().unwrap();
+                word.push(quote);
+                read_quoted(&mut chars, &mut word, '\'', true, position)?;
+            }
+            '<' | '>' => {
+                // A file descriptor number straight before the operator is
+                // part of it, as in 2> or 2>&1
+                let mut operator = String::new();
+                if !word.is_empty() && word.chars().all(|c| c.is_ascii_digit()) {
+                    operator = std::mem::take(&mut word);
+                }
+                push_word(&mut tokens, &mut word);
+                operator.push(c);
+                match chars.peek() {
+                    Some((_, '>')) if c == '>' => operator.push(chars.next().unwrap().1),
+                    Some((_, '<')) if c == '<' => {
+                        operator.push(chars.next().unwrap().1);
+                        if chars.peek().map(|(_, next)| *next) == Some('-') {
+                            operator.push(chars.next().unwrap().1);
+                        }
+                        here_documents.push(tokens.len());
+                    }
+                    Some((_, '&')) => operator.push(chars.next().unwrap().1),
+                    _ => (),
+                }
+                tokens.push(operator);
+            }
+            '|' | '&' | ';' | '(' | ')' => {
+                let doubled = match chars.peek() {
+                    Some((_, next)) => *next == c && "|&;".contains(c),
+                    None => false,
+                };
+                push_word(&mut tokens, &mut word);
+                if doubled {
+                    chars.next();
+                    tokens.push(format!("{0}{0}", c));
+                } else {
+                    tokens.push(c.to_string());
+                }
+            }
+            _ => {
+                word.push(c);
+            }
+        }
+    }
+    push_word(&mut tokens, &mut word);
+    if !here_documents.is_empty() {
+        return Err(LexError {
+            message: String::from("here-document not terminated"),
+            position: input.len(),
+            here_document: true,
+        })
+    }
+
+    return Ok(tokens)
+}
+
+// Reads the lines of the here-document for the << operator at `operator`, up
+// to the one holding just its delimiter. <<- strips leading tabs from every
+// line.
+fn read_here_document(
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    tokens: &mut [String],
+    operator: usize,
+    position: usize
+) -> Result<()>
+{
+    // Without a delimiter the parser reports the missing word
+    let delimiter = match tokens.get(operator + 1) {
+        Some(delimiter) if !is_control_operator(delimiter) => delimiter.clone(),
+        _ => return Ok(()),
+    };
+    let strip_tabs = tokens[operator].ends_with('-');
+    let quoted = delimiter.contains(['\'', '"', '\\']);
+    let delimiter = unquote(&delimiter);
+
+    let mut body = String::new();
+    loop {
+        if chars.peek().is_none() {
+            return Err(LexError {
+                message: format!("here-document not terminated by '{}'", delimiter),
+                position,
+                here_document: true,
+            })
+        }
+        let mut line = String::new();
+        for (_, c) in chars.by_ref() {
+            if c == '\n' {
+                break;
+            }
+            line.push(c);
+        }
+        let line = if strip_tabs { line.trim_start_matches('\t') } else { &line };
+        if line == delimiter {
+            break;
+        }
+        body.push_str(line);
+        body.push('\n');
+    }
+    tokens[operator + 1] = here_document_word(&body, quoted);
+    Ok(())
+}
+
+// The word for the text of a here-document. A quoted delimiter keeps the
+// text as it is, otherwise it is expanded as if it were in double quotes,
+// apart from the double quotes themselves.
+fn here_document_word(body: &str, quoted: bool) -> String
+{
+    if quoted {
+        return quote(body)
+    }
+
+    let mut word = String::from("\"");
+    let mut chars = body.char_indices().peekable();
+    while let Some((position, c)) = chars.next()
+    {
+        match c {
+            '"' => word.push_str("\\\""),
+            '\\' => match chars.next() {
+                Some((_, '"')) => word.push_str("\\\\\\\""),
+                Some((_, escaped)) => {
+                    word.push(c);
+                    word.push(escaped);
+                }
+                None => word.push_str("\\\\"),
+            },
+            // Substitutions are kept whole, quotes and all
+            '$' if chars.peek().map(|(_, next)| *next) == Some('(') => {
+                word.push(c);
+                word.push(chars.next().unwrap().1);
+                let _ = read_substitution(&mut chars, &mut word, position);
+            }
+            '`' => {
+                word.push(c);
+                let _ = read_quoted(&mut chars, &mut word, '`', true, position);
+            }
+            _ => word.push(c),
+        }
+    }
+    word.push('"');
+    word
+}
+
+fn read_quoted(
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    word: &mut String,
+    quote: char,
+    escapes: bool,
+    position: usize
+) -> Result<()>
+{
+    loop {
+        match chars.next() {
+            Some((_, '\\')) if escapes => {
+                word.push('\\');
+                if let Some((_, escaped)) = chars.next() {
+                    word.push(escaped);
+                }
+            }
+            Some((_, c)) if c == quote => {
+                word.push(c);
+                return Ok(())
+            }
+            // Substitutions inside double quotes can have quotes of their own
+            Some((_, '$')) if quote == '"' && chars.peek().map(|(_, next)| *next) == Some('(') => {
+                word.push('$');
+                word.push(chars.next().unwrap().1);
+                read_substitution(chars, word, position)?;
+            }
+            Some((_, '`')) if quote == '"' => {
+                word.push('`');
+                read_quoted(chars, word, '`', true, position)?;
+            }
+            Some((_, c)) => word.push(c),
+            None => return Err(LexError {
+                message: format!("unterminated {} quote", match quote {
+                    '"' => "double",
+                    '`' => "back",
+                    _ => "single",
+                }),
+                position,
+                here_document: false,
+            }),
+        }
+    }
+}
+
+// Reads the rest of a $(...) or $((...)) after its opening parenthesis, up
+// to the one that closes it, skipping over quoted text
+fn read_substitution(
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    word: &mut String,
+    position: usize
+) -> Result<()>
+{
+    let mut depth = 1;
+    while let Some((_, c)) = chars.next()
+    {
+        match c {
+            '\'' => {
+                word.push(c);
+                read_quoted(chars, word, '\'', false, position)?;
+                continue
+            }
+            '"' => {
+                word.push(c);
+                read_quoted(chars, word, '"', true, position)?;
+                continue
+            }
+            '\\' => {
+                word.push(c);
+                if let Some((_, escaped)) = chars.next() {
+                    word.push(escaped);
+                }
+                continue
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => (),
+        }
+        word.push(c);
+        if depth == 0 {
+            return Ok(())
+        }
+    }
+    Err(LexError {
+        message: String::from("missing closing ')'"),
+        position,
+        here_document: false,
+    })
+}
+
+fn push_word(tokens: &mut Vec<String>, word: &mut String)
+{
+    if !word.is_empty() {
+        tokens.push(std::mem::take(word));
+    }
+}
+
+// Whether a token is an operator that ends a command, rather than a word
+pub fn is_control_operator(token: &str) -> bool
+{
+    matches!(token, ";" | ";;" | "&" | "&&" | "||" | "\n" | "(" | ")")
+}
+
+// Quotes a word so that tokenize will read it back as a single, unchanged word
+pub fn quote(word: &str) -> String
+{
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+// Removes the quotes and backslash escapes from a raw word, without expanding
+// it, as when reading back saved alias definitions
+pub fn unquote(word: &str) -> String
+{
+    let mut unquoted = String::new();
+    let mut chars = word.chars();
+
+    while let Some(c) = chars.next()
+    {
+        match c {
+            '\'' => {
+                for quoted in chars.by_ref() {
+                    if quoted == '\'' {
+                        break;
+                    }
+                    unquoted.push(quoted);
+                }
+            }
+            '"' => {
+                while let Some(quoted) = chars.next() {
+                    match quoted {
+                        '"' => break,
+                        '\\' => {
+                            match chars.next() {
+                                Some(escaped) if "\"\\$`".contains(escaped) => unquoted.push(escaped),
+                                Some(other) => {
+                                    unquoted.push('\\');
+                                    unquoted.push(other);
+                                }
+                                None => (),
+                            }
+                        }
+                        _ => unquoted.push(quoted),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    unquoted.push(escaped);
+                }
+            }
+            _ => unquoted.push(c),
+        }
+    }
+
+    return unquoted
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_quotes_and_operators()
+    {
+        let tokens = tokenize("ls -l 'my file' \"a \\\"b\\\"\" x\\ y|sort>>out.txt").unwrap();
+        assert_eq!(tokens, vec!("ls", "-l", "'my file'", "\"a \\\"b\\\"\"", "x\\ y", "|", "sort", ">>", "out.txt"));
+    }
+
+    #[test]
+    fn test_tokenize_lists_and_comments()
+    {
+        let tokens = tokenize("a && b || c; d # comment | e\nf '#' x&").unwrap();
+        assert_eq!(tokens, vec!("a", "&&", "b", "||", "c", ";", "d", "\n", "f", "'#'", "x", "&"));
+        assert!(tokenize("echo 'unterminated").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_redirections()
+    {
+        let tokens = tokenize("cmd 2>&1 >out 2>>err a2>b <&0").unwrap();
+        assert_eq!(tokens, vec!("cmd", "2>&", "1", ">", "out", "2>>", "err", "a2", ">", "b", "<&", "0"));
+    }
+
+    #[test]
+    fn test_tokenize_substitutions()
+    {
+        let tokens = tokenize("echo $(ls -l | grep \")\") \"$(( (1 + 2) * 3 ))\" `date +%s`x;").unwrap();
+        assert_eq!(tokens, vec!("echo", "$(ls -l | grep \")\")", "\"$(( (1 + 2) * 3 ))\"", "`date +%s`x", ";"));
+        assert!(tokenize("echo $(true").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_here_documents()
+    {
+        let tokens = tokenize("cat <<EOF; cat <<-'END'\n\"$x\" \\\" $(echo \"a\")\nEOF\n\t$x\n\tEND\necho").unwrap();
+        assert_eq!(tokens, vec!("cat", "<<", "\"\\\"$x\\\" \\\\\\\" $(echo \"a\")\n\"", ";", "cat", "<<-", "'$x\n'", "\n", "echo"));
+        assert!(tokenize("cat <<EOF\nno end\n").is_err());
+    }
+
+    #[test]
+    fn test_quote_round_trip()
+    {
+        let word = "it's a 'test'";
+        let tokens = tokenize(&quote(word)).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(unquote(&tokens[0]), word);
+    }
+}
@@ -1,108 +1,231 @@
-#[macro_use]
-//extern crate rustyline;
-
-use std::borrow::Cow::{self, Borrowed, Owned};
-use std::collections::HashMap;
-use std::iter::FromIterator;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio, Child};
-use std::io;
-use std::io::Write;
-use std::fs::{File, OpenOptions, read_to_string};
+use std::process::{Command, Stdio, Child, ExitStatus};
+use std::io::{self, ErrorKind, IsTerminal, Read, Write};
+use std::fs::{File, OpenOptions};
 use std::error::Error;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+#[cfg(target_family = "unix")]
+use std::os::unix::io::AsRawFd;
 
-use rustyline::completion::{Completer, FilenameCompleter, Pair};
-use rustyline::config::OutputStreamType;
-use rustyline::{CompletionType, Editor, Config, EditMode, Context};
-use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
-use rustyline::hint::{Hinter, HistoryHinter};
-use rustyline::validate::{self, MatchingBracketValidator, Validator};
+use glob::Pattern;
+use rustyline::Editor;
 use rustyline::error::ReadlineError;
-use rustyline::Helper;
 
 use crate::parser::ParseNodeType;
 use crate::parser::ParseNode;
 use crate::parser::RedirectionOp;
-use crate::parser::parse_input;
+use crate::parser::ListOp;
+use crate::parser::parse_program;
 
-use crate::commands::change_folder::change_folder;
+use crate::alias::{expand_aliases, ALIASES};
 #[cfg(target_family = "unix")]
-use crate::commands::clear::clear;
-#[cfg(target_family = "windows")]
-use crate::commands::clear_windows::clear;
-use crate::commands::exit::exit;
+use crate::blocks::add_block;
+use crate::encoding::{display, display_os, from_bytes, from_os_str, to_os_string, to_path};
+use crate::expansion::{expand_pattern, expand_string, expand_words, split_assignment};
+use crate::history::expand_history;
+use crate::lexer::{tokenize, LexError};
+#[cfg(target_family = "unix")]
+use crate::jobs::{self, ProcessState};
+#[cfg(target_family = "unix")]
+use crate::signals;
+use crate::notify;
+use crate::options::{auto_cd, default_timeout, errexit, interactive, notify_after, pipefail, posix_mode};
+use crate::path_cache::{find_command, is_path, path_commands};
+#[cfg(target_family = "unix")]
+use crate::pty::{self, PtyCapture};
+use crate::status::{last_status, pipe_status, set_last_status, set_pipe_status};
+use crate::timeout::{format_duration, parse_timeout_arguments, Limit, TIMEOUT_STATUS};
+#[cfg(target_family = "unix")]
+use crate::timeout::Watchdog;
+use crate::timer;
+use crate::suggest;
+use crate::traps::{self, TrapCondition};
+use crate::variables::{
+    get_variable, pop_local_scope, push_local_scope, set_last_background_pid, set_positional_parameters, set_variable,
+};
 
+use crate::commands::{Builtin, BuiltinIo};
 use crate::config::OxideHistory;
 
-use crate::ALIASES;
 use crate::BUILTINS;
+use crate::OXIDE_ONLY_BUILTINS;
 use crate::MyHelper;
 
 
 const PROMPT: &str = ">> ";
-const DEBUG: bool = true;
 const EXECUTE_AST: bool = true;
+// Status of a command interrupted by Ctrl-C, 128 + SIGINT
+const INTERRUPTED_STATUS: i32 = 130;
+// A function run in place of commands that can't be found, as in bash
+const NOT_FOUND_HANDLER: &str = "command_not_found_handle";
+// A function run in place of the usual notification for a long command
+const NOTIFY_HANDLER: &str = "long_command_handle";
+
+lazy_static! {
+    // Set OXIDE_DEBUG to print the parse tree and other internals
+    static ref DEBUG: bool = std::env::var_os("OXIDE_DEBUG").is_some();
+
+    static ref FUNCTIONS: Mutex<HashMap<String, ParseNode>> = Mutex::new(HashMap::new());
+
+    // Status of the most recent command substitution, taken by the command
+    // whose words it was in
+    static ref SUBSTITUTION_STATUS: Mutex<Option<i32>> = Mutex::new(None);
+
+    // Foreground pipelines that ran long enough to be announced, with their
+    // status and how long they were waited for, taken by the prompt
+    static ref LONG_COMMANDS: Mutex<Vec<(String, i32, Duration)>> = Mutex::new(Vec::new());
+
+    static ref CONTROL_FLOW: Mutex<ControlFlowState> = Mutex::new(ControlFlowState {
+        pending: None,
+        loop_depth: 0,
+        function_depth: 0,
+        source_depth: 0,
+        condition_depth: 0,
+    });
+}
+
+// Set by the break, continue and return builtins and consumed by the
+// enclosing loop or function
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlFlow {
+    Break(usize),
+    Continue(usize),
+    Return(i32),
+}
+
+struct ControlFlowState {
+    pending: Option<ControlFlow>,
+    loop_depth: usize,
+    function_depth: usize,
+    // Files being run by the . builtin, which return can end
+    source_depth: usize,
+    // Lists whose status is being tested, where failures don't set off ERR
+    condition_depth: usize,
+}
 
 #[derive(Debug)]
-struct CommandData <'a> {
+struct CommandData {
     command: String,
     arguments: Vec<String>,
-    redirection_op: Option<RedirectionOp>,
-    redirection_files: Vec<&'a Path>,
+    redirections: Vec<Redirection>,
+    // NAME=value words before the command
+    assignments: Vec<(String, String)>,
+    lookup: CommandLookup,
+    // Set by a timeout prefix
+    timeout: Option<Limit>,
+    // A compound command or subshell run as this stage in place of a command
+    compound: Option<ParseNode>,
+    // Status of the last command substitution in the words, which a command
+    // made up only of assignments exits with
+    substitution_status: Option<i32>,
+}
+
+// A redirection of one of a command's streams, such as 2>> errors.txt
+#[derive(Debug, PartialEq)]
+struct Redirection {
+    fd: i32,
+    op: RedirectionOp,
+    // Files, a file descriptor number to copy or the text of a here-document
+    targets: Vec<String>,
+}
+
+// What a command name may run, narrowed by the command and builtin builtins
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CommandLookup {
+    Any,
+    SkipFunctions,
+    BuiltinOnly,
 }
 
-impl CommandData <'_> {
-    fn new() -> CommandData<'static> {
+impl CommandData {
+    fn new() -> CommandData {
         CommandData {
             command: "".to_string(),
             arguments: Vec::new(),
-            redirection_op: None,
-            redirection_files: Vec::new(),
+            redirections: Vec::new(),
+            assignments: Vec::new(),
+            lookup: CommandLookup::Any,
+            timeout: None,
+            compound: None,
+            substitution_status: None,
         }
     }
 
-    // Do we really need this???
-    fn has_redirection(&self) -> bool {
-        match self.redirection_op
-        {
-            Some(_) => return true,
-            None => return false,
-        }
-    }
 }
 
 
 
 pub fn run(mut rl: Editor<MyHelper>, oxide_history: OxideHistory) -> Result<(), Box<dyn Error>> {
-    if oxide_history.exists {
-        if rl.load_history(&oxide_history.path).is_err() && DEBUG {
-            println!("Could not find history at: {}", oxide_history.path.display());
-        }
+    if oxide_history.exists && rl.load_history(&oxide_history.path).is_err() && *DEBUG {
+        println!("Could not find history at: {}", oxide_history.path.display());
     }
 
+    // How long the last command ran, when it passed the notify threshold
+    let mut long_command: Option<Duration> = None;
     loop {
-        let prompt = format!("{0} {1}", std::env::current_dir().unwrap().to_str().unwrap(), PROMPT);
-        rl.helper_mut().unwrap().colored_prompt = format!("\x1b[1;32m{}\x1b[0m", prompt);
+        run_pending_traps();
+        #[cfg(target_family = "unix")]
+        jobs::notify_jobs();
+
+        // The folder may have been removed from under the shell
+        let current_dir = match std::env::current_dir() {
+            Ok(current_dir) => display_os(current_dir.as_os_str()),
+            Err(_) => String::from("?"),
+        };
+        // Show the status of the last command when it failed
+        let status = match last_status() {
+            0 => String::new(),
+            status => format!("[{}] ", status),
+        };
+        // and how long it took when that was long enough to be announced
+        let elapsed = match long_command.take() {
+            Some(elapsed) => format!("{} ", notify::format_elapsed(elapsed)),
+            None => String::new(),
+        };
+        let prompt = format!("{0} {1}{2}{3}", current_dir, status, elapsed, PROMPT);
+        rl.helper_mut().unwrap().colored_prompt = format!(
+            "\x1b[1;32m{0} \x1b[1;31m{1}\x1b[1;33m{2}\x1b[1;32m{3}\x1b[0m", current_dir, status, elapsed, PROMPT
+        );
         let readline = rl.readline(&prompt);
 
         //let mut input = String::new();
         match readline {
-            Ok(mut input) => {
-                if DEBUG 
+            Ok(input) => {
+                if *DEBUG 
                 {
                     println!("Read the following: {}", input);
                 }
                 if input.is_empty()
                 {
-                    print!("");
                     continue
                 }
 
+                let input = match expand_history(&input, rl.history()) {
+                    _ if posix_mode() => input,
+                    Ok(expansion) => {
+                        if expansion.expanded || expansion.print_only {
+                            println!("{}", expansion.line);
+                        }
+                        if expansion.print_only {
+                            rl.add_history_entry(expansion.line.trim());
+                            continue
+                        }
+                        expansion.line
+                    }
+                    Err(err) => {
+                        println!("{}", err);
+                        continue
+                    }
+                };
+
+                // Saved before running so the history builtin sees this line
                 rl.add_history_entry(input.as_str().trim());
-                execute_input(&mut input);
                 match rl.save_history(&oxide_history.path) {
                     Ok(_) => {
-                        if DEBUG {
+                        if *DEBUG {
                             println!("History saved.")
                         }
                     },
@@ -114,6 +237,26 @@ pub fn run(mut rl: Editor<MyHelper>, oxide_history: OxideHistory) -> Result<(),
                         }
                     }
                 }
+                #[cfg(target_family = "unix")]
+                let exit_warned = jobs::exit_warned();
+                #[cfg(target_family = "unix")]
+                signals::clear_interrupted();
+
+                // Typed text may hold the characters that stand in for bytes
+                execute_input(&from_bytes(input.as_bytes()));
+                let long_commands = std::mem::take(&mut *LONG_COMMANDS.lock().unwrap());
+                for (command, status, elapsed) in long_commands
+                {
+                    announce_long_command(&command, status, elapsed);
+                    long_command = Some(elapsed);
+                }
+
+                // A second exit straight after the jobs warning exits anyway
+                #[cfg(target_family = "unix")]
+                if exit_warned
+                {
+                    jobs::set_exit_warned(false);
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
@@ -126,473 +269,2152 @@ pub fn run(mut rl: Editor<MyHelper>, oxide_history: OxideHistory) -> Result<(),
                 println!("Error when reading from input: {:?}", err);
                 return Err(Box::new(err))
             }
-        } 
+        } 
+    }
+
+    return Ok(())
+}
+
+
+fn split_input(input: &str) -> Result<Vec<String>, LexError> {
+    let tokens = tokenize(input)?;
+    Ok(expand_aliases(tokens))
+}
+
+// Tells the user a long command has finished, through a long_command_handle
+// function if there is one, called with the command, its status and how many
+// seconds it took
+fn announce_long_command(command: &str, status: i32, elapsed: Duration)
+{
+    let last_status = last_status();
+    let handler = FUNCTIONS.lock().unwrap().get(NOTIFY_HANDLER).cloned();
+    match handler {
+        Some(handler) => {
+            let arguments = vec!(command.to_string(), status.to_string(), elapsed.as_secs().to_string());
+            call_function(&handler, &arguments);
+            set_last_status(last_status);
+        }
+        None => notify::notify_terminal(command, status, elapsed),
+    }
+}
+
+// Runs a script or `-c` command string, returning the exit status. As in sh
+// each complete command is run before the next is parsed, so the commands
+// before a syntax error still run and aliases apply from the next line on.
+pub fn run_script(source: &str) -> i32 {
+    let mut status = 0;
+    let mut command = String::new();
+    let mut lines = source.split_inclusive('\n').peekable();
+    while let Some(line) = lines.next()
+    {
+        command.push_str(line);
+        let ast_root = match parse_input(&command) {
+            Ok(ast_root) => ast_root,
+            // A quote or compound command carries on to the next line
+            Err(err) if err.incomplete && lines.peek().is_some() => continue,
+            Err(err) => {
+                println!("{}", err.message);
+                set_last_status(2);
+                return 2
+            }
+        };
+        command.clear();
+        // Blank lines and comments leave the status alone
+        if ast_root.children.as_ref().is_some_and(|children| !children.is_empty())
+        {
+            status = execute_ast(&ast_root);
+        }
+        if control_flow_pending() || interrupted()
+        {
+            break
+        }
+    }
+    return status
+}
+
+// Runs the commands of a $(...) or `...` substitution in a copy of the shell
+// and returns what they write, less any newlines at the end. Their status
+// becomes $?.
+#[cfg(target_family = "unix")]
+pub fn substitute_command(source: &str) -> String {
+    let (mut reader, writer) = match io::pipe() {
+        Ok(pipe) => pipe,
+        Err(err) => {
+            eprintln!("oxide: {}", err);
+            record_substitution_status(1);
+            return String::new()
+        }
+    };
+    // The copy stays in the shell's process group, in the foreground
+    let shell_group = unsafe { libc::getpgrp() };
+    let streams = [Stream::Inherit, Stream::Owned(writer.into()), Stream::Inherit];
+    let other_end = Some(reader.as_raw_fd());
+    let pid = match fork_stage(streams, Vec::new(), Some(shell_group), other_end, || run_script(source)) {
+        Ok(pid) => pid,
+        Err(err) => {
+            eprintln!("oxide: fork: {}", err);
+            record_substitution_status(1);
+            return String::new()
+        }
+    };
+
+    let mut output = Vec::new();
+    let _ = reader.read_to_end(&mut output);
+    let status = jobs::wait_for_process(pid).status();
+    record_substitution_status(status);
+    from_bytes(&output).trim_end_matches('\n').to_string()
+}
+
+// Without fork the commands run in a new shell, which only sees exported
+// variables
+#[cfg(target_family = "windows")]
+pub fn substitute_command(source: &str) -> String {
+    let mut command = Command::new(std::env::current_exe().unwrap_or_else(|_| PathBuf::from("oxide")));
+    if posix_mode()
+    {
+        command.arg("--posix");
+    }
+    let output = command.arg("-c").arg(source).stderr(Stdio::inherit()).output();
+    match output {
+        Ok(output) => {
+            record_substitution_status(output.status.code().unwrap_or(1));
+            from_bytes(&output.stdout).trim_end_matches(['\r', '\n']).to_string()
+        }
+        Err(err) => {
+            eprintln!("oxide: {}", err);
+            record_substitution_status(1);
+            String::new()
+        }
+    }
+}
+
+fn record_substitution_status(status: i32) {
+    set_last_status(status);
+    *SUBSTITUTION_STATUS.lock().unwrap() = Some(status);
+}
+
+// Why input couldn't be parsed, and whether it could be the start of a
+// command that carries on past the end of it
+struct InputError {
+    message: String,
+    incomplete: bool,
+}
+
+fn parse_input(input: &str) -> Result<ParseNode, InputError> {
+    // The lexer only fails on quotes and braces left open
+    let commands = split_input(input).map_err(|err| InputError { message: err.to_string(), incomplete: true })?;
+    parse_program(&commands).map_err(|err| InputError {
+        message: err.to_string(),
+        incomplete: err.at_end_of_input(&commands),
+    })
+}
+
+fn execute_input(input: &str) -> i32 {
+    if *DEBUG{
+        println!("Executing on following string: {}", input);
+    }
+
+    match parse_input(input) {
+        Ok(ast_root) => execute_ast(&ast_root),
+        Err(err) => {
+            println!("{}", err.message);
+            set_last_status(2);
+            return 2
+        }
+    }
+}
+
+fn execute_ast(ast_root: &ParseNode) -> i32 {
+    let mut status = 0;
+    if EXECUTE_AST
+    {
+        status = execute_command_list(ast_root);
+    }
+    // A loop cut short by Ctrl-C fails as if it had been killed by it
+    if interrupted() && status != INTERRUPTED_STATUS
+    {
+        eprintln!();
+        status = INTERRUPTED_STATUS;
+        set_last_status(status);
+    }
+    if *DEBUG
+    {
+        println!("Parse Tree: {:#?}", ast_root);
+    }
+    return status
+}
+
+// Whether a break, continue or return is waiting to unwind the current list
+fn control_flow_pending() -> bool {
+    CONTROL_FLOW.lock().unwrap().pending.is_some()
+}
+
+// Whether Ctrl-C has interrupted the input being run, which unwinds every
+// list and loop
+#[cfg(target_family = "unix")]
+fn interrupted() -> bool {
+    signals::interrupted()
+}
+
+#[cfg(target_family = "windows")]
+fn interrupted() -> bool {
+    false
+}
+
+// Runs the command string set for a trap, leaving $? as it was. The DEBUG,
+// ERR and RETURN traps don't go off while another trap runs.
+fn run_trap(condition: TrapCondition) {
+    let action = match traps::trap_action(condition) {
+        Some(action) => action,
+        None => return,
+    };
+    let pseudo_signal = !matches!(condition, TrapCondition::Signal(_));
+    if pseudo_signal && traps::running_trap()
+    {
+        return
+    }
+
+    let status = last_status();
+    let statuses = pipe_status();
+    let was_running = traps::set_running_trap(true);
+    execute_input(&action);
+    traps::set_running_trap(was_running);
+    set_last_status(status);
+    set_pipe_status(statuses);
+}
+
+// Runs the traps of any signals that arrived since the last safe point
+#[cfg(target_family = "unix")]
+fn run_pending_traps() {
+    for signal in signals::take_trapped_signals()
+    {
+        run_trap(TrapCondition::Signal(signal));
+    }
+}
+
+#[cfg(target_family = "windows")]
+fn run_pending_traps() {
+}
+
+// Ends the shell for set -e or a failed expansion, as the exit builtin would
+fn exit_shell(status: i32) -> ! {
+    #[cfg(target_family = "unix")]
+    jobs::hang_up_jobs();
+    let _ = io::stdout().flush();
+    run_exit_trap(status);
+    std::process::exit(status)
+}
+
+// Runs the EXIT trap as the shell exits with the given status
+pub fn run_exit_trap(status: i32) {
+    if let Some(action) = traps::take_exit_trap()
+    {
+        set_last_status(status);
+        execute_input(&action);
+        let _ = io::stdout().flush();
+    }
+}
+
+// Runs a command whose status is being tested, such as the condition of an if
+fn execute_condition<F: FnOnce() -> i32>(execute: F) -> i32 {
+    CONTROL_FLOW.lock().unwrap().condition_depth += 1;
+    let status = execute();
+    CONTROL_FLOW.lock().unwrap().condition_depth -= 1;
+    return status
+}
+
+// ERR and DEBUG are not inherited by functions, so only the call itself
+// sets them off
+fn pseudo_signals_enabled() -> bool {
+    CONTROL_FLOW.lock().unwrap().function_depth == 0
+}
+
+// Used by the break, continue and return builtins
+pub fn request_control_flow(control_flow: ControlFlow) -> Result<(), String> {
+    let mut state = CONTROL_FLOW.lock().unwrap();
+    match control_flow {
+        ControlFlow::Break(_) | ControlFlow::Continue(_) if state.loop_depth == 0 => {
+            return Err(String::from("only meaningful in a `for', `while', or `until' loop"))
+        }
+        ControlFlow::Break(levels) | ControlFlow::Continue(levels) if levels == 0 => {
+            return Err(String::from("loop count out of range"))
+        }
+        ControlFlow::Return(_) if state.function_depth == 0 && state.source_depth == 0 => {
+            return Err(String::from("can only `return' from a function or sourced script"))
+        }
+        ControlFlow::Break(levels) => state.pending = Some(ControlFlow::Break(levels.min(state.loop_depth))),
+        ControlFlow::Continue(levels) => state.pending = Some(ControlFlow::Continue(levels.min(state.loop_depth))),
+        ControlFlow::Return(_) => state.pending = Some(control_flow),
+    }
+    Ok(())
+}
+
+// Consumes a break or continue aimed at the innermost loop. Returns whether the
+// loop should stop.
+fn loop_should_stop() -> bool {
+    let mut state = CONTROL_FLOW.lock().unwrap();
+    match state.pending {
+        Some(ControlFlow::Break(1)) => {
+            state.pending = None;
+            true
+        }
+        Some(ControlFlow::Break(levels)) => {
+            state.pending = Some(ControlFlow::Break(levels - 1));
+            true
+        }
+        Some(ControlFlow::Continue(1)) => {
+            state.pending = None;
+            false
+        }
+        Some(ControlFlow::Continue(levels)) => {
+            state.pending = Some(ControlFlow::Continue(levels - 1));
+            true
+        }
+        Some(ControlFlow::Return(_)) => true,
+        None => false,
+    }
+}
+
+pub fn is_function(name: &str) -> bool {
+    FUNCTIONS.lock().unwrap().contains_key(name)
+}
+
+fn get_builtin(command: &str) -> Option<Builtin> {
+    if posix_mode() && OXIDE_ONLY_BUILTINS.contains(command) {
+        return None
+    }
+    BUILTINS.get(command).copied()
+}
+
+// The builtin a command runs, if any. A timeout with options only GNU
+// timeout knows is left to the program, unless the builtin is asked for with
+// `builtin`.
+fn command_builtin(command_data: &CommandData) -> Option<Builtin> {
+    let passes_to_program = command_data.lookup != CommandLookup::BuiltinOnly
+        && command_data.command == "timeout"
+        && parse_timeout_arguments(&command_data.arguments).is_none()
+        && find_command("timeout").is_some();
+    match passes_to_program {
+        true => None,
+        false => get_builtin(&command_data.command),
+    }
+}
+
+pub fn is_builtin(command: &str) -> bool {
+    get_builtin(command).is_some()
+}
+
+fn execute_command_list(command_list_node: &ParseNode) -> i32 {
+    let mut status = 0;
+    for and_or_node in command_list_node.children.as_ref().unwrap()
+    {
+        status = match &and_or_node.entry {
+            ParseNodeType::Background(command) => {
+                execute_background(&and_or_node.children.as_ref().unwrap()[0], command)
+            }
+            _ => execute_and_or_list(and_or_node),
+        };
+        #[cfg(target_family = "unix")]
+        jobs::reap_jobs();
+        run_pending_traps();
+        if control_flow_pending() || interrupted()
+        {
+            break
+        }
+    }
+    return status
+}
+
+// Runs an and-or list in a forked copy of the shell without waiting for it
+#[cfg(target_family = "unix")]
+fn execute_background(and_or_node: &ParseNode, command: &str) -> i32 {
+    let trapped = traps::trapped_signals();
+    let interactive = interactive();
+    let _ = io::stdout().flush();
+    match unsafe { libc::fork() } {
+        -1 => {
+            eprintln!("oxide: fork: {}", io::Error::last_os_error());
+            1
+        }
+        0 => {
+            traps::leave_traps_for_subshell(&trapped, interactive);
+            if jobs::job_control()
+            {
+                unsafe { libc::setpgid(0, 0); }
+                signals::reset_signals();
+                jobs::disable_job_control();
+            }
+            else
+            {
+                // Without job control background jobs must not read the
+                // terminal or be stopped by Ctrl-C
+                signals::ignore_keyboard_signals();
+                if let Ok(null) = File::open("/dev/null")
+                {
+                    unsafe { libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO); }
+                }
+            }
+            let status = execute_and_or_list(and_or_node);
+            let _ = io::stdout().flush();
+            unsafe { libc::_exit(status) }
+        }
+        pid => {
+            jobs::set_process_group(pid, pid);
+            let id = jobs::add_background_job(pid, vec!(pid), command);
+            set_last_background_pid(pid as u32);
+            if interactive
+            {
+                eprintln!("[{}] {}", id, pid);
+            }
+            0
+        }
+    }
+}
+
+#[cfg(target_family = "windows")]
+fn execute_background(and_or_node: &ParseNode, _command: &str) -> i32 {
+    eprintln!("oxide: background jobs are not supported on Windows, running in the foreground");
+    execute_and_or_list(and_or_node)
+}
+
+fn execute_and_or_list(and_or_node: &ParseNode) -> i32 {
+    let children = and_or_node.children.as_ref().unwrap();
+    // Every pipeline but the last is a condition for the next
+    let last_index = children.len() - 1;
+    let mut status = if last_index == 0 {
+        execute_pipeline(&children[0])
+    } else {
+        execute_condition(|| execute_pipeline(&children[0]))
+    };
+
+    let mut child_index = 1;
+    while child_index + 1 < children.len() && !control_flow_pending() && !interrupted()
+    {
+        let run_next = match children[child_index].entry {
+            ParseNodeType::ListOp(ListOp::And) => status == 0,
+            ParseNodeType::ListOp(ListOp::Or) => status != 0,
+            _ => false,
+        };
+        if run_next && child_index + 1 == last_index
+        {
+            status = execute_pipeline(&children[child_index + 1]);
+        }
+        else if run_next
+        {
+            status = execute_condition(|| execute_pipeline(&children[child_index + 1]));
+        }
+        child_index += 2;
+    }
+    return status
+}
+
+fn execute_pipeline(pipeline_node: &ParseNode) -> i32 {
+    let children = pipeline_node.children.as_ref().unwrap();
+    let status = match &pipeline_node.entry {
+        ParseNodeType::Expr => {
+            // A compound command run by the shell itself sets these off for
+            // the commands in it instead
+            let simple = !is_compound_in_place(pipeline_node);
+            if simple && pseudo_signals_enabled()
+            {
+                run_trap(TrapCondition::Debug);
+            }
+            let status = read_ast_and_execute(pipeline_node);
+            let tested = CONTROL_FLOW.lock().unwrap().condition_depth > 0;
+            if simple && status != 0 && !tested
+            {
+                set_last_status(status);
+                if pseudo_signals_enabled()
+                {
+                    run_trap(TrapCondition::Err);
+                }
+                if errexit()
+                {
+                    exit_shell(status)
+                }
+            }
+            status
+        }
+        ParseNodeType::Negation => {
+            if execute_condition(|| execute_pipeline(&children[0])) == 0 { 1 } else { 0 }
+        }
+        ParseNodeType::Timed(posix) => execute_timed(children, *posix),
+        ParseNodeType::IfExpr => execute_if(children),
+        ParseNodeType::WhileExpr => execute_loop(children, false),
+        ParseNodeType::UntilExpr => execute_loop(children, true),
+        ParseNodeType::ForExpr(name) => execute_for(name, children),
+        ParseNodeType::CaseExpr => execute_case(children),
+        ParseNodeType::BraceGroup => execute_command_list(&children[0]),
+        ParseNodeType::Subshell => {
+            let mut command_data = CommandData::new();
+            command_data.compound = Some(pipeline_node.clone());
+            execute_on_command_list(vec!(command_data))
+        }
+        ParseNodeType::FunctionDef(name) => {
+            FUNCTIONS.lock().unwrap().insert(name.clone(), children[0].clone());
+            0
+        }
+        _ => {
+            eprintln!("Unexpected parsenode in command list!");
+            1
+        }
+    };
+    set_last_status(status);
+    return status
+}
+
+fn is_compound(entry: &ParseNodeType) -> bool
+{
+    matches!(entry,
+        ParseNodeType::IfExpr | ParseNodeType::WhileExpr | ParseNodeType::UntilExpr | ParseNodeType::ForExpr(_)
+        | ParseNodeType::CaseExpr | ParseNodeType::BraceGroup | ParseNodeType::Subshell)
+}
+
+// Whether a pipeline is a compound command on its own with redirections,
+// which the shell runs itself
+fn is_compound_in_place(expr_node: &ParseNode) -> bool
+{
+    let children = expr_node.children.as_ref().unwrap();
+    let compound = children.first()
+        .is_some_and(|child| is_compound(&child.entry) && child.entry != ParseNodeType::Subshell);
+    compound && !children.iter().any(|child| child.entry == ParseNodeType::PipeExpr)
+}
+
+// Runs a pipeline and reports the time and resources it took on stderr, in
+// the format set by TIMEFORMAT
+fn execute_timed(children: &[ParseNode], posix: bool) -> i32 {
+    let start = Instant::now();
+    timer::start_timer();
+    let status = children.first().map_or(0, execute_pipeline);
+    let usage = timer::stop_timer();
+
+    let format = match get_variable("TIMEFORMAT") {
+        _ if posix => String::from(timer::POSIX_FORMAT),
+        Some(format) => format,
+        None => String::from(timer::DEFAULT_FORMAT),
+    };
+    // An empty TIMEFORMAT turns the report off
+    if !format.is_empty()
+    {
+        eprintln!("{}", timer::format_times(&format, start.elapsed(), &usage));
+    }
+    return status
+}
+
+fn execute_if(children: &[ParseNode]) -> i32 {
+    let mut child_index = 0;
+    while child_index + 1 < children.len()
+    {
+        let condition_status = execute_condition(|| execute_command_list(&children[child_index]));
+        if control_flow_pending()
+        {
+            return condition_status
+        }
+        if condition_status == 0
+        {
+            return execute_command_list(&children[child_index + 1])
+        }
+        child_index += 2;
+    }
+
+    // An odd child left over is the else branch
+    if child_index < children.len()
+    {
+        return execute_command_list(&children[child_index])
+    }
+    return 0
+}
+
+fn execute_loop(children: &[ParseNode], until: bool) -> i32 {
+    let mut status = 0;
+    CONTROL_FLOW.lock().unwrap().loop_depth += 1;
+    loop {
+        let condition_status = execute_condition(|| execute_command_list(&children[0]));
+        if loop_should_stop() || interrupted() || (condition_status == 0) == until
+        {
+            break
+        }
+        status = execute_command_list(&children[1]);
+        if loop_should_stop() || interrupted()
+        {
+            break
+        }
+    }
+    CONTROL_FLOW.lock().unwrap().loop_depth -= 1;
+    return status
+}
+
+fn execute_for(name: &str, children: &[ParseNode]) -> i32 {
+    let raw_words: Vec<String> = children[0].children.as_ref().unwrap().iter()
+        .filter_map(|node| match &node.entry {
+            ParseNodeType::File(word) => Some(word.clone()),
+            _ => None,
+        })
+        .collect();
+    let words = match expand_words(&raw_words) {
+        Ok(words) => words,
+        Err(err) => {
+            eprintln!("oxide: {}", err);
+            return 1
+        }
+    };
+
+    let mut status = 0;
+    CONTROL_FLOW.lock().unwrap().loop_depth += 1;
+    for word in words
+    {
+        if let Err(err) = set_variable(name, &word)
+        {
+            eprintln!("oxide: {}", err);
+            status = 1;
+            break
+        }
+        status = execute_command_list(&children[1]);
+        if loop_should_stop() || interrupted()
+        {
+            break
+        }
+    }
+    CONTROL_FLOW.lock().unwrap().loop_depth -= 1;
+    return status
+}
+
+fn execute_case(children: &[ParseNode]) -> i32 {
+    let word = match &children[0].entry {
+        ParseNodeType::File(raw_word) => expand_string(raw_word),
+        _ => Err(String::from("missing case word")),
+    };
+    let word = match word {
+        Ok(word) => word,
+        Err(err) => {
+            eprintln!("oxide: {}", err);
+            return 1
+        }
+    };
+
+    for case_item in &children[1..]
+    {
+        let item_children = case_item.children.as_ref().unwrap();
+        for pattern_node in item_children[0].children.as_ref().unwrap()
+        {
+            let raw_pattern = match &pattern_node.entry {
+                ParseNodeType::File(raw_pattern) => raw_pattern,
+                _ => continue,
+            };
+            let matched = match expand_pattern(raw_pattern) {
+                Ok(pattern) => match Pattern::new(&pattern) {
+                    Ok(pattern) => pattern.matches(&word),
+                    Err(_) => pattern == word,
+                },
+                Err(err) => {
+                    eprintln!("oxide: {}", err);
+                    return 1
+                }
+            };
+            if matched
+            {
+                return execute_command_list(&item_children[1])
+            }
+        }
+    }
+    return 0
+}
+
+// Runs the commands of a file for the . builtin, with new positional
+// parameters if any were given. A return in the file ends it.
+pub fn source_file(source: &str, parameters: Option<Vec<String>>) -> i32 {
+    let previous_parameters = parameters.map(set_positional_parameters);
+    CONTROL_FLOW.lock().unwrap().source_depth += 1;
+
+    let mut status = run_script(source);
+
+    let mut state = CONTROL_FLOW.lock().unwrap();
+    state.source_depth -= 1;
+    if let Some(ControlFlow::Return(return_status)) = state.pending {
+        status = return_status;
+        state.pending = None;
+    }
+    drop(state);
+
+    if let Some(previous_parameters) = previous_parameters
+    {
+        set_positional_parameters(previous_parameters);
+    }
+    return status
+}
+
+fn call_function(body: &ParseNode, arguments: &[String]) -> i32 {
+    let previous_parameters = set_positional_parameters(arguments.to_vec());
+    CONTROL_FLOW.lock().unwrap().function_depth += 1;
+    push_local_scope();
+
+    let mut status = execute_pipeline(body);
+
+    let mut state = CONTROL_FLOW.lock().unwrap();
+    state.function_depth -= 1;
+    if let Some(ControlFlow::Return(return_status)) = state.pending {
+        status = return_status;
+        state.pending = None;
+    }
+    drop(state);
+
+    // Runs with the function's parameters still set
+    set_last_status(status);
+    run_trap(TrapCondition::Return);
+    pop_local_scope();
+    set_positional_parameters(previous_parameters);
+    return status
+}
+
+fn read_ast_and_execute(ast_root: &ParseNode) -> i32 {
+    let expr_children = ast_root.children.as_ref().unwrap();
+    
+    let mut commands_and_arguments = accumulate_commands_and_arguments(expr_children);
+
+    for command_data in commands_and_arguments.iter_mut()
+    {
+        if let Err(err) = expand_command_data(command_data)
+        {
+            eprintln!("oxide: {}", err);
+            // As POSIX asks, a script stops at an expansion that fails, such
+            // as ${name?} or $name under set -u
+            if !interactive()
+            {
+                exit_shell(1)
+            }
+            return 1
+        }
+        strip_command_prefixes(command_data);
+    }
+
+    execute_on_command_list(commands_and_arguments)
+}
+
+// Performs assignment detection and word expansion on the raw words of a command
+fn expand_command_data(command_data: &mut CommandData) -> Result<(), String> {
+    SUBSTITUTION_STATUS.lock().unwrap().take();
+    let mut raw_words = vec!(std::mem::take(&mut command_data.command));
+    raw_words.append(&mut command_data.arguments);
+
+    let mut assignment_count = 0;
+    for raw_word in &raw_words
+    {
+        match split_assignment(raw_word) {
+            Some((name, raw_value)) => {
+                command_data.assignments.push((name.to_string(), expand_string(raw_value)?));
+                assignment_count += 1;
+            }
+            None => break,
+        }
+    }
+
+    let mut words = expand_words(&raw_words[assignment_count..])?.into_iter();
+    command_data.command = words.next().unwrap_or_default();
+    command_data.arguments = words.collect();
+
+    for redirection in command_data.redirections.iter_mut()
+    {
+        for target in redirection.targets.iter_mut()
+        {
+            *target = expand_string(target)?;
+        }
+    }
+    command_data.substitution_status = SUBSTITUTION_STATUS.lock().unwrap().take();
+    Ok(())
+}
+
+// `command name` skips functions, `builtin name` only runs builtins and
+// `timeout duration name` runs a command with a time limit. The query forms
+// such as `command -v name` are left to run as builtins, as is a timeout with
+// bad arguments so it can report them.
+fn strip_command_prefixes(command_data: &mut CommandData) {
+    loop {
+        let (lookup, skip) = match command_data.command.as_str() {
+            #[cfg(target_family = "unix")]
+            "timeout" if !posix_mode() => match parse_timeout_arguments(&command_data.arguments) {
+                Some((limit, skip)) if skip < command_data.arguments.len() => {
+                    // As with GNU timeout, 0 is no limit at all
+                    command_data.timeout = Some(limit).filter(|limit| !limit.duration.is_zero());
+                    (command_data.lookup, skip)
+                }
+                _ => return,
+            },
+            "command" => {
+                let skip = command_data.arguments.iter().take_while(|argument| *argument == "-p" || *argument == "--").count();
+                match command_data.arguments.get(skip).map(String::as_str) {
+                    None | Some("-v") | Some("-V") => return,
+                    Some(_) => (CommandLookup::SkipFunctions, skip),
+                }
+            }
+            "builtin" if !command_data.arguments.is_empty() => (CommandLookup::BuiltinOnly, 0),
+            _ => return,
+        };
+        command_data.arguments.drain(..skip);
+        command_data.command = command_data.arguments.remove(0);
+        command_data.lookup = lookup;
+    }
+}
+
+fn accumulate_commands_and_arguments(mut expr_children: &[ParseNode]) -> Vec<CommandData> {
+    let mut commands_and_arguments: Vec<CommandData> = Vec::new();
+    let mut child_index = 0;
+    let mut command_data = CommandData::new();
+    while child_index < expr_children.len()
+    {
+        match expr_children[child_index].entry {
+            ParseNodeType::CommandExpr => {
+                let command_expr_children = expr_children[child_index].children.as_ref().unwrap();
+                read_command_expr(
+                    &mut command_data.command, 
+                    &mut command_data.arguments, 
+                    command_expr_children
+                );
+                
+                child_index += 1;
+            }
+            ParseNodeType::RedirectionExpr => {
+                let redirection_expr_children = expr_children[child_index].children.as_ref().unwrap();
+                command_data.redirections.push(read_redirection_expr(redirection_expr_children));
+                
+                child_index += 1;
+            }
+            ref entry if is_compound(entry) => {
+                command_data.compound = Some(expr_children[child_index].clone());
+                child_index += 1;
+            }
+            ParseNodeType::PipeExpr => {
+                let pipe_expr_children = expr_children[child_index].children.as_ref().unwrap();
+                
+                // Add command struct to the vector and reset struct
+                commands_and_arguments.push(command_data);
+                command_data = CommandData::new();
+
+                // Set new children to recurse down the PipeExpr
+                expr_children = pipe_expr_children;
+                // Set child index to avoid Pipe node.
+                child_index = 1;
+                continue
+            }
+            _ => break,
+        }
+    }
+
+    // TODO: Is there a smarter way to check if the struct is empty?
+    if !command_data.command.is_empty() || !command_data.redirections.is_empty() || command_data.compound.is_some()
+    {
+        commands_and_arguments.push(command_data);
+    }
+
+    return commands_and_arguments
+}
+
+fn read_command_expr(
+    command: &mut String, 
+    arguments: &mut Vec<String>,
+    command_expr_children: &[ParseNode]
+)
+{
+    for node in command_expr_children.iter() 
+    {
+        
+        match &node.entry
+        {
+            ParseNodeType::Command(command_name) => {
+                *command = command_name.clone()
+            }
+            ParseNodeType::File(filename) => {
+                arguments.push(filename.to_string())
+            }
+            _ => eprintln!("Unexpected parsenode in command expression!")
+        } 
+    }
+}
+
+fn read_redirection_expr(redirection_expr_children: &[ParseNode]) -> Redirection
+{
+    let mut fd = None;
+    let mut redirection_op = RedirectionOp::Output;
+    let mut targets = Vec::new();
+    for node in redirection_expr_children.iter()
+    {
+        match &node.entry
+        {
+            ParseNodeType::IoNumber(number) => {
+                fd = Some(*number)
+            }
+            ParseNodeType::RedirectionOp(redirection_op_name) => {
+                redirection_op = redirection_op_name.clone()
+            }
+            ParseNodeType::File(target) => {
+                targets.push(target.clone())
+            }
+            _ => eprintln!("Unexpected parsenode in redirection expression!")
+        }
+    }
+
+    // Input redirections default to stdin and output ones to stdout
+    let fd = fd.unwrap_or(match redirection_op {
+        RedirectionOp::Input | RedirectionOp::DuplicateInput | RedirectionOp::HereDocument => 0,
+        _ => 1,
+    });
+    Redirection { fd, op: redirection_op, targets }
+}
+
+// A file or pipe owned by the shell, given to a stage as one of its streams
+#[cfg(target_family = "unix")]
+type OwnedStream = std::os::fd::OwnedFd;
+#[cfg(target_family = "windows")]
+type OwnedStream = std::os::windows::io::OwnedHandle;
+
+// Streams above 2 given to a stage, by number
+type NumberedStreams = Vec<(usize, Stream)>;
+
+// One of the streams of a pipeline stage
+enum Stream {
+    // The shell's own stream with the same number
+    Inherit,
+    Null,
+    Owned(OwnedStream),
+}
+
+impl Stream {
+    fn from_file(file: File) -> Stream {
+        Stream::Owned(file.into())
+    }
+
+    // A copy of the stream, for a redirection such as 2>&1. `fd` is the
+    // number of the stream being copied.
+    fn try_clone(&self, fd: usize) -> io::Result<Stream> {
+        match self {
+            Stream::Inherit => shell_stream(fd).map(Stream::Owned),
+            Stream::Null => Ok(Stream::Null),
+            Stream::Owned(stream) => stream.try_clone().map(Stream::Owned),
+        }
+    }
+
+    fn into_stdio(self) -> Stdio {
+        match self {
+            Stream::Inherit => Stdio::inherit(),
+            Stream::Null => Stdio::null(),
+            Stream::Owned(stream) => Stdio::from(stream),
+        }
+    }
+
+    fn into_reader(self) -> Box<dyn Read + Send> {
+        match self {
+            #[cfg(target_family = "unix")]
+            Stream::Inherit => Box::new(ShellStdin),
+            #[cfg(target_family = "windows")]
+            Stream::Inherit => Box::new(io::stdin()),
+            Stream::Null => Box::new(io::empty()),
+            Stream::Owned(stream) => Box::new(File::from(stream)),
+        }
+    }
+
+    // Whether the stream is a terminal. `fd` is the number it is used as.
+    fn is_terminal(&self, fd: usize) -> bool {
+        match self {
+            Stream::Inherit if fd == 0 => io::stdin().is_terminal(),
+            Stream::Inherit if fd == 2 => io::stderr().is_terminal(),
+            Stream::Inherit => io::stdout().is_terminal(),
+            Stream::Null => false,
+            Stream::Owned(stream) => stream.is_terminal(),
+        }
+    }
+
+    fn into_writer(self, fd: usize) -> Box<dyn Write + Send> {
+        match self {
+            Stream::Inherit if fd == 2 => Box::new(io::stderr()),
+            Stream::Inherit => Box::new(io::stdout()),
+            Stream::Null => Box::new(io::sink()),
+            Stream::Owned(stream) => Box::new(File::from(stream)),
+        }
+    }
+}
+
+// The shell's stdin, read straight from the descriptor without the buffering
+// of io::stdin(). A builtin such as read then takes only what it uses, and
+// leaves the rest for the commands after it.
+#[cfg(target_family = "unix")]
+struct ShellStdin;
+
+#[cfg(target_family = "unix")]
+impl Read for ShellStdin {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let count = unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+        if count < 0
+        {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(count as usize)
+    }
+}
+
+// A copy of one of the shell's own standard streams
+#[cfg(target_family = "unix")]
+fn shell_stream(fd: usize) -> io::Result<OwnedStream>
+{
+    use std::os::fd::AsFd;
+    match fd {
+        0 => io::stdin().as_fd().try_clone_to_owned(),
+        1 => io::stdout().as_fd().try_clone_to_owned(),
+        2 => io::stderr().as_fd().try_clone_to_owned(),
+        // Higher numbers are only open once exec has redirected them
+        _ => {
+            let fd = fd as libc::c_int;
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1
+            {
+                return Err(io::Error::last_os_error())
+            }
+            unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()
+        }
+    }
+}
+
+#[cfg(target_family = "windows")]
+fn shell_stream(fd: usize) -> io::Result<OwnedStream>
+{
+    use std::os::windows::io::AsHandle;
+    match fd {
+        0 => io::stdin().as_handle().try_clone_to_owned(),
+        1 => io::stdout().as_handle().try_clone_to_owned(),
+        2 => io::stderr().as_handle().try_clone_to_owned(),
+        _ => Err(io::Error::from(ErrorKind::Unsupported)),
+    }
+}
+
+// A started stage of a pipeline
+enum Stage {
+    Process(Child),
+    // A builtin run in a forked copy of the shell
+    #[cfg(target_family = "unix")]
+    Forked(libc::pid_t),
+    #[cfg(target_family = "windows")]
+    Builtin(JoinHandle<i32>),
+    Finished(i32),
+}
+
+impl Stage {
+    fn wait(self) -> i32 {
+        match self {
+            Stage::Process(process) => wait_for_process(process),
+            #[cfg(target_family = "unix")]
+            Stage::Forked(pid) => jobs::wait_for_process(pid).status(),
+            #[cfg(target_family = "windows")]
+            Stage::Builtin(handle) => handle.join().unwrap_or(1),
+            Stage::Finished(status) => status,
+        }
+    }
+}
+
+// Builtins, functions and compound commands in a pipeline run in a forked
+// copy of the shell, as in sh, so `cd / | cat` or `echo hi | exit 3` leave
+// the shell itself alone. Whatever the copy needs from the shell's tables is
+// read before forking, so until `run` starts the child only makes
+// async-signal-safe calls, which include moving the stage's streams onto its
+// 0, 1 and 2. `other_end` is the read end of the pipe the stage writes to,
+// which the copy closes so it sees the reader go away.
+#[cfg(target_family = "unix")]
+fn fork_stage<F: FnOnce() -> i32>(
+    streams: [Stream; 3],
+    numbered: NumberedStreams,
+    pgid: Option<libc::pid_t>,
+    other_end: Option<libc::c_int>,
+    run: F,
+) -> io::Result<libc::pid_t>
+{
+    let moves = prepare_moves(streams, numbered)?;
+    let job_control = jobs::job_control();
+    let trapped = traps::trapped_signals();
+    let interactive = interactive();
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            if job_control
+            {
+                unsafe { libc::setpgid(0, pgid.unwrap_or(0)); }
+            }
+            if signals::ignoring_signals()
+            {
+                signals::reset_signals();
+            }
+            // The copy has no jobs of its own to wait for or hang up
+            traps::leave_traps_for_subshell(&trapped, interactive);
+            jobs::disable_job_control();
+            jobs::forget_jobs();
+            if let Some(fd) = other_end
+            {
+                unsafe { libc::close(fd); }
+            }
+            if apply_moves(&moves).is_err()
+            {
+                unsafe { libc::_exit(1) }
+            }
+            let status = run();
+            let _ = io::stdout().flush();
+            let _ = io::stderr().flush();
+            unsafe { libc::_exit(status) }
+        }
+        pid => Ok(pid),
+    }
+}
+
+// The stage for a forked copy of the shell, the first of which leads the
+// pipeline's process group
+#[cfg(target_family = "unix")]
+fn forked_stage(fork_result: io::Result<libc::pid_t>, process_group: &mut Option<u32>) -> Stage
+{
+    match fork_result {
+        Ok(pid) => {
+            let pgid = *process_group.get_or_insert(pid as u32) as libc::pid_t;
+            jobs::set_process_group(pid, pgid);
+            Stage::Forked(pid)
+        }
+        Err(err) => {
+            eprintln!("oxide: fork: {}", err);
+            Stage::Finished(1)
+        }
+    }
+}
+
+// Writes to every file of a redirection to more than one file
+struct MultiWriter(Vec<File>);
+
+impl Write for MultiWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for file in self.0.iter_mut()
+        {
+            file.write_all(buffer)?;
+        }
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for file in self.0.iter_mut()
+        {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn execute_on_command_list(commands_and_arguments: Vec<CommandData>) -> i32
+{
+    if *DEBUG
+    {
+        println!("{:?}", commands_and_arguments);
+    }
+
+    // A lone builtin runs in the shell itself so it can change the shell's state
+    let single_command = commands_and_arguments.len() == 1;
+    let description = describe_pipeline(&commands_and_arguments);
+    let mut process_group: Option<u32> = None;
+    // The shortest limit set with timeout, or the default one
+    let limit = commands_and_arguments.iter()
+        .filter_map(|command_data| command_data.timeout)
+        .min_by_key(|limit| limit.duration)
+        .or_else(|| default_timeout().map(Limit::new));
+    let last_index = commands_and_arguments.len() - 1;
+    let mut stages: Vec<Stage> = Vec::new();
+    let mut copy_threads: Vec<JoinHandle<()>> = Vec::new();
+    // Input for the next stage, the output of the previous one
+    let mut next_input = Stream::Inherit;
+    // Records the output of the last stage as a block, when that's turned on
+    #[cfg(target_family = "unix")]
+    let mut capture: Option<PtyCapture> = None;
+
+    for (index, mut command_data) in commands_and_arguments.into_iter().enumerate()
+    {
+        let is_last = index == last_index;
+        let input = std::mem::replace(&mut next_input, Stream::Null);
+
+        if single_command && auto_cd() && is_directory_command(&command_data)
+        {
+            command_data.arguments.push(std::mem::replace(&mut command_data.command, String::from("cd")));
+        }
+        // With auto_cd a path that doesn't exist may be a mistyped directory
+        if single_command && auto_cd() && command_data.arguments.is_empty()
+            && is_path(&command_data.command) && !to_path(&command_data.command).exists()
+        {
+            stages.push(Stage::Finished(command_not_found(command_data, single_command)));
+            continue
+        }
+
+        let skip_functions = command_data.lookup != CommandLookup::Any;
+        // Compound commands, functions, assignments and builtins that run
+        // shell code need the shell itself
+        let shell_stage = command_data.command.is_empty()
+            || (!skip_functions && is_function(&command_data.command))
+            || runs_shell_code(&command_data);
+        let runs_in_shell = shell_stage || command_builtin(&command_data).is_some();
+        if command_data.timeout.is_some() && runs_in_shell
+        {
+            eprintln!("oxide: timeout: {}: only external commands can be given a time limit", command_data.command);
+            stages.push(Stage::Finished(126));
+            continue
+        }
+
+        // On their own they run in the shell, apart from a subshell, which
+        // is always a copy of it where there is fork
+        let is_subshell = command_data.compound.as_ref().is_some_and(|node| node.entry == ParseNodeType::Subshell);
+        if shell_stage && single_command && (cfg!(target_family = "windows") || !is_subshell)
+        {
+            stages.push(Stage::Finished(execute_on_command_data(&command_data)));
+            continue
+        }
+        #[cfg(target_family = "windows")]
+        if shell_stage
+        {
+            eprintln!("oxide: functions and compound commands can't be used in a pipeline on Windows");
+            stages.push(Stage::Finished(1));
+            continue
+        }
+
+        if single_command && command_data.command == "exec"
+        {
+            stages.push(Stage::Finished(execute_exec(command_data)));
+            continue
+        }
+
+        let builtin = command_builtin(&command_data);
+        if builtin.is_none() && command_data.lookup == CommandLookup::BuiltinOnly
+        {
+            eprintln!("oxide: builtin: {}: not a shell builtin", display(&command_data.command));
+            stages.push(Stage::Finished(1));
+            continue
+        }
+
+        // Every stage but the last writes to a pipe read by the next one
+        let mut streams = [input, Stream::Inherit, Stream::Inherit];
+        if !is_last
+        {
+            match io::pipe() {
+                Ok((reader, writer)) => {
+                    next_input = Stream::Owned(reader.into());
+                    streams[1] = Stream::Owned(writer.into());
+                }
+                Err(err) => {
+                    eprintln!("oxide: {}", err);
+                    stages.push(Stage::Finished(1));
+                    continue
+                }
+            }
+        }
+        #[cfg(target_family = "unix")]
+        if is_last && builtin.is_none() && capture_blocks()
+        {
+            capture = PtyCapture::open().ok();
+            if let Some(capture) = &capture
+            {
+                if let (Ok(stdout), Ok(stderr)) = (capture.terminal(), capture.terminal())
+                {
+                    streams[1] = Stream::from_file(stdout);
+                    streams[2] = Stream::from_file(stderr);
+                }
+            }
+        }
+
+        let (streams, numbered) = match redirect_streams(&command_data, streams, &mut copy_threads) {
+            Ok(streams) => streams,
+            Err(err) => {
+                eprintln!("oxide: {}", err);
+                stages.push(Stage::Finished(1));
+                continue
+            }
+        };
+        #[cfg(target_family = "unix")]
+        let pgid = process_group.map(|pgid| pgid as libc::pid_t);
+        #[cfg(target_family = "unix")]
+        let other_end = match &next_input {
+            Stream::Owned(reader) => Some(reader.as_raw_fd()),
+            _ => None,
+        };
+
+        #[cfg(target_family = "unix")]
+        if shell_stage
+        {
+            let fork_result = fork_stage(streams, numbered, pgid, other_end, || run_in_shell(&command_data));
+            stages.push(forked_stage(fork_result, &mut process_group));
+            continue
+        }
+
+        if let Some(builtin) = builtin
+        {
+            let arguments = command_data.arguments;
+            let stdout_is_terminal = streams[1].is_terminal(1);
+            #[cfg(target_family = "unix")]
+            if !single_command
+            {
+                // The copy's own streams are the stage's by the time it runs
+                let mut builtin_io = BuiltinIo {
+                    stdin: Stream::Inherit.into_reader(),
+                    stdout: Stream::Inherit.into_writer(1),
+                    stderr: Stream::Inherit.into_writer(2),
+                    stdout_is_terminal,
+                };
+                let fork_result = fork_stage(streams, numbered, pgid, other_end, move || {
+                    let status = builtin(&arguments, &mut builtin_io);
+                    let _ = builtin_io.stdout.flush();
+                    status
+                });
+                stages.push(forked_stage(fork_result, &mut process_group));
+                continue
+            }
+
+            let [stdin, stdout, stderr] = streams;
+            let mut builtin_io = BuiltinIo {
+                stdin: stdin.into_reader(),
+                stdout: stdout.into_writer(1),
+                stderr: stderr.into_writer(2),
+                stdout_is_terminal,
+            };
+            if single_command
+            {
+                let status = builtin(&arguments, &mut builtin_io);
+                let _ = builtin_io.stdout.flush();
+                stages.push(Stage::Finished(status));
+            }
+            else
+            {
+                // Run alongside the other stages so neither side blocks on a full pipe
+                #[cfg(target_family = "windows")]
+                stages.push(Stage::Builtin(thread::spawn(move || {
+                    let status = builtin(&arguments, &mut builtin_io);
+                    let _ = builtin_io.stdout.flush();
+                    status
+                })));
+            }
+            continue
+        }
+
+        let program = match find_program(&command_data) {
+            Some(program) => program,
+            None => {
+                stages.push(Stage::Finished(command_not_found(command_data, single_command)));
+                continue
+            }
+        };
+        match spawn_stage(&command_data, &program, streams, numbered, process_group)
+        {
+            Ok(process) => {
+                #[cfg(target_family = "unix")]
+                {
+                    // The first process leads the pipeline's process group
+                    let pid = process.id();
+                    let pgid = *process_group.get_or_insert(pid) as libc::pid_t;
+                    jobs::set_process_group(pid as libc::pid_t, pgid);
+                }
+                stages.push(Stage::Process(process));
+            }
+            Err(status) => stages.push(Stage::Finished(status)),
+        }
+    }
+
+    // Only pipelines that run programs are timed for notify, from when the
+    // shell starts waiting for them
+    let runs_processes = stages.iter().any(|stage| !matches!(stage, Stage::Finished(_)));
+    let started = Instant::now();
+    // Stages report their status in pipeline order
+    #[cfg(target_family = "unix")]
+    let watchdog = match (limit, process_group) {
+        // Without job control the pipeline shares the shell's process group,
+        // so its processes are signalled one by one
+        (Some(limit), Some(_)) if !jobs::job_control() => {
+            let pids = stages.iter().filter_map(|stage| match stage {
+                Stage::Process(process) => Some(process.id() as libc::pid_t),
+                Stage::Forked(pid) => Some(*pid),
+                Stage::Finished(_) => None,
+            }).collect();
+            Some(Watchdog::start(pids, limit))
+        }
+        (Some(limit), Some(pgid)) => Some(Watchdog::start(vec!(-(pgid as libc::pid_t)), limit)),
+        _ => None,
+    };
+    #[cfg(target_family = "unix")]
+    let (statuses, timed_out) = wait_for_stages(stages, &description, watchdog);
+    #[cfg(target_family = "windows")]
+    let (statuses, timed_out) = (wait_for_stages(stages, &description), false);
+    let elapsed = started.elapsed();
+    for copy_thread in copy_threads
+    {
+        let _ = copy_thread.join();
+    }
+
+    // With pipefail the last stage to fail decides the status
+    let status = if timed_out {
+        eprintln!("oxide: {}: timed out after {}", description, format_duration(limit.unwrap().duration));
+        TIMEOUT_STATUS
+    } else if pipefail() {
+        statuses.iter().rev().find(|status| **status != 0).copied().unwrap_or(0)
+    } else {
+        statuses.last().copied().unwrap_or(0)
+    };
+
+    #[cfg(target_family = "unix")]
+    if let Some(capture) = capture
+    {
+        if statuses.contains(&jobs::STOPPED_STATUS)
+        {
+            capture.detach();
+        }
+        else
+        {
+            add_block(&description, status, capture.finish());
+        }
+    }
+    // A pipeline stopped with Ctrl-Z hasn't finished yet
+    #[cfg(target_family = "unix")]
+    let stopped = statuses.contains(&jobs::STOPPED_STATUS);
+    #[cfg(target_family = "windows")]
+    let stopped = false;
+    if runs_processes && !stopped && interactive() && notify_after().is_some_and(|threshold| elapsed >= threshold)
+    {
+        LONG_COMMANDS.lock().unwrap().push((description, status, elapsed));
+    }
+    set_pipe_status(statuses);
+    return status
+}
+
+// Blocks are only recorded at the prompt, when there is a terminal to show
+// the output on as well
+#[cfg(target_family = "unix")]
+fn capture_blocks() -> bool
+{
+    crate::options::blocks() && interactive() && pty::can_capture()
+}
+
+// The pipeline as text, for the job table
+fn describe_pipeline(commands_and_arguments: &[CommandData]) -> String
+{
+    let commands: Vec<String> = commands_and_arguments.iter()
+        .map(|command_data| {
+            if let Some(compound) = &command_data.compound
+            {
+                return String::from(match compound.entry {
+                    ParseNodeType::Subshell => "( ... )",
+                    ParseNodeType::BraceGroup => "{ ... }",
+                    ParseNodeType::IfExpr => "if ... fi",
+                    ParseNodeType::WhileExpr => "while ... done",
+                    ParseNodeType::UntilExpr => "until ... done",
+                    ParseNodeType::ForExpr(_) => "for ... done",
+                    _ => "case ... esac",
+                })
+            }
+            let mut words = vec!(command_data.command.clone());
+            words.extend(command_data.arguments.iter().cloned());
+            words.join(" ")
+        })
+        .collect();
+    commands.join(" | ")
+}
+
+// Applies a command's redirections, in order, to the streams it would
+// otherwise have. Streams above 2 are returned by number, in the order they
+// were first redirected. Output to several files goes through a pipe to a
+// thread that copies it to each of them.
+fn redirect_streams(
+    command_data: &CommandData,
+    mut streams: [Stream; 3],
+    copy_threads: &mut Vec<JoinHandle<()>>
+) -> Result<([Stream; 3], NumberedStreams), String>
+{
+    let mut numbered: NumberedStreams = Vec::new();
+    for redirection in &command_data.redirections
+    {
+        let fd = redirection.fd as usize;
+        #[cfg(target_family = "windows")]
+        if fd > 2
+        {
+            return Err(format!("{}: only file descriptors 0, 1 and 2 can be redirected on Windows", fd))
+        }
+        let target = match redirection.targets.first() {
+            Some(target) => target,
+            None => return Err(String::from("missing redirection target")),
+        };
+        let path = to_path(target);
+        let file_error = |err: io::Error| format!("{}: {}", target, err);
+
+        let stream = match redirection.op {
+            RedirectionOp::Input => Stream::from_file(File::open(path).map_err(file_error)?),
+            RedirectionOp::Output | RedirectionOp::Append if redirection.targets.len() == 1 => {
+                Stream::from_file(open_output_file(&path, &redirection.op).map_err(file_error)?)
+            }
+            RedirectionOp::Output | RedirectionOp::Append => {
+                let (mut reader, writer) = io::pipe().map_err(|err| err.to_string())?;
+                let mut files = MultiWriter(open_output_files(redirection));
+                copy_threads.push(thread::spawn(move || {
+                    let _ = io::copy(&mut reader, &mut files);
+                }));
+                Stream::Owned(writer.into())
+            }
+            // Written to a pipe by a thread, so a long one can't fill the pipe
+            // before anything reads it
+            RedirectionOp::HereDocument => {
+                let (reader, mut writer) = io::pipe().map_err(|err| err.to_string())?;
+                let body = target.clone();
+                copy_threads.push(thread::spawn(move || {
+                    let _ = writer.write_all(body.as_bytes());
+                }));
+                Stream::Owned(reader.into())
+            }
+            RedirectionOp::DuplicateOutput | RedirectionOp::DuplicateInput => {
+                let bad_fd = || format!("{}: bad file descriptor", target);
+                match target.parse::<usize>() {
+                    _ if target == "-" => Stream::Null,
+                    Ok(source) if source <= 2 => streams[source].try_clone(source).map_err(|_| bad_fd())?,
+                    Ok(source) => match numbered.iter().find(|(number, _)| *number == source) {
+                        Some((_, Stream::Null)) => return Err(bad_fd()),
+                        Some((_, stream)) => stream.try_clone(source).map_err(|_| bad_fd())?,
+                        None => Stream::Owned(shell_stream(source).map_err(|_| bad_fd())?),
+                    },
+                    Err(_) => return Err(bad_fd()),
+                }
+            }
+        };
+        if fd <= 2
+        {
+            streams[fd] = stream;
+        }
+        else
+        {
+            numbered.retain(|(number, _)| *number != fd);
+            numbered.push((fd, stream));
+        }
+    }
+    Ok((streams, numbered))
+}
+
+// Descriptor numbers paired with the stream to put there, or None to close
+// it, as in 3>&-
+#[cfg(target_family = "unix")]
+type StreamMoves = Vec<(libc::c_int, Option<std::os::fd::OwnedFd>)>;
+
+// Gets streams ready to be put in place with apply_moves. Each is first
+// copied above every number being set, so putting one in place can't close
+// another. Standard streams that are closed read and write nothing instead.
+#[cfg(target_family = "unix")]
+fn prepare_moves(streams: [Stream; 3], numbered: NumberedStreams) -> io::Result<StreamMoves>
+{
+    use std::os::fd::{FromRawFd, OwnedFd};
+    let lowest_free = numbered.iter().map(|(fd, _)| *fd).max().unwrap_or(2) as libc::c_int + 1;
+    let mut moves = Vec::new();
+    for (fd, stream) in IntoIterator::into_iter(streams).enumerate().chain(numbered)
+    {
+        let source: OwnedFd = match stream {
+            Stream::Inherit => continue,
+            Stream::Null if fd <= 2 => OpenOptions::new().read(true).write(true).open("/dev/null")?.into(),
+            Stream::Null => {
+                moves.push((fd as libc::c_int, None));
+                continue
+            }
+            Stream::Owned(stream) => stream,
+        };
+        let copy = unsafe { libc::fcntl(source.as_raw_fd(), libc::F_DUPFD_CLOEXEC, lowest_free) };
+        if copy == -1
+        {
+            return Err(io::Error::last_os_error())
+        }
+        moves.push((fd as libc::c_int, Some(unsafe { OwnedFd::from_raw_fd(copy) })));
     }
-
-    return Ok(())
+    Ok(moves)
 }
 
-
-fn split_input(input: &mut String) -> Vec<&str> {
-    let command_vector = input.split_whitespace();
-    let commands: Vec<&str> = Vec::from_iter(command_vector);
-    commands
+// Only makes async-signal-safe calls, so it can run between fork and exec
+#[cfg(target_family = "unix")]
+fn apply_moves(moves: &StreamMoves) -> io::Result<()>
+{
+    for (fd, source) in moves
+    {
+        match source {
+            Some(source) => if unsafe { libc::dup2(source.as_raw_fd(), *fd) } == -1 {
+                return Err(io::Error::last_os_error())
+            },
+            None => unsafe { libc::close(*fd); },
+        }
+    }
+    Ok(())
 }
 
-fn execute_input(input: &mut String) {
-    if DEBUG{
-        println!("Executing on following string: {}", input);
+// Hands a program the streams above 2 it was redirected, moved onto their
+// numbers just before it starts
+#[cfg(target_family = "unix")]
+fn attach_numbered_streams(command: &mut Command, numbered: NumberedStreams) -> io::Result<()>
+{
+    use std::os::unix::process::CommandExt;
+    let moves = prepare_moves([Stream::Inherit, Stream::Inherit, Stream::Inherit], numbered)?;
+    unsafe {
+        command.pre_exec(move || apply_moves(&moves));
     }
+    Ok(())
+}
 
-    let commands: Vec<&str> = split_input(input);
-    let ast_root;
-    match parse_input(&commands) {
-        Ok(ast) => ast_root = ast,
-        Err(e) => {
-            println!("{}", e);
-            return
+// Starts one stage of a pipeline with the given streams. Returns the exit
+// status to use if the process could not be started.
+fn spawn_stage(
+    command_data: &CommandData,
+    program: &Path,
+    streams: [Stream; 3],
+    numbered: NumberedStreams,
+    process_group: Option<u32>
+) -> Result<Child, i32>
+{
+    let mut command = build_command(command_data, program);
+
+    #[cfg(target_family = "unix")]
+    {
+        jobs::prepare_command(&mut command, process_group.map(|pgid| pgid as libc::pid_t));
+        if let Err(err) = attach_numbered_streams(&mut command, numbered)
+        {
+            eprintln!("oxide: {}", err);
+            return Err(1)
         }
     }
-    if EXECUTE_AST
+    #[cfg(target_family = "windows")]
+    let _ = (process_group, numbered);
+
+    let [stdin, stdout, stderr] = streams;
+    command.stdin(stdin.into_stdio())
+           .stdout(stdout.into_stdio())
+           .stderr(stderr.into_stdio());
+
+    match command.spawn()
     {
-        read_ast_and_execute(&ast_root);
+        Ok(process) => Ok(process),
+        Err(err) => Err(spawn_error(&command_data.command, err)),
     }
-    if DEBUG
+}
+
+// The file a command runs, found through the PATH cache unless the command
+// sets its own PATH. None if the command can't be found.
+fn find_program(command_data: &CommandData) -> Option<PathBuf>
+{
+    let sets_path = command_data.assignments.iter().any(|(name, _)| name == "PATH");
+    if sets_path
     {
-        println!("Parse Tree: {:#?}", ast_root);
+        return Some(to_path(&command_data.command))
     }
+    find_command(&command_data.command)
 }
 
-fn read_ast_and_execute(ast_root: &ParseNode) {
-    let mut expr_children = ast_root.children.as_ref().unwrap();
-    
-    let commands_and_arguments = accumulate_commands_and_arguments(&mut expr_children);
-
-    execute_on_command_list(commands_and_arguments);
+fn build_command(command_data: &CommandData, program: &Path) -> Command
+{
+    let mut command = Command::new(program);
+    // Programs see the name they were run as, not the path it resolved to
+    #[cfg(target_family = "unix")]
+    std::os::unix::process::CommandExt::arg0(&mut command, to_os_string(&command_data.command));
+    command.args(command_data.arguments.iter().map(|argument| to_os_string(argument)))
+           .envs(command_data.assignments.iter().map(|(name, value)| (name, to_os_string(value))));
+    command
 }
 
-fn accumulate_commands_and_arguments(mut expr_children: &Vec<ParseNode>) -> Vec<CommandData> {
-    let mut commands_and_arguments: Vec<CommandData> = Vec::new();
-    let mut child_index = 0;
-    let mut command_data = CommandData::new();
-    while child_index < expr_children.len()
+// Deals with a command that isn't a builtin, function or anything in PATH. A
+// command_not_found_handle function is run in its place if there is one.
+// Otherwise similar names are suggested, and at the prompt the closest can be
+// run instead.
+fn command_not_found(mut command_data: CommandData, single_command: bool) -> i32
+{
+    let handler = FUNCTIONS.lock().unwrap().get(NOT_FOUND_HANDLER).cloned();
+    if let Some(handler) = handler.filter(|_| single_command)
     {
-        match expr_children[child_index].entry {
-            ParseNodeType::CommandExpr => {
-                let command_expr_children = expr_children[child_index].children.as_ref().unwrap();
-                read_command_expr(
-                    &mut command_data.command, 
-                    &mut command_data.arguments, 
-                    command_expr_children
-                );
-                
-                child_index += 1;
-            }
-            ParseNodeType::RedirectionExpr => {
-                let redirection_expr_children = expr_children[child_index].children.as_ref().unwrap();
-                read_redirection_expr(
-                    &mut command_data.redirection_op,
-                    &mut command_data.redirection_files,
-                    redirection_expr_children
-                );
-                
-                child_index += 1;
-            }
-            ParseNodeType::PipeExpr => {
-                let pipe_expr_children = expr_children[child_index].children.as_ref().unwrap();
-                
-                // Add command struct to the vector and reset struct
-                commands_and_arguments.push(command_data);
-                command_data = CommandData::new();
+        let mut arguments = vec!(command_data.command.clone());
+        arguments.extend(command_data.arguments.iter().cloned());
+        return call_function(&handler, &arguments)
+    }
 
-                // Set new children to recurse down the PipeExpr
-                expr_children = pipe_expr_children;
-                // Set child index to avoid Pipe node.
-                child_index = 1;
-                continue
-            }
-            _ => break,
-        }
+    eprintln!("oxide: {}: command not found", display(&command_data.command));
+    if posix_mode()
+    {
+        return 127
+    }
+    let mut names = command_names();
+    if auto_cd()
+    {
+        names.extend(directory_names(&command_data.command));
+    }
+    let suggestions = suggest::closest(&command_data.command, &names);
+    match suggestions.len() {
+        0 => return 127,
+        1 => eprintln!("Did you mean {}?", suggestions[0]),
+        _ => eprintln!("Did you mean one of: {}?", suggestions.join(", ")),
     }
 
-    // TODO: Is there a smarter way to check if the struct is empty?
-    if !(command_data.command == "".to_string())
+    if single_command && can_ask()
     {
-        commands_and_arguments.push(command_data);
+        let mut words = vec!(suggestions[0].clone());
+        words.extend(command_data.arguments.iter().cloned());
+        let correction = display(&words.join(" "));
+        if suggest::offer_correction(&correction, &mut io::stdin().lock(), &mut io::stderr())
+        {
+            command_data.command = suggestions[0].clone();
+            return execute_on_command_list(vec!(command_data))
+        }
     }
+    127
+}
 
-    return commands_and_arguments
+// With auto_cd a directory name on its own changes into it, as long as it
+// isn't also the name of a command
+fn is_directory_command(command_data: &CommandData) -> bool
+{
+    let name = &command_data.command;
+    command_data.arguments.is_empty()
+        && command_data.assignments.is_empty()
+        && to_path(name).is_dir()
+        && !is_builtin(name)
+        && !is_function(name)
+        && (is_path(name) || find_command(name).is_none())
 }
 
-fn read_command_expr(
-    command: &mut String, 
-    arguments: &mut Vec<String>,
-    command_expr_children: &Vec<ParseNode>
-)
+// Directories a mistyped directory name could have meant: those beside it
+// for a path, otherwise those in the current folder
+fn directory_names(name: &str) -> Vec<String>
 {
-    for node in command_expr_children.iter() 
-    {
-        
-        match &node.entry
-        {
-            ParseNodeType::Command(command_name) => {
-                *command = command_name.clone()
-            }
-            ParseNodeType::File(filename) => {
-                arguments.push(filename.to_string())
-            }
-            _ => eprintln!("Unexpected parsenode in command expression!")
-        } 
+    let (parent, prefix) = match name.rfind('/') {
+        Some(index) => (to_path(&name[..=index]), name[..=index].to_string()),
+        None => (PathBuf::from("."), String::new()),
+    };
+    match std::fs::read_dir(parent) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| format!("{}{}", prefix, from_os_str(&entry.file_name())))
+            .collect(),
+        Err(_) => Vec::new(),
     }
 }
 
-fn read_redirection_expr<'a>(
-    redirection_op: &mut Option<RedirectionOp>, 
-    redirection_files: &mut Vec<&'a Path>,
-    redirection_expr_children: &'a Vec<ParseNode>
-)
-{ 
-    for node in redirection_expr_children.iter()
+// Every name that could be run as a command, for suggestions
+fn command_names() -> BTreeSet<String>
+{
+    let mut names: BTreeSet<String> = BUILTINS.keys()
+        .filter(|name| is_builtin(name))
+        .map(|name| name.to_string())
+        .collect();
+    names.extend(ALIASES.lock().unwrap().keys().cloned());
+    names.extend(FUNCTIONS.lock().unwrap().keys().cloned());
+    names.extend(path_commands());
+    names
+}
+
+// Questions can only be asked at the prompt, with someone to answer them
+#[cfg(target_family = "unix")]
+fn can_ask() -> bool
+{
+    interactive() && unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+#[cfg(target_family = "windows")]
+fn can_ask() -> bool
+{
+    interactive()
+}
+
+// Reports a command that could not be started, returning its exit status
+fn spawn_error(name: &str, err: io::Error) -> i32
+{
+    if err.kind() == ErrorKind::NotFound
     {
-        match &node.entry
-        {
-            ParseNodeType::RedirectionOp(redirection_op_name) => {
-                *redirection_op = Some(redirection_op_name.clone())
-            }
-            ParseNodeType::File(filename) => {
-                redirection_files.push(Path::new(filename))
-            }
-            _ => eprintln!("Unexpected parsenode in redirection expression!")
-        }
+        eprintln!("oxide: {}: No such file or directory", display(name));
+        127
+    }
+    else
+    {
+        eprintln!("oxide: {}: {}", display(name), err);
+        126
     }
 }
 
-fn execute_on_command_list(commands_and_arguments: Vec<CommandData>)
+// exec with a command replaces the shell with it. Without one, its
+// redirections apply to the shell itself for the rest of the session.
+fn execute_exec(mut command_data: CommandData) -> i32
 {
-    if DEBUG
+    if command_data.arguments.first().map(String::as_str) == Some("--")
     {
-        println!("{:?}", commands_and_arguments);
+        command_data.arguments.remove(0);
     }
+    // Copy threads for output to several files live as long as the shell
+    let mut copy_threads = Vec::new();
+    let streams = [Stream::Inherit, Stream::Inherit, Stream::Inherit];
+    let (streams, numbered) = match redirect_streams(&command_data, streams, &mut copy_threads) {
+        Ok(streams) => streams,
+        Err(err) => {
+            eprintln!("oxide: exec: {}", err);
+            return 1
+        }
+    };
 
-    // Execute separately so builtins take effect
-    if commands_and_arguments.len() == 1
+    if command_data.arguments.is_empty()
     {
-        let command_data = &commands_and_arguments[0];
-        execute_on_command_data(&command_data);         
-        return
+        return redirect_shell(streams, numbered)
+    }
+    command_data.command = command_data.arguments.remove(0);
+    let status = replace_shell(&command_data, streams, numbered);
+    // A script can't carry on once exec has failed
+    if !interactive()
+    {
+        run_exit_trap(status);
+        std::process::exit(status);
     }
+    status
+}
 
-    let mut previous_process: Option<Child> = None;
-    for command_data in commands_and_arguments
+// Makes the redirected streams the shell's own
+#[cfg(target_family = "unix")]
+fn redirect_shell(streams: [Stream; 3], numbered: NumberedStreams) -> i32
+{
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    if let Err(err) = prepare_moves(streams, numbered).and_then(|moves| apply_moves(&moves))
     {
-        // If BUILTIN start the piping again after the builtin command
-        if let Some(_) = BUILTINS.get(&command_data.command[..])
-        {
-            println!("Builtin command: {} encountered during piping, consider removing", command_data.command);
-            previous_process = None;
-        }
-        else
-        {
-            match previous_process
-            {
-                // TODO: Move these into methods on the command_data struct
-                Some(prev_process) => {
-                    let next_process = 
-                        match Command::new(command_data.command)
-                                      .args(command_data.arguments)
-                                      .stdout(Stdio::piped())
-                                      .stdin(prev_process.stdout.unwrap())
-                                      .spawn()
-                        {
-                            Ok(process) => process,
-                            Err(err) => {
-                                eprintln!("{}", err);
-                                return
-                            }
-                        };
-                    
-                    previous_process = Some(next_process);
-                }
-                None => {
-                    let next_process = 
-                        match Command::new(command_data.command)
-                                      .args(command_data.arguments)
-                                      .stdout(Stdio::piped())
-                                      .stdin(Stdio::piped())
-                                      .spawn()
-                        {
-                            Ok(process) => process,
-                            Err(err) => {
-                                eprintln!("{}", err);
-                                return
-                            }
-                        };
-                    
-                    previous_process = Some(next_process);
-                }
-            }
-        }
+        eprintln!("oxide: exec: {}", err);
+        return 1
+    }
+    0
+}
 
-        if let Some(redirection_op) = command_data.redirection_op
-        { 
-            match redirection_op  
-            {
-                RedirectionOp::Output => {
-                    redirect_output_process(previous_process, &command_data.redirection_files, true);
-                    previous_process = None;
-                }
-                RedirectionOp::Append => {
-                    redirect_output_process(previous_process, &command_data.redirection_files, false);
-                    previous_process = None;
-                }
-                RedirectionOp::Input => {
-                    //redirect_input_process(&mut previous_process.as_mut(), &command_data.redirection_files);
+#[cfg(target_family = "windows")]
+fn redirect_shell(_streams: [Stream; 3], _numbered: NumberedStreams) -> i32
+{
+    eprintln!("oxide: exec: redirecting the shell's own streams is not supported on Windows");
+    1
+}
 
-                    redirect_input_process(previous_process.as_mut(), &command_data.redirection_files);
-                }
-            }    
+// Runs a program in place of the shell, returning only if that fails
+#[cfg(target_family = "unix")]
+fn replace_shell(command_data: &CommandData, streams: [Stream; 3], numbered: NumberedStreams) -> i32
+{
+    use std::os::unix::process::CommandExt;
+    let mut command = match find_program(command_data) {
+        Some(program) => build_command(command_data, &program),
+        None => {
+            eprintln!("oxide: exec: {}: not found", display(&command_data.command));
+            return 127
         }
-        else
-        {
-            if let Some(ref process) = previous_process 
-            {
-                // Should drop handle to stdin stopping process hanging on input
-                let _input = process.stdin.as_ref();
-            }
+    };
+    if signals::ignoring_signals()
+    {
+        unsafe {
+            command.pre_exec(|| {
+                signals::reset_signals();
+                Ok(())
+            });
         }
     }
-
-    // Just print the results of the pipe expression
-    if let Some(process) = previous_process 
+    if let Err(err) = attach_numbered_streams(&mut command, numbered)
     {
-        match process.wait_with_output()
-        {
-            Ok(command_output) => {
-                let output_data = command_output.stdout;
-                println!("{}", String::from_utf8(output_data).unwrap())
-            }
-            Err(_) => println!("Could not get output from final command!"),
+        eprintln!("oxide: exec: {}", err);
+        return 1
+    }
+    let [stdin, stdout, stderr] = streams;
+    command.stdin(stdin.into_stdio())
+           .stdout(stdout.into_stdio())
+           .stderr(stderr.into_stdio());
+
+    let _ = io::stdout().flush();
+    let err = command.exec();
+    spawn_error(&command_data.command, err)
+}
+
+// Windows can't replace a process, so run the program and exit with its status
+#[cfg(target_family = "windows")]
+fn replace_shell(command_data: &CommandData, streams: [Stream; 3], numbered: NumberedStreams) -> i32
+{
+    let program = match find_program(command_data) {
+        Some(program) => program,
+        None => {
+            eprintln!("oxide: exec: {}: not found", display(&command_data.command));
+            return 127
         }
-    } 
+    };
+    let status = match spawn_stage(command_data, &program, streams, numbered, None) {
+        Ok(process) => wait_for_process(process),
+        Err(status) => return status,
+    };
+    std::process::exit(status)
+}
+
+fn open_output_file(path: &Path, redirection_op: &RedirectionOp) -> io::Result<File>
+{
+    // TODO: Do we want to warn people before we overwrite with > operator?
+    let overwrite = *redirection_op == RedirectionOp::Output;
+    OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(overwrite)
+                .append(!overwrite)
+                .open(path)
 }
 
+// Opens every file of an output redirection, skipping any that fail
+fn open_output_files(redirection: &Redirection) -> Vec<File>
+{
+    redirection.targets.iter()
+        .filter_map(|target| match open_output_file(&to_path(target), &redirection.op) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                eprintln!("oxide: {}: {}", target, err);
+                None
+            }
+        })
+        .collect()
+}
 
-// Output redirection on piped processes
-fn redirect_output_process(process: Option<Child>, filelist: &Vec<&Path>, overwrite: bool)
+// Waits for every stage of a foreground pipeline, which owns the terminal
+// while it runs. If it is stopped with Ctrl-Z the rest of it becomes a job.
+// Also returns whether the pipeline ran out of time.
+#[cfg(target_family = "unix")]
+fn wait_for_stages(stages: Vec<Stage>, description: &str, watchdog: Option<Watchdog>) -> (Vec<i32>, bool)
 {
-    if DEBUG 
+    let process_group = stages.iter().find_map(|stage| match stage {
+        Stage::Process(process) => Some(process.id() as libc::pid_t),
+        Stage::Forked(pid) => Some(*pid),
+        Stage::Finished(_) => None,
+    });
+    if let Some(pgid) = process_group
     {
-        println!("Printing output to files: {:?}", filelist);
+        jobs::give_terminal(pgid, None);
     }
 
-    let mut output = String::new();
-    if let Some(real_process) = process
+    let mut statuses = Vec::new();
+    let mut stopped_pids = Vec::new();
+    // The first signal to kill a stage, reported once for the whole pipeline
+    let mut killed = None;
+    for stage in stages
     {
-        match real_process.wait_with_output()
-        {
-            Ok(command_output) => {
-                let output_data = command_output.stdout;
-                output = 
-                    match String::from_utf8(output_data)
-                    {
-                        Ok(output_string) => output_string,
-                        Err(_) => {
-                            println!("Could not read data from process into string!");
-                            return
-                        }
-                    };
+        let pid = match stage {
+            Stage::Process(process) => process.id() as libc::pid_t,
+            Stage::Forked(pid) => pid,
+            stage => {
+                statuses.push(stage.wait());
+                continue
             }
-            Err(_) => {
-                println!("Could not get output from process during output redirection");
-                return
+        };
+        if !stopped_pids.is_empty()
+        {
+            stopped_pids.push(pid);
+            statuses.push(jobs::STOPPED_STATUS);
+            continue
+        }
+        let state = jobs::wait_for_process(pid);
+        match state {
+            ProcessState::Stopped => stopped_pids.push(pid),
+            ProcessState::Killed(signal, core_dumped) => {
+                killed.get_or_insert((signal, core_dumped));
             }
+            ProcessState::Exited(_) => (),
         }
+        statuses.push(state.status());
     }
 
-    for file in filelist.iter()
+    let timed_out = watchdog.is_some_and(Watchdog::finish);
+    if let Some(pgid) = process_group
     {
-        // TODO: Do we want to warn people before we overwrite with > operator?
-        match OpenOptions::new()
-                          .write(true)
-                          .create(true)
-                          .truncate(overwrite)
-                          .append(!overwrite)
-                          .open(file)
+        let tmodes = jobs::take_terminal();
+        if !stopped_pids.is_empty()
         {
-            Ok(mut fp) => {
-                match fp.write_all(output.as_bytes()) 
-                {
-                    Err(err) => eprintln!("{}", err),
-                    _ => (),
-                }
-            }
-            Err(err) => eprintln!("{}", err),
+            jobs::add_stopped_job(pgid, stopped_pids, description, tmodes);
         }
     }
+    // A pipeline killed for running out of time is reported as such instead
+    if let Some((signal, core_dumped)) = killed.filter(|_| !timed_out)
+    {
+        jobs::report_killed(signal, core_dumped);
+    }
+    (statuses, timed_out)
 }
 
-// Output redirection on individual command
-fn redirect_output(command: &str, arguments: &Vec<String>, filelist: &Vec<&Path>, overwrite: bool)
+#[cfg(target_family = "windows")]
+fn wait_for_stages(stages: Vec<Stage>, _description: &str) -> Vec<i32>
+{
+    stages.into_iter().map(Stage::wait).collect()
+}
+
+fn wait_for_process(mut process: Child) -> i32
 {
-    match execute_command(command, arguments) 
+    match process.wait()
     {
-        Some(output) => {
-            for file in filelist.iter()
-            {
-                // TODO: Do we want to warn people before we overwrite with > operator?
-                match OpenOptions::new()
-                                  .write(true)
-                                  .create(true)
-                                  .truncate(overwrite)
-                                  .append(!overwrite)
-                                  .open(file)
-                {
-                    Ok(mut fp) => {
-                        match fp.write_all(output.as_bytes()) 
-                        {
-                            Err(err) => eprintln!("{}", err),
-                            _ => (),
-                        }
-                    }
-                    Err(err) => eprintln!("{}", err),
-                }
-            }
+        Ok(status) => exit_code(status),
+        Err(err) => {
+            eprintln!("oxide: {}", err);
+            1
         }
-        None => println!("Command {} not understood", command), 
     }
 }
 
-// Input redirection on piped processes
-// TODO: Change expects to handle errors.
-fn redirect_input_process(process: Option<&mut Child>, filelist: &Vec<&Path>)
+// Runs assignments, functions and compound commands in the shell itself
+fn execute_on_command_data(command_data: &CommandData) -> i32
 {
-    if let Some(real_process) = process 
+    // A command made up only of assignments sets shell variables, and one
+    // with redirections only creates or opens their files
+    if command_data.command.is_empty() && command_data.compound.is_none()
     {
-        let file_contents = read_to_string(filelist[0]).expect("Could not read input file");
-
+        let mut copy_threads = Vec::new();
+        let streams = [Stream::Inherit, Stream::Inherit, Stream::Inherit];
+        if let Err(err) = redirect_streams(command_data, streams, &mut copy_threads)
         {
-            let stdin = real_process.stdin.as_mut().expect("Failed to get input handle");
-            stdin.write_all(file_contents.as_bytes());
+            eprintln!("oxide: {}", err);
+            return 1
         }
+        for copy_thread in copy_threads
+        {
+            let _ = copy_thread.join();
+        }
+        return run_in_shell(command_data)
     }
+    execute_redirected(command_data, || run_in_shell(command_data))
 }
 
-// Input redirection on individual command
-fn redirect_input(command: &str, arguments: &Vec<String>, filelist: &Vec<&Path>)
+// Runs what a stage needs the shell for, once its redirections are in place
+fn run_in_shell(command_data: &CommandData) -> i32
 {
-    //TODO: Right now we do input redirection like bash (only first file is used as input)
-    //      This can be changed if we want to be opinionated:
-    //          Concat all data in files and use resulting blob as input
-    //          Run command separately for each file and output each result
-    match BUILTINS.get(command) {
-        Some(comm) => {
-            comm(arguments.iter().map(Path::new).collect::<Vec<&Path>>()); 
-        }
-        None => {
-            // Make a mutable copy of command so we can modify it if its an alias
-            let mut command = command;
-
-            if let Some(comm) = ALIASES.get(command) {
-                command = comm;
-            }
-
-            let mut process = Command::new(command)
-                                      .args(arguments)
-                                      .stdin(Stdio::piped())
-                                      .stdout(Stdio::piped())
-                                      .spawn()
-                                      .expect("Failed to spawn child process to execute command");
-            //redirect_input_process(&mut Some(&process), filelist);
-            redirect_input_process(Some(&mut process), filelist);
-            
-            match process.wait_with_output()
-            {
-                Ok(command_output) => {
-                    let output_data = command_output.stdout;
-                    println!("{}", String::from_utf8(output_data).unwrap());
-                }
-                Err(err) => eprintln!("{}", err)
-            }
+    if let Some(compound) = &command_data.compound
+    {
+        return match compound.entry {
+            ParseNodeType::Subshell => execute_command_list(&compound.children.as_ref().unwrap()[0]),
+            _ => execute_pipeline(compound),
         }
     }
-}
 
-fn execute_on_command_data(command_data: &CommandData)
-{
-    if let Some(redirection_op) = &command_data.redirection_op
-    { 
-        match redirection_op  
+    if command_data.command.is_empty()
+    {
+        for (name, value) in &command_data.assignments
         {
-            RedirectionOp::Output => {
-                redirect_output(
-                    &command_data.command,
-                    &command_data.arguments,
-                    &command_data.redirection_files, 
-                    true
-                );
-            }
-            RedirectionOp::Append => {
-                redirect_output(
-                    &command_data.command,
-                    &command_data.arguments,
-                    &command_data.redirection_files, 
-                    false
-                );
-            }
-            RedirectionOp::Input => {
-                redirect_input(
-                    &command_data.command,
-                    &command_data.arguments,
-                    &command_data.redirection_files, 
-                );
+            if let Err(err) = set_variable(name, value)
+            {
+                eprintln!("oxide: {}", err);
+                return 1
             }
-        }    
+        }
+        return command_data.substitution_status.unwrap_or(0)
     }
-    else
+
+    let function_body = match command_data.lookup {
+        CommandLookup::Any => FUNCTIONS.lock().unwrap().get(&command_data.command).cloned(),
+        _ => None,
+    };
+    if let Some(body) = function_body
     {
-        if let Some(output) = execute_command(&command_data.command, &command_data.arguments)
-        {
-            println!("{}", output);
+        return call_function(&body, &command_data.arguments)
+    }
+    match get_builtin(&command_data.command) {
+        Some(builtin) if runs_shell_code(command_data) => {
+            // By now the builtin's streams are the shell's own
+            let mut builtin_io = BuiltinIo {
+                stdin: Stream::Inherit.into_reader(),
+                stdout: Stream::Inherit.into_writer(1),
+                stderr: Stream::Inherit.into_writer(2),
+                stdout_is_terminal: io::stdout().is_terminal(),
+            };
+            let status = builtin(&command_data.arguments, &mut builtin_io);
+            let _ = builtin_io.stdout.flush();
+            status
+        }
+        _ => {
+            eprintln!("Command {} not understood", command_data.command);
+            127
         }
     }
-        
-            
 }
 
-// TODO: Return result, String if Ok and Error if Err
-fn execute_command(command: &str, arguments: &Vec<String>) -> Option<String>
+// . and eval run commands in the shell, so their redirections have to apply
+// to the shell's own streams, as for a function
+fn runs_shell_code(command_data: &CommandData) -> bool
 {
-    if let Some(comm) = BUILTINS.get(command) {
-        comm(arguments.iter().map(Path::new).collect::<Vec<&Path>>());
-        // Custom BUILTINS will always return emptystring
-        return Some(String::from(""));
-    }
-    
-    // Make a mutable copy of command so we can modify it if its an alias
-    let mut command = command;
+    matches!(command_data.command.as_str(), "." | "eval") && get_builtin(&command_data.command).is_some()
+}
 
-    if let Some(comm) = ALIASES.get(command) {
-        command = comm;
+// Runs shell code with a command's redirections applied to the shell's own
+// streams until it finishes, as for `while read line; do ...; done < file`.
+// The shell's streams are kept above 9, out of the way of the numbers a
+// script would use.
+#[cfg(target_family = "unix")]
+fn execute_redirected<F: FnOnce() -> i32>(command_data: &CommandData, execute: F) -> i32
+{
+    if command_data.redirections.is_empty()
+    {
+        return execute()
     }
-    
-    let process = Command::new(command).args(arguments).spawn();
+    let mut copy_threads = Vec::new();
+    let streams = [Stream::Inherit, Stream::Inherit, Stream::Inherit];
+    let moves = redirect_streams(command_data, streams, &mut copy_threads)
+        .and_then(|(streams, numbered)| prepare_moves(streams, numbered).map_err(|err| err.to_string()));
+    let moves = match moves {
+        Ok(moves) => moves,
+        Err(err) => {
+            eprintln!("oxide: {}", err);
+            return 1
+        }
+    };
 
-    if let Ok(running_process) = process
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    let lowest_saved = moves.iter().map(|(fd, _)| *fd + 1).max().unwrap_or(0).max(10);
+    let mut saved = Vec::new();
+    for (fd, source) in &moves
     {
-        match running_process.wait_with_output()
+        // -1 when the shell didn't have the stream open
+        saved.push((*fd, unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, lowest_saved) }));
+        let result = match source {
+            Some(source) => unsafe { libc::dup2(source.as_raw_fd(), *fd) },
+            None => unsafe { libc::close(*fd) },
+        };
+        if result == -1 && source.is_some()
         {
-            Ok(command_output) => {
-                let output_data = command_output.stdout;
-                return Some(String::from_utf8(output_data).unwrap())
+            eprintln!("oxide: {}", io::Error::last_os_error());
+            break
+        }
+    }
+    // Every stream that was moved into place ran into no problems
+    let status = if saved.len() == moves.len() { execute() } else { 1 };
+    drop(moves);
+
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    for (fd, copy) in saved.into_iter().rev()
+    {
+        unsafe {
+            if copy == -1
+            {
+                libc::close(fd);
+            }
+            else
+            {
+                libc::dup2(copy, fd);
+                libc::close(copy);
             }
-            Err(_) => return None,
         }
     }
-    else
+    for copy_thread in copy_threads
+    {
+        let _ = copy_thread.join();
+    }
+    status
+}
+
+#[cfg(target_family = "windows")]
+fn execute_redirected<F: FnOnce() -> i32>(command_data: &CommandData, execute: F) -> i32
+{
+    if command_data.redirections.is_empty()
+    {
+        return execute()
+    }
+    eprintln!("oxide: redirecting functions and compound commands is not supported on Windows");
+    1
+}
+
+fn exit_code(status: ExitStatus) -> i32
+{
+    // Processes killed by a signal report 128 + the signal number, like sh
+    #[cfg(target_family = "unix")]
     {
- eprintln!("Could not get output from command: {}", command);
-        return None;
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal
+        }
     }
+    status.code().unwrap_or(1)
 }
 
 
@@ -603,39 +2425,43 @@ mod tests {
     #[test]
     fn test_split_input()
     {
-        let mut input = String::from("ls .. . wow");
+        let input = String::from("ls .. . wow");
         let expected_split = vec!("ls", "..", ".", "wow");
-        assert_eq!(split_input(&mut input), expected_split);
+        assert_eq!(split_input(&input).unwrap(), expected_split);
     }
 
     #[test]
     fn test_accumulate_commands_and_arguments()
     {
-        let mut input = String::from("ls . | sort > test.txt");
-        let commands = split_input(&mut input);
-        let ast_root = parse_input(&commands).unwrap();
-        let mut expr_children = ast_root.children.as_ref().unwrap();
+        let input = String::from("ls . | sort > test.txt");
+        let commands = split_input(&input).unwrap();
+        let ast_root = parse_program(&commands).unwrap();
+        let and_or_list = &ast_root.children.as_ref().unwrap()[0];
+        let pipeline = &and_or_list.children.as_ref().unwrap()[0];
+        let expr_children = pipeline.children.as_ref().unwrap();
         
-        let commands_and_arguments = accumulate_commands_and_arguments(&mut expr_children);
+        let commands_and_arguments = accumulate_commands_and_arguments(expr_children);
         let first_command = &commands_and_arguments[0];
         let second_command = &commands_and_arguments[1];
         
         assert_eq!(first_command.command, "ls");
         assert_eq!(first_command.arguments, vec!(String::from(".")));
-        assert_eq!(first_command.redirection_op, None);
-        assert_eq!(first_command.redirection_files, Vec::<&Path>::new());
+        assert_eq!(first_command.redirections, Vec::new());
 
         assert_eq!(second_command.command, "sort");
         assert_eq!(second_command.arguments, Vec::<String>::new());
-        assert_eq!(second_command.redirection_op, Some(RedirectionOp::Output));
-        assert_eq!(second_command.redirection_files, vec!(Path::new("test.txt")));
+        assert_eq!(second_command.redirections, vec!(Redirection {
+            fd: 1,
+            op: RedirectionOp::Output,
+            targets: vec!(String::from("test.txt")),
+        }));
     }
     
     #[test]
     fn test_read_command_expr()
     {
         let mut command_data = CommandData::new();
-        let mut command_expr_children = vec!(
+        let command_expr_children = vec!(
             ParseNode {
                 entry: ParseNodeType::Command(String::from("ls")),
                 children: None,
@@ -662,15 +2488,13 @@ mod tests {
 
         assert_eq!(command_data.command, String::from("ls"));
         assert_eq!(command_data.arguments, vec!(String::from("."), String::from("..")));
-        assert_eq!(command_data.redirection_op, None); 
-        assert_eq!(command_data.redirection_files, Vec::<&Path>::new());
+        assert_eq!(command_data.redirections, Vec::new());
     }
 
     #[test]
     fn test_read_redirection_expr()
     {
-        let mut command_data = CommandData::new();
-        let mut command_expr_children = vec!(
+        let command_expr_children = vec!(
             ParseNode {
                 entry: ParseNodeType::RedirectionOp(RedirectionOp::Input),
                 children: None,
@@ -689,16 +2513,27 @@ mod tests {
             }
         );
 
-        read_redirection_expr(
-            &mut command_data.redirection_op, 
-            &mut command_data.redirection_files, 
-            &command_expr_children
-        );
+        let redirection = read_redirection_expr(&command_expr_children);
+
+        assert_eq!(redirection, Redirection {
+            fd: 0,
+            op: RedirectionOp::Input,
+            targets: vec!(String::from("test1.txt"), String::from("test2.txt")),
+        });
 
-        assert_eq!(command_data.command, String::from(""));
-        assert_eq!(command_data.arguments, Vec::<String>::new());
-        assert_eq!(command_data.redirection_op, Some(RedirectionOp::Input)); 
-        assert_eq!(command_data.redirection_files, vec!(Path::new("test1.txt"), Path::new("test2.txt")));
+    }
+
+    #[test]
+    fn test_run_script_until_syntax_error()
+    {
+        let source = "OXIDE_TEST_BEFORE=ran\nif true\nthen\n  OXIDE_TEST_INSIDE=ran\nfi\nfi\nOXIDE_TEST_AFTER=ran\n";
+        assert_eq!(run_script(source), 2);
+        assert_eq!(get_variable("OXIDE_TEST_BEFORE").as_deref(), Some("ran"));
+        assert_eq!(get_variable("OXIDE_TEST_INSIDE").as_deref(), Some("ran"));
+        assert_eq!(get_variable("OXIDE_TEST_AFTER"), None);
 
+        // A compound command left open is only an error once the script ends
+        assert_eq!(run_script("OXIDE_TEST_OPEN=ran\nwhile true; do\n"), 2);
+        assert_eq!(get_variable("OXIDE_TEST_OPEN").as_deref(), Some("ran"));
     }
 }
@@ -0,0 +1,179 @@
+// Colours for file names, read from LS_COLORS so they match ls and other tools
+use std::collections::HashMap;
+
+// GNU ls's colours for kinds of file when LS_COLORS doesn't give them
+const DEFAULT_KINDS: &str = "di=01;34:ln=01;36:so=01;35:pi=40;33:bd=40;33;01:cd=40;33;01:or=40;31;01:\
+su=37;41:sg=30;43:tw=30;42:ow=34;42:st=37;44:ex=01;32";
+
+// Used only without LS_COLORS: archives in red, images and video in magenta
+// and audio in cyan, as dircolors sets them up
+const DEFAULT_SUFFIXES: &str = "*.tar=01;31:*.tgz=01;31:*.gz=01;31:*.xz=01;31:*.bz2=01;31:*.zst=01;31:\
+*.zip=01;31:*.7z=01;31:*.rar=01;31:*.deb=01;31:*.rpm=01;31:*.jar=01;31:\
+*.jpg=01;35:*.jpeg=01;35:*.png=01;35:*.gif=01;35:*.bmp=01;35:*.svg=01;35:*.webp=01;35:\
+*.mp4=01;35:*.mkv=01;35:*.webm=01;35:*.mov=01;35:*.avi=01;35:\
+*.mp3=00;36:*.flac=00;36:*.ogg=00;36:*.wav=00;36:*.m4a=00;36";
+
+// What a file is, as far as its colour goes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FileKind {
+    File,
+    Executable,
+    Setuid,
+    Setgid,
+    Folder,
+    Sticky,
+    OtherWritable,
+    StickyOtherWritable,
+    Link,
+    BrokenLink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileKind {
+    fn key(self) -> &'static str
+    {
+        match self {
+            FileKind::File => "fi",
+            FileKind::Executable => "ex",
+            FileKind::Setuid => "su",
+            FileKind::Setgid => "sg",
+            FileKind::Folder => "di",
+            FileKind::Sticky => "st",
+            FileKind::OtherWritable => "ow",
+            FileKind::StickyOtherWritable => "tw",
+            FileKind::Link => "ln",
+            FileKind::BrokenLink => "or",
+            FileKind::Fifo => "pi",
+            FileKind::Socket => "so",
+            FileKind::BlockDevice => "bd",
+            FileKind::CharDevice => "cd",
+        }
+    }
+}
+
+pub struct Theme
+{
+    kinds: HashMap<String, String>,
+    // Colours for names ending in a suffix such as .tar, the longest match winning
+    suffixes: Vec<(String, String)>,
+    // ln=target colours links like the files they point to
+    links_as_targets: bool,
+}
+
+impl Theme
+{
+    // Kinds LS_COLORS leaves out keep GNU's colours, but its suffixes replace
+    // the built-in ones so names look the same as they do in ls
+    pub fn new(ls_colors: Option<&str>) -> Theme
+    {
+        let mut theme = Theme {
+            kinds: HashMap::new(),
+            suffixes: Vec::new(),
+            links_as_targets: false,
+        };
+        theme.apply(DEFAULT_KINDS);
+        match ls_colors {
+            Some(ls_colors) if !ls_colors.is_empty() => theme.apply(ls_colors),
+            _ => theme.apply(DEFAULT_SUFFIXES),
+        }
+        theme
+    }
+
+    // Entries are key=code pairs separated by colons, later ones overriding earlier
+    fn apply(&mut self, colors: &str)
+    {
+        for entry in colors.split(':')
+        {
+            let (key, code) = match entry.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if let Some(suffix) = key.strip_prefix('*')
+            {
+                let suffix = suffix.to_lowercase();
+                self.suffixes.retain(|(existing, _)| *existing != suffix);
+                self.suffixes.push((suffix, code.to_string()));
+            }
+            else if key == "ln" && code == "target"
+            {
+                self.links_as_targets = true;
+            }
+            else
+            {
+                self.links_as_targets &= key != "ln";
+                self.kinds.insert(key.to_string(), code.to_string());
+            }
+        }
+    }
+
+    pub fn links_as_targets(&self) -> bool
+    {
+        self.links_as_targets
+    }
+
+    // The colour for a file, if it has one. Suffixes only colour plain files,
+    // as with ls.
+    pub fn code(&self, kind: FileKind, name: &str) -> Option<&str>
+    {
+        let code = match kind {
+            FileKind::File => self.suffix_code(name).or_else(|| self.kinds.get("fi").map(|code| &code[..])),
+            kind => self.kinds.get(kind.key()).or_else(|| match kind {
+                FileKind::Sticky | FileKind::OtherWritable | FileKind::StickyOtherWritable => self.kinds.get("di"),
+                FileKind::BrokenLink => self.kinds.get("ln"),
+                _ => None,
+            }).map(|code| &code[..]),
+        };
+        // 0 and 00 mean the terminal's usual colour
+        code.filter(|code| !code.is_empty() && code.chars().any(|c| c != '0'))
+    }
+
+    fn suffix_code(&self, name: &str) -> Option<&str>
+    {
+        let name = name.to_lowercase();
+        self.suffixes.iter()
+            .filter(|(suffix, _)| name.ends_with(&suffix[..]))
+            .max_by_key(|(suffix, _)| suffix.len())
+            .map(|(_, code)| &code[..])
+    }
+}
+
+pub fn paint(text: &str, code: Option<&str>) -> String
+{
+    match code {
+        Some(code) => format!("\x1b[{}m{}\x1b[0m", code, text),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_theme()
+    {
+        let theme = Theme::new(None);
+        assert_eq!(theme.code(FileKind::Folder, "src"), Some("01;34"));
+        assert_eq!(theme.code(FileKind::Executable, "build.tar"), Some("01;32"));
+        assert_eq!(theme.code(FileKind::File, "Backup.TAR.GZ"), Some("01;31"));
+        assert_eq!(theme.code(FileKind::File, "main.rs"), None);
+        assert_eq!(paint("src", theme.code(FileKind::Folder, "src")), "\x1b[01;34msrc\x1b[0m");
+    }
+
+    #[test]
+    fn test_ls_colors()
+    {
+        let theme = Theme::new(Some("di=00;33:ln=target:*.rs=0;32:*.tar.gz=01;33:fi=00:no:tw=01"));
+        assert_eq!(theme.code(FileKind::Folder, "src"), Some("00;33"));
+        assert_eq!(theme.code(FileKind::File, "main.rs"), Some("0;32"));
+        assert_eq!(theme.code(FileKind::File, "a.tar.gz"), Some("01;33"));
+        // The built-in suffixes are replaced, and fi=00 is no colour
+        assert_eq!(theme.code(FileKind::File, "a.zip"), None);
+        assert_eq!(theme.code(FileKind::Socket, "socket"), Some("01;35"));
+        assert_eq!(theme.code(FileKind::StickyOtherWritable, "tmp"), Some("01"));
+        assert!(theme.links_as_targets());
+    }
+}
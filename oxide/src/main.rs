@@ -1,17 +1,14 @@
+#![allow(clippy::needless_return)]
+
 #[macro_use]
 extern crate lazy_static;
 
 use std::borrow::Cow::{self, Borrowed, Owned};
 use std::process;
-use std::collections::HashMap;
-use std::iter::FromIterator;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio, Child};
-use std::io;
-use std::io::Write;
-use std::fs::{File, OpenOptions, read_to_string};
-use std::error::Error;
-
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::Path;
 
 use rustyline::completion::{Completer, FilenameCompleter, Pair};
 use rustyline::config::OutputStreamType;
@@ -22,43 +19,141 @@ use rustyline::validate::{self, MatchingBracketValidator, Validator};
 use rustyline::error::ReadlineError;
 use rustyline::Helper;
 
+mod alias;
+mod arithmetic;
+mod blocks;
 mod config;
+mod encoding;
+mod expansion;
+mod file_info;
+mod history;
+mod ignore;
+#[cfg(target_family = "unix")]
+mod jobs;
+mod lexer;
+mod ls_colors;
+mod notify;
+mod options;
 mod parser;
+mod path_cache;
+#[cfg(target_family = "unix")]
+mod pty;
+#[cfg(target_family = "unix")]
+mod signals;
+mod status;
+mod suggest;
+mod timeout;
+mod timer;
+mod traps;
 mod commands;
 mod logic;
+mod variables;
 
+use crate::commands::alias::{alias, unalias};
+use crate::commands::blocks::blocks;
 use crate::commands::change_folder::change_folder;
 #[cfg(target_family = "unix")]
 use crate::commands::clear::clear;
 #[cfg(target_family = "windows")]
 use crate::commands::clear_windows::clear;
+use crate::commands::control_flow::{break_loop, continue_loop, null, return_function};
+use crate::commands::create::{create, create_folder, touch};
+use crate::commands::exec::exec;
 use crate::commands::exit::exit;
+use crate::commands::history::history;
+#[cfg(target_family = "unix")]
+use crate::commands::jobs::{bg, disown, fg, jobs, wait};
+use crate::commands::list::list;
+use crate::commands::lookup::{builtin, command, hash, type_command, which};
+use crate::commands::read::read;
+use crate::commands::remove::{remove, remove_folder};
+use crate::commands::show::show;
+use crate::commands::source::{dot, eval};
+use crate::commands::Builtin;
+use crate::commands::status::status;
+#[cfg(target_family = "unix")]
+use crate::commands::timeout::timeout;
+use crate::commands::trap::trap;
+#[cfg(target_family = "unix")]
+use crate::commands::ulimit::ulimit;
+use crate::commands::variables::{export, local, readonly, set, shift, unset};
 
+use alias::load_aliases;
 use config::OxideHistory;
-use logic::run;
+use logic::{run, run_exit_trap, run_script};
+use options::OPTIONS;
 
 lazy_static! {
-    static ref BUILTINS: HashMap<&'static str, fn(Vec<&Path>) -> ()> = {
+    static ref BUILTINS: HashMap<&'static str, Builtin> = {
         let mut builtin_hm = HashMap::new();
-        builtin_hm.insert("exit", exit as fn(Vec<&Path>) -> ());
+        builtin_hm.insert("exit", exit as Builtin);
         builtin_hm.insert("cd", change_folder);
         builtin_hm.insert("cf", change_folder);
         builtin_hm.insert("clear", clear);
+        builtin_hm.insert("alias", alias);
+        builtin_hm.insert("unalias", unalias);
+        builtin_hm.insert("export", export);
+        builtin_hm.insert("unset", unset);
+        builtin_hm.insert("set", set);
+        builtin_hm.insert("shift", shift);
+        builtin_hm.insert("readonly", readonly);
+        builtin_hm.insert("local", local);
+        builtin_hm.insert(".", dot);
+        builtin_hm.insert("eval", eval);
+        builtin_hm.insert("break", break_loop);
+        builtin_hm.insert("continue", continue_loop);
+        builtin_hm.insert("return", return_function);
+        builtin_hm.insert(":", null);
+        builtin_hm.insert("status", status);
+        builtin_hm.insert("history", history);
+        builtin_hm.insert("read", read);
+        builtin_hm.insert("trap", trap);
+        builtin_hm.insert("blocks", blocks);
+        builtin_hm.insert("type", type_command);
+        builtin_hm.insert("which", which);
+        builtin_hm.insert("hash", hash);
+        builtin_hm.insert("command", command);
+        builtin_hm.insert("builtin", builtin);
+        builtin_hm.insert("exec", exec);
+        builtin_hm.insert("list", list);
+        builtin_hm.insert("show", show);
+        builtin_hm.insert("create", create);
+        builtin_hm.insert("touch", touch);
+        builtin_hm.insert("createf", create_folder);
+        builtin_hm.insert("remove", remove);
+        builtin_hm.insert("removef", remove_folder);
+        #[cfg(target_family = "unix")]
+        {
+            builtin_hm.insert("jobs", jobs);
+            builtin_hm.insert("fg", fg);
+            builtin_hm.insert("bg", bg);
+            builtin_hm.insert("wait", wait);
+            builtin_hm.insert("disown", disown);
+            builtin_hm.insert("timeout", timeout);
+            builtin_hm.insert("ulimit", ulimit);
+        }
 
         builtin_hm
     };
 
-    static ref ALIASES: HashMap<&'static str, &'static str> = {
-        let mut alias_hm = HashMap::new();
-        alias_hm.insert("list", "ls");
-        alias_hm.insert("show", "cat");
-        alias_hm.insert("remove", "rm");
-        alias_hm.insert("removef", "rm -r");
-        alias_hm.insert("create", "touch"); 
-        alias_hm.insert("createf", "mkdir");
-
-        alias_hm
-    };        
+    // Builtins that are not part of POSIX sh, hidden in POSIX mode
+    static ref OXIDE_ONLY_BUILTINS: HashSet<&'static str> = {
+        let mut oxide_only = HashSet::new();
+        oxide_only.insert("cf");
+        oxide_only.insert("status");
+        oxide_only.insert("blocks");
+        oxide_only.insert("which");
+        oxide_only.insert("timeout");
+        oxide_only.insert("list");
+        oxide_only.insert("show");
+        oxide_only.insert("create");
+        oxide_only.insert("touch");
+        oxide_only.insert("createf");
+        oxide_only.insert("remove");
+        oxide_only.insert("removef");
+
+        oxide_only
+    };
 }
 
 pub struct MyHelper {
@@ -120,6 +215,10 @@ impl Validator for MyHelper {
         &self,
         ctx: &mut validate::ValidationContext,
     ) -> rustyline::Result<validate::ValidationResult> {
+        // A here-document carries on over the lines after its command
+        if lexer::tokenize(ctx.input()).is_err_and(|err| err.in_here_document()) {
+            return Ok(validate::ValidationResult::Incomplete)
+        }
         self.validator.validate(ctx)
     }
 
@@ -130,7 +229,96 @@ impl Validator for MyHelper {
 
 impl Helper for MyHelper {}
 
+const USAGE: &str = "usage: oxide [--posix] [-c command [name [args ...]] | script [args ...]]";
+
+// Where commands are read from, decided by the command line arguments
+enum Input {
+    Interactive,
+    Command(String),
+    Script(String),
+}
+
+fn parse_arguments() -> Input {
+    // Arguments that aren't valid UTF-8 are carried through escaped
+    let mut arguments = env::args_os().map(|argument| encoding::from_os_str(&argument));
+
+    // Like bash, behave as POSIX sh when invoked under that name
+    let invoked_as = arguments.next().unwrap_or_default();
+    if Path::new(&invoked_as).file_name().is_some_and(|name| name == "sh") {
+        OPTIONS.lock().unwrap().posix = true;
+    }
+
+    let mut input = Input::Interactive;
+    while let Some(argument) = arguments.next()
+    {
+        match &argument[..] {
+            "--posix" => OPTIONS.lock().unwrap().posix = true,
+            "-c" => {
+                let command = match arguments.next() {
+                    Some(command) => command,
+                    None => {
+                        eprintln!("oxide: -c: option requires an argument");
+                        process::exit(2);
+                    }
+                };
+                if let Some(name) = arguments.next() {
+                    variables::set_script_name(&name);
+                }
+                input = Input::Command(command);
+                break;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            option if option.starts_with('-') && option != "-" => {
+                eprintln!("oxide: {}: invalid option\n{}", encoding::display(option), USAGE);
+                process::exit(2);
+            }
+            script => {
+                variables::set_script_name(script);
+                input = Input::Script(script.to_string());
+                break;
+            }
+        }
+    }
+
+    variables::set_positional_parameters(arguments.collect());
+    return input
+}
+
 fn main() {
+    let input = parse_arguments();
+    #[cfg(target_family = "unix")]
+    signals::init_signals();
+    match input {
+        Input::Command(command) => {
+            let status = run_script(&command);
+            run_exit_trap(status);
+            process::exit(status)
+        }
+        Input::Script(path) => {
+            let source = match fs::read(encoding::to_path(&path)) {
+                Ok(source) => encoding::from_bytes(&source),
+                Err(err) => {
+                    eprintln!("oxide: {}: {}", encoding::display(&path), err);
+                    process::exit(127);
+                }
+            };
+            let status = run_script(&source);
+            run_exit_trap(status);
+            process::exit(status)
+        }
+        Input::Interactive => {
+            OPTIONS.lock().unwrap().interactive = true;
+            #[cfg(target_family = "unix")]
+            {
+                signals::init_interactive_signals();
+                jobs::init_job_control();
+            }
+        }
+    }
+
     println!("Welcome to Oxide! A shell written entirely in Rust.");
     let rl_config = Config::builder()
         .history_ignore_space(true)
@@ -151,10 +339,15 @@ fn main() {
     rl.set_helper(Some(helper));
 
     let oxide_history = OxideHistory::new();
+    // alias and unalias are part of sh, but Oxide's saved aliases are not
+    if !options::posix_mode() {
+        load_aliases();
+    }
 
     if let Err(err) = run(rl, oxide_history)
     {
         eprintln!("Error when running shell {}", err);
         process::exit(1);
     }
+    run_exit_trap(status::last_status());
 }
@@ -0,0 +1,59 @@
+// Announces commands that took a long time once they finish, for when the
+// terminal has been left while they ran
+use std::io::{self, Write};
+use std::time::Duration;
+
+// Such as 45s, 2m3s or 1h0m12s
+pub fn format_elapsed(elapsed: Duration) -> String
+{
+    let seconds = elapsed.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h{}m{}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+pub fn notification_text(command: &str, status: i32, elapsed: Duration) -> String
+{
+    // Control characters would end the escape sequence early
+    let command: String = command.chars().filter(|c| !c.is_control()).collect();
+    match status {
+        0 => format!("{} finished after {}", command, format_elapsed(elapsed)),
+        status => format!("{} failed with status {} after {}", command, status, format_elapsed(elapsed)),
+    }
+}
+
+// Rings the bell and sends both the OSC 9 notification used by iTerm2 and
+// Windows Terminal and the OSC 777 one used by VTE based terminals and foot.
+// Terminals ignore whichever they don't understand.
+pub fn notify_terminal(command: &str, status: i32, elapsed: Duration)
+{
+    let text = notification_text(command, status, elapsed);
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x07\x1b]9;{}\x07\x1b]777;notify;Oxide;{}\x07", text, text);
+    let _ = stdout.flush();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_elapsed()
+    {
+        assert_eq!(format_elapsed(Duration::from_millis(45_900)), "45s");
+        assert_eq!(format_elapsed(Duration::from_secs(123)), "2m3s");
+        assert_eq!(format_elapsed(Duration::from_secs(3612)), "1h0m12s");
+    }
+
+    #[test]
+    fn test_notification_text()
+    {
+        assert_eq!(notification_text("make\x1b", 2, Duration::from_secs(90)), "make failed with status 2 after 1m30s");
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::timeout::{format_duration, parse_duration};
+
+#[derive(Debug, Clone, Default)]
+pub struct ShellOptions {
+    // POSIX sh compatibility: POSIX lexing, parsing and expansion rules and no
+    // Oxide-only syntax, aliases or builtins
+    pub posix: bool,
+    // Whether commands are being read from the prompt rather than a script
+    pub interactive: bool,
+    // A pipeline fails if any stage fails, not just the last one
+    pub pipefail: bool,
+    // set -e: a command that fails, other than one whose status is being
+    // tested, ends the shell
+    pub errexit: bool,
+    // set -u: expanding a variable that isn't set is an error
+    pub nounset: bool,
+    // Record the output of each foreground command as a block, running it
+    // under a pseudo-terminal
+    pub blocks: bool,
+    // A directory name on its own as a command changes into it
+    pub auto_cd: bool,
+    // Time limit for foreground pipelines that don't set their own with timeout
+    pub timeout: Option<Duration>,
+    // Foreground pipelines run from the prompt that take at least this long
+    // are announced when they finish
+    pub notify: Option<Duration>,
+}
+
+lazy_static! {
+    pub static ref OPTIONS: Mutex<ShellOptions> = Mutex::new(ShellOptions::default());
+}
+
+pub fn posix_mode() -> bool
+{
+    OPTIONS.lock().unwrap().posix
+}
+
+pub fn interactive() -> bool
+{
+    OPTIONS.lock().unwrap().interactive
+}
+
+pub fn pipefail() -> bool
+{
+    OPTIONS.lock().unwrap().pipefail
+}
+
+pub fn errexit() -> bool
+{
+    OPTIONS.lock().unwrap().errexit
+}
+
+pub fn nounset() -> bool
+{
+    OPTIONS.lock().unwrap().nounset
+}
+
+pub fn blocks() -> bool
+{
+    OPTIONS.lock().unwrap().blocks
+}
+
+pub fn auto_cd() -> bool
+{
+    OPTIONS.lock().unwrap().auto_cd
+}
+
+pub fn default_timeout() -> Option<Duration>
+{
+    OPTIONS.lock().unwrap().timeout
+}
+
+pub fn notify_after() -> Option<Duration>
+{
+    OPTIONS.lock().unwrap().notify
+}
+
+// Options that can be changed with `set -o name` and `set +o name`, and how
+// `set -o` shows them
+pub fn option_states() -> Vec<(&'static str, String)>
+{
+    let options = OPTIONS.lock().unwrap();
+    let on_off = |value: bool| String::from(if value { "on" } else { "off" });
+    vec!(
+        ("auto_cd", on_off(options.auto_cd)),
+        ("blocks", on_off(options.blocks)),
+        ("errexit", on_off(options.errexit)),
+        ("notify", options.notify.map_or_else(|| on_off(false), format_duration)),
+        ("nounset", on_off(options.nounset)),
+        ("pipefail", on_off(options.pipefail)),
+        ("timeout", options.timeout.map_or_else(|| on_off(false), format_duration)),
+    )
+}
+
+// The option a single letter flag such as `set -e` stands for
+pub fn flag_option(flag: char) -> Option<&'static str>
+{
+    match flag {
+        'e' => Some("errexit"),
+        'u' => Some("nounset"),
+        _ => None,
+    }
+}
+
+// Options with a duration, such as `set -o timeout=DURATION`, are turned off
+// with `set +o timeout`, or for timeout a duration of 0
+pub fn set_option(name: &str, value: bool) -> Result<(), String>
+{
+    let mut options = OPTIONS.lock().unwrap();
+    let (name, duration) = match name.split_once('=') {
+        Some((name, duration)) if value => match parse_duration(duration) {
+            Some(duration) => (name, Some(duration)),
+            None => return Err(format!("{}: invalid duration", duration)),
+        },
+        Some(_) => return Err(format!("{}: invalid option name", name)),
+        None => (name, None),
+    };
+    match (name, duration) {
+        ("auto_cd", None) => options.auto_cd = value,
+        ("blocks", None) => options.blocks = value,
+        ("errexit", None) => options.errexit = value,
+        ("nounset", None) => options.nounset = value,
+        ("pipefail", None) => options.pipefail = value,
+        ("notify", _) if value && duration.is_none() => {
+            return Err(String::from("notify: usage: set -o notify=DURATION"))
+        }
+        ("notify", _) => options.notify = duration,
+        ("timeout", _) if value && duration.is_none() => {
+            return Err(String::from("timeout: usage: set -o timeout=DURATION"))
+        }
+        ("timeout", _) => options.timeout = duration.filter(|duration| !duration.is_zero()),
+        _ => return Err(format!("{}: invalid option name", name)),
+    }
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use std::collections::HashSet;
 use std::fmt;
 
+use crate::lexer::is_control_operator;
+use crate::options::posix_mode;
+
 type Result<T> = std::result::Result<T, ParseError>;
 
 #[derive(Debug, Clone)]
@@ -9,6 +12,14 @@ pub struct ParseError {
     token_index: usize,
 }
 
+impl ParseError {
+    // Whether the input ran out before the error, so more of it could make
+    // it parse, as with an if still waiting for its fi
+    pub fn at_end_of_input(&self, input_tokens: &[String]) -> bool {
+        self.token_index >= input_tokens.len()
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Parse Error at token {0}: {1}", self.token_index, self.message)
@@ -18,7 +29,13 @@ impl fmt::Display for ParseError {
 lazy_static! {
     static ref REDIRECTION_OPS: HashSet<&'static str> = 
         [
-            ">", ">>", "<", "|"
+            ">", ">>", "<", ">&", "<&", "<<", "<<-", "|"
+        ].iter().cloned().collect();
+
+    static ref RESERVED_WORDS: HashSet<&'static str> =
+        [
+            "if", "then", "elif", "else", "fi", "while", "until", "do", "done",
+            "for", "in", "case", "esac", "{", "}", "!", "time"
         ].iter().cloned().collect();
 }
 
@@ -28,22 +45,53 @@ pub enum RedirectionOp {
     Output,
     Append,
     Input,
+    // >&n and <&n, making one file descriptor a copy of another
+    DuplicateOutput,
+    DuplicateInput,
+    // << and <<-, whose target is the text of the document
+    HereDocument,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListOp {
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum ParseNodeType {
+    CommandList,
+    AndOrList,
+    // An and-or list run in the background with '&', along with its text
+    Background(String),
+    ListOp(ListOp),
+    Negation,
+    // A pipeline timed with `time`, and whether -p asked for the POSIX format
+    Timed(bool),
+    IfExpr,
+    WhileExpr,
+    UntilExpr,
+    ForExpr(String),
+    CaseExpr,
+    CaseItem,
+    WordList,
+    BraceGroup,
+    // ( list ), run in a copy of the shell
+    Subshell,
+    FunctionDef(String),
     Expr,
     CommandExpr,
-    RedirectionPipeExpr,
     PipeExpr,
     RedirectionExpr,
     Command(String),
     File(String),
     RedirectionOp(RedirectionOp),
+    // The file descriptor a redirection applies to, when given
+    IoNumber(i32),
     Pipe,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ParseNode {
     pub entry: ParseNodeType,
     pub children: Option<Vec<ParseNode>>,
@@ -51,27 +99,522 @@ pub struct ParseNode {
 
 
 
-pub fn parse_input(input_tokens: &Vec<&str>) -> Result<ParseNode>
+pub fn is_reserved_word(token: &str) -> bool
+{
+    RESERVED_WORDS.contains(token)
+}
+
+// Redirection operators may start with a file descriptor number, as in 2>
+fn is_redirection_op(token: &str) -> bool
+{
+    REDIRECTION_OPS.contains(token.trim_start_matches(|c: char| c.is_ascii_digit()))
+}
+
+// Whether the token at the index redirects a stream, rather than piping one
+fn at_redirection(input_tokens: &[String], input_index: usize) -> bool
+{
+    input_tokens.get(input_index).is_some_and(|token| is_redirection_op(token) && token != "|")
+}
+
+fn is_compound_start(token: &str) -> bool
+{
+    matches!(token, "if" | "while" | "until" | "for" | "case" | "{" | "(")
+}
+
+// Parses a whole program: pipelines joined into lists by ';', '&&', '||' and
+// newlines, along with compound commands and function definitions
+pub fn parse_program(input_tokens: &[String]) -> Result<ParseNode>
+{
+    let mut input_index: usize = 0;
+    let command_list_node = parse_command_list(input_tokens, &mut input_index, &[])?;
+
+    if input_index < input_tokens.len()
+    {
+        return Err(unexpected_token(input_tokens, input_index))
+    }
+    return Ok(command_list_node)
+}
+
+fn unexpected_token(input_tokens: &[String], input_index: usize) -> ParseError
+{
+    let token = match input_tokens.get(input_index).map(|token| token.as_str()) {
+        Some("\n") => "newline",
+        Some(token) => token,
+        None => "end of input",
+    };
+    ParseError {
+        message: format!("unexpected token '{0}'", token),
+        token_index: input_index,
+    }
+}
+
+fn expect_token(input_tokens: &[String], input_index: &mut usize, expected: &str) -> Result<()>
+{
+    if *input_index < input_tokens.len() && input_tokens[*input_index] == expected
+    {
+        *input_index += 1;
+        return Ok(())
+    }
+    let mut error = unexpected_token(input_tokens, *input_index);
+    error.message = format!("expected '{0}' but got {1}", expected, error.message.trim_start_matches("unexpected token "));
+    return Err(error)
+}
+
+fn skip_newlines(input_tokens: &[String], input_index: &mut usize)
+{
+    while *input_index < input_tokens.len() && input_tokens[*input_index] == "\n"
+    {
+        *input_index += 1;
+    }
+}
+
+fn at_terminator(input_tokens: &[String], input_index: usize, terminators: &[&str]) -> bool
+{
+    input_index == input_tokens.len() || terminators.contains(&input_tokens[input_index].as_str())
+}
+
+// Parses and-or lists separated by ';', '&' or newlines until one of the
+// terminators is reached in command position
+fn parse_command_list(input_tokens: &[String], input_index: &mut usize, terminators: &[&str]) -> Result<ParseNode>
+{
+    let mut command_list_node = ParseNode {
+        entry: ParseNodeType::CommandList,
+        children: Some(Vec::new()),
+    };
+
+    loop {
+        skip_newlines(input_tokens, input_index);
+        if at_terminator(input_tokens, *input_index, terminators)
+        {
+            break
+        }
+
+        let list_start = *input_index;
+        parse_and_or_list(input_tokens, input_index, &mut command_list_node)?;
+
+        if at_terminator(input_tokens, *input_index, terminators)
+        {
+            break
+        }
+        match input_tokens[*input_index].as_str() {
+            ";" | "\n" => *input_index += 1,
+            "&" => {
+                let children = command_list_node.children.as_mut().unwrap();
+                let and_or_node = children.pop().unwrap();
+                children.push(ParseNode {
+                    entry: ParseNodeType::Background(input_tokens[list_start..*input_index].join(" ")),
+                    children: Some(vec!(and_or_node)),
+                });
+                *input_index += 1;
+            }
+            _ => return Err(unexpected_token(input_tokens, *input_index)),
+        }
+    }
+
+    return Ok(command_list_node)
+}
+
+// A command list that must contain at least one command, such as an if condition
+fn parse_compound_list(input_tokens: &[String], input_index: &mut usize, terminators: &[&str]) -> Result<ParseNode>
+{
+    let command_list_node = parse_command_list(input_tokens, input_index, terminators)?;
+    if command_list_node.children.as_ref().unwrap().is_empty()
+    {
+        return Err(unexpected_token(input_tokens, *input_index))
+    }
+    return Ok(command_list_node)
+}
+
+fn parse_and_or_list(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+{
+    let mut and_or_node = ParseNode {
+        entry: ParseNodeType::AndOrList,
+        children: Some(Vec::new()),
+    };
+
+    parse_pipeline(input_tokens, input_index, &mut and_or_node)?;
+
+    while *input_index < input_tokens.len()
+    {
+        let list_op = match input_tokens[*input_index].as_str() {
+            "&&" => ListOp::And,
+            "||" => ListOp::Or,
+            _ => break,
+        };
+        and_or_node.children.as_mut().unwrap().push(ParseNode {
+            entry: ParseNodeType::ListOp(list_op),
+            children: None,
+        });
+        *input_index += 1;
+        skip_newlines(input_tokens, input_index);
+
+        parse_pipeline(input_tokens, input_index, &mut and_or_node)?;
+    }
+
+    tree_node.children.as_mut().unwrap().push(and_or_node);
+    return Ok(())
+}
+
+// A pipeline of simple commands, or a compound command
+fn parse_pipeline(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+{
+    if *input_index == input_tokens.len()
+    {
+        return Err(unexpected_token(input_tokens, *input_index))
+    }
+
+    let pipeline_node = match input_tokens[*input_index].as_str() {
+        "!" => {
+            *input_index += 1;
+            let mut negation_node = ParseNode {
+                entry: ParseNodeType::Negation,
+                children: Some(Vec::new()),
+            };
+            parse_pipeline(input_tokens, input_index, &mut negation_node)?;
+            negation_node
+        }
+        "time" => parse_time(input_tokens, input_index)?,
+        // A compound command on its own stays as it is, so the commands in
+        // it are the ones traps and errexit see. A subshell is one command
+        // to the shell running it.
+        token if is_compound_start(token) => {
+            let mut expr_node = ParseNode {
+                entry: ParseNodeType::Expr,
+                children: Some(Vec::new()),
+            };
+            parse_compound_stage(input_tokens, input_index, &mut expr_node)?;
+            let children = expr_node.children.as_ref().unwrap();
+            match children.len() {
+                1 if children[0].entry != ParseNodeType::Subshell => expr_node.children.unwrap().pop().unwrap(),
+                _ => expr_node,
+            }
+        }
+        token if is_reserved_word(token) => return Err(unexpected_token(input_tokens, *input_index)),
+        _ if input_tokens.get(*input_index + 1).map(|token| token.as_str()) == Some("(") => {
+            parse_function_def(input_tokens, input_index)?
+        }
+        _ => parse_expr(input_tokens, input_index)?,
+    };
+
+    tree_node.children.as_mut().unwrap().push(pipeline_node);
+    return Ok(())
+}
+
+// time [-p] [pipeline]. Without a pipeline it reports the time taken by nothing.
+fn parse_time(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    *input_index += 1;
+    let posix = input_tokens.get(*input_index).map(|token| token.as_str()) == Some("-p");
+    if posix
+    {
+        *input_index += 1;
+    }
+    let mut time_node = ParseNode {
+        entry: ParseNodeType::Timed(posix),
+        children: Some(Vec::new()),
+    };
+    match input_tokens.get(*input_index).map(|token| token.as_str()) {
+        None | Some(";") | Some("\n") | Some("&") | Some("&&") | Some("||") => (),
+        _ => parse_pipeline(input_tokens, input_index, &mut time_node)?,
+    }
+    return Ok(time_node)
+}
+
+// if list then list [elif list then list]... [else list] fi
+fn parse_if(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    let mut if_node = ParseNode {
+        entry: ParseNodeType::IfExpr,
+        children: Some(Vec::new()),
+    };
+    *input_index += 1;
+
+    loop {
+        let condition = parse_compound_list(input_tokens, input_index, &["then"])?;
+        expect_token(input_tokens, input_index, "then")?;
+        let body = parse_compound_list(input_tokens, input_index, &["elif", "else", "fi"])?;
+        if_node.children.as_mut().unwrap().push(condition);
+        if_node.children.as_mut().unwrap().push(body);
+
+        match input_tokens.get(*input_index).map(|token| token.as_str()) {
+            Some("elif") => *input_index += 1,
+            Some("else") => {
+                *input_index += 1;
+                let else_body = parse_compound_list(input_tokens, input_index, &["fi"])?;
+                if_node.children.as_mut().unwrap().push(else_body);
+                expect_token(input_tokens, input_index, "fi")?;
+                break
+            }
+            _ => {
+                expect_token(input_tokens, input_index, "fi")?;
+                break
+            }
+        }
+    }
+
+    return Ok(if_node)
+}
+
+// while list do list done, or until list do list done
+fn parse_loop(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    let entry = if input_tokens[*input_index] == "while" { ParseNodeType::WhileExpr } else { ParseNodeType::UntilExpr };
+    *input_index += 1;
+
+    let condition = parse_compound_list(input_tokens, input_index, &["do"])?;
+    expect_token(input_tokens, input_index, "do")?;
+    let body = parse_compound_list(input_tokens, input_index, &["done"])?;
+    expect_token(input_tokens, input_index, "done")?;
+
+    return Ok(ParseNode {
+        entry,
+        children: Some(vec!(condition, body)),
+    })
+}
+
+// for name [in word...] do list done. Without `in` the loop runs over "$@".
+fn parse_for(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    *input_index += 1;
+    let name = match input_tokens.get(*input_index) {
+        Some(name) if !is_control_operator(name) && !is_redirection_op(name) => name.clone(),
+        _ => return Err(unexpected_token(input_tokens, *input_index)),
+    };
+    *input_index += 1;
+    skip_newlines(input_tokens, input_index);
+
+    let mut word_list_node = ParseNode {
+        entry: ParseNodeType::WordList,
+        children: Some(Vec::new()),
+    };
+    if input_tokens.get(*input_index).map(|token| token.as_str()) == Some("in")
+    {
+        *input_index += 1;
+        while *input_index < input_tokens.len() && !is_control_operator(&input_tokens[*input_index])
+        {
+            word_list_node.children.as_mut().unwrap().push(ParseNode {
+                entry: ParseNodeType::File(input_tokens[*input_index].clone()),
+                children: None,
+            });
+            *input_index += 1;
+        }
+    }
+    else
+    {
+        word_list_node.children.as_mut().unwrap().push(ParseNode {
+            entry: ParseNodeType::File(String::from("\"$@\"")),
+            children: None,
+        });
+    }
+
+    if let Some(";") = input_tokens.get(*input_index).map(|token| token.as_str())
+    {
+        *input_index += 1;
+    }
+    skip_newlines(input_tokens, input_index);
+    expect_token(input_tokens, input_index, "do")?;
+    let body = parse_compound_list(input_tokens, input_index, &["done"])?;
+    expect_token(input_tokens, input_index, "done")?;
+
+    return Ok(ParseNode {
+        entry: ParseNodeType::ForExpr(name),
+        children: Some(vec!(word_list_node, body)),
+    })
+}
+
+// case word in [(]pattern[|pattern]...) list ;; ... esac
+fn parse_case(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    *input_index += 1;
+    let word = match input_tokens.get(*input_index) {
+        Some(word) if !is_control_operator(word) => word.clone(),
+        _ => return Err(unexpected_token(input_tokens, *input_index)),
+    };
+    *input_index += 1;
+    skip_newlines(input_tokens, input_index);
+    expect_token(input_tokens, input_index, "in")?;
+
+    let mut case_node = ParseNode {
+        entry: ParseNodeType::CaseExpr,
+        children: Some(vec!(ParseNode {
+            entry: ParseNodeType::File(word),
+            children: None,
+        })),
+    };
+
+    loop {
+        skip_newlines(input_tokens, input_index);
+        match input_tokens.get(*input_index).map(|token| token.as_str()) {
+            Some("esac") => {
+                *input_index += 1;
+                break
+            }
+            Some("(") => *input_index += 1,
+            None => return Err(unexpected_token(input_tokens, *input_index)),
+            _ => (),
+        }
+
+        let mut pattern_list_node = ParseNode {
+            entry: ParseNodeType::WordList,
+            children: Some(Vec::new()),
+        };
+        loop {
+            match input_tokens.get(*input_index) {
+                Some(pattern) if !is_control_operator(pattern) && pattern != "|" => {
+                    pattern_list_node.children.as_mut().unwrap().push(ParseNode {
+                        entry: ParseNodeType::File(pattern.clone()),
+                        children: None,
+                    });
+                    *input_index += 1;
+                }
+                _ => return Err(unexpected_token(input_tokens, *input_index)),
+            }
+            match input_tokens.get(*input_index).map(|token| token.as_str()) {
+                Some("|") => *input_index += 1,
+                _ => break,
+            }
+        }
+        expect_token(input_tokens, input_index, ")")?;
+
+        let body = parse_command_list(input_tokens, input_index, &[";;", "esac"])?;
+        case_node.children.as_mut().unwrap().push(ParseNode {
+            entry: ParseNodeType::CaseItem,
+            children: Some(vec!(pattern_list_node, body)),
+        });
+
+        if let Some(";;") = input_tokens.get(*input_index).map(|token| token.as_str())
+        {
+            *input_index += 1;
+        }
+    }
+
+    return Ok(case_node)
+}
+
+fn parse_compound_command(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
 {
-    let mut input_index: usize = 0; 
-    return parse_expr(input_tokens, &mut input_index);
+    match input_tokens.get(*input_index).map(|token| token.as_str()) {
+        Some("if") => parse_if(input_tokens, input_index),
+        Some("while") | Some("until") => parse_loop(input_tokens, input_index),
+        Some("for") => parse_for(input_tokens, input_index),
+        Some("case") => parse_case(input_tokens, input_index),
+        Some("{") => parse_brace_group(input_tokens, input_index),
+        Some("(") => parse_subshell(input_tokens, input_index),
+        _ => Err(unexpected_token(input_tokens, *input_index)),
+    }
 }
 
-fn parse_expr(input_tokens: &Vec<&str>, input_index: &mut usize) -> Result<ParseNode>
+// A compound command as a stage of a pipeline, with any redirections after
+// it, as in `while read line; do ...; done < input.txt | sort`
+fn parse_compound_stage(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+{
+    let compound_node = parse_compound_command(input_tokens, input_index)?;
+    tree_node.children.as_mut().unwrap().push(compound_node);
+    while at_redirection(input_tokens, *input_index)
+    {
+        parse_redirection_expr(input_tokens, input_index, tree_node, posix_mode())?;
+    }
+    // Anything else after it is left to whatever the command is part of,
+    // as with the `then` in `if { true; } then`
+    match input_tokens.get(*input_index).map(|token| token.as_str()) {
+        Some("|") => parse_pipe_expr(input_tokens, input_index, tree_node),
+        _ => Ok(()),
+    }
+}
+
+// ( list )
+fn parse_subshell(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    *input_index += 1;
+    let body = parse_compound_list(input_tokens, input_index, &[")"])?;
+    expect_token(input_tokens, input_index, ")")?;
+
+    return Ok(ParseNode {
+        entry: ParseNodeType::Subshell,
+        children: Some(vec!(body)),
+    })
+}
+
+// { list; }
+fn parse_brace_group(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    *input_index += 1;
+    let body = parse_compound_list(input_tokens, input_index, &["}"])?;
+    expect_token(input_tokens, input_index, "}")?;
+
+    return Ok(ParseNode {
+        entry: ParseNodeType::BraceGroup,
+        children: Some(vec!(body)),
+    })
+}
+
+// name() compound-command [redirections]. Redirections apply each time the
+// function is called.
+fn parse_function_def(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
+{
+    let name = input_tokens[*input_index].clone();
+    *input_index += 1;
+    expect_token(input_tokens, input_index, "(")?;
+    expect_token(input_tokens, input_index, ")")?;
+    skip_newlines(input_tokens, input_index);
+
+    let mut body = parse_compound_command(input_tokens, input_index)?;
+    if at_redirection(input_tokens, *input_index)
+    {
+        let mut expr_node = ParseNode {
+            entry: ParseNodeType::Expr,
+            children: Some(vec!(body)),
+        };
+        while at_redirection(input_tokens, *input_index)
+        {
+            parse_redirection_expr(input_tokens, input_index, &mut expr_node, posix_mode())?;
+        }
+        body = expr_node;
+    }
+
+    return Ok(ParseNode {
+        entry: ParseNodeType::FunctionDef(name),
+        children: Some(vec!(body)),
+    })
+}
+
+fn parse_expr(input_tokens: &[String], input_index: &mut usize) -> Result<ParseNode>
 {
     let mut parse_tree = ParseNode {
         entry: ParseNodeType::Expr,
         children: Some(Vec::new()),
     };
     
-    parse_command_expr(input_tokens, input_index, &mut parse_tree)?;
-    
-    parse_redirection_pipe_expr(input_tokens, input_index, &mut parse_tree)?;
-     
+    parse_simple_command(input_tokens, input_index, &mut parse_tree)?;
+
     return Ok(parse_tree)
 }
 
-fn parse_command_expr(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+// A command with its arguments and redirections, which may come before the
+// command as in `> out.txt echo hi`. Those take one target each, and a
+// command can be made of nothing but them.
+fn parse_simple_command(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+{
+    let mut redirected = false;
+    while at_redirection(input_tokens, *input_index)
+    {
+        parse_redirection_expr(input_tokens, input_index, tree_node, true)?;
+        redirected = true;
+    }
+
+    let command_ended = match input_tokens.get(*input_index) {
+        Some(token) => is_control_operator(token) || token == "|",
+        None => true,
+    };
+    if !(redirected && command_ended)
+    {
+        parse_command_expr(input_tokens, input_index, tree_node)?;
+    }
+
+    parse_redirection_pipe_expr(input_tokens, input_index, tree_node)
+}
+
+fn parse_command_expr(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
 {
     let mut command_expr_node = ParseNode {
         entry: ParseNodeType::CommandExpr,
@@ -87,31 +630,48 @@ fn parse_command_expr(input_tokens: &Vec<&str>, input_index: &mut usize, tree_no
     return Ok(())
 }
 
-fn parse_redirection_pipe_expr(input_tokens: &Vec<&str>, input_index: &mut usize, mut tree_node: &mut ParseNode) -> Result<()>
+fn parse_redirection_pipe_expr(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
 {
-    if *input_index == input_tokens.len() 
+    if *input_index == input_tokens.len() || is_control_operator(&input_tokens[*input_index])
     {
         return Ok(())
     }
     
-    if input_tokens[*input_index] != "|"
+    // Any number of redirections, such as `> out.txt 2>&1`. In POSIX mode
+    // each takes one target and later words are more arguments, so
+    // `echo hi > out.txt there` writes "hi there".
+    while *input_index < input_tokens.len()
+        && !is_control_operator(&input_tokens[*input_index])
+        && input_tokens[*input_index] != "|"
     {
-        parse_redirection_expr(input_tokens, input_index, &mut tree_node)?;
+        if is_redirection_op(&input_tokens[*input_index])
+        {
+            parse_redirection_expr(input_tokens, input_index, tree_node, posix_mode())?;
+        }
+        else
+        {
+            let mut arguments_node = ParseNode {
+                entry: ParseNodeType::CommandExpr,
+                children: Some(Vec::new()),
+            };
+            parse_filelist(input_tokens, input_index, &mut arguments_node)?;
+            tree_node.children.as_mut().unwrap().push(arguments_node);
+        }
     }
 
-    parse_pipe_expr(input_tokens, input_index, &mut tree_node)?;
+    parse_pipe_expr(input_tokens, input_index, tree_node)?;
     
     return Ok(())
 }
 
-fn parse_pipe_expr(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+fn parse_pipe_expr(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
 {
-    if *input_index == input_tokens.len() 
+    if *input_index == input_tokens.len() || is_control_operator(&input_tokens[*input_index])
     {
         return Ok(())
     }
 
-    let token = input_tokens[*input_index];
+    let token = &input_tokens[*input_index];
     if token != "|"
     {
         return Err(ParseError {
@@ -132,17 +692,28 @@ fn parse_pipe_expr(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node:
 
     pipe_expr_node.children.as_mut().unwrap().push(pipe_node);
     *input_index += 1;
+    skip_newlines(input_tokens, input_index);
 
-    parse_command_expr(input_tokens, input_index, &mut pipe_expr_node)?;
-
-    parse_redirection_pipe_expr(input_tokens, input_index, &mut pipe_expr_node)?;
+    match input_tokens.get(*input_index) {
+        Some(token) if is_compound_start(token) => {
+            parse_compound_stage(input_tokens, input_index, &mut pipe_expr_node)?
+        }
+        _ => parse_simple_command(input_tokens, input_index, &mut pipe_expr_node)?,
+    }
 
     tree_node.children.as_mut().unwrap().push(pipe_expr_node);
 
     return Ok(())
 }
 
-fn parse_redirection_expr(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+// A redirection operator and its target. Outside POSIX mode output can go
+// to several files at once, as in `> a.txt b.txt`.
+fn parse_redirection_expr(
+    input_tokens: &[String],
+    input_index: &mut usize,
+    tree_node: &mut ParseNode,
+    single_target: bool
+) -> Result<()>
 { 
     if *input_index == input_tokens.len() 
     {
@@ -154,16 +725,32 @@ fn parse_redirection_expr(input_tokens: &Vec<&str>, input_index: &mut usize, tre
         children: Some(Vec::new()),
     };
 
+    // A here-document is always the one word the lexer made of it
+    let single_target = single_target
+        || input_tokens[*input_index].trim_start_matches(|c: char| c.is_ascii_digit()).starts_with("<<");
     parse_redirection_op(input_tokens, input_index, &mut redirection_expr_node)?;
-        
-    parse_filelist(input_tokens, input_index, &mut redirection_expr_node)?; 
+
+    // Every redirection needs a target, as in `> out.txt`
+    match input_tokens.get(*input_index) {
+        Some(target) if single_target && !is_redirection_op(target) && !is_control_operator(target) => {
+            redirection_expr_node.children.as_mut().unwrap().push(ParseNode {
+                entry: ParseNodeType::File(target.to_string()),
+                children: None,
+            });
+            *input_index += 1;
+        }
+        Some(token) if !is_redirection_op(token) && !is_control_operator(token) => {
+            parse_filelist(input_tokens, input_index, &mut redirection_expr_node)?;
+        }
+        _ => return Err(unexpected_token(input_tokens, *input_index)),
+    }
     
     tree_node.children.as_mut().unwrap().push(redirection_expr_node);
     
     return Ok(())
 }
     
-fn parse_command(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+fn parse_command(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
 {   
     if *input_index == input_tokens.len() 
     {
@@ -176,7 +763,11 @@ fn parse_command(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &
         })
     }
 
-    let command = input_tokens[*input_index];
+    let command = &input_tokens[*input_index];
+    if is_control_operator(command) || is_redirection_op(command)
+    {
+        return Err(unexpected_token(input_tokens, *input_index))
+    }
 
     // Add Expr to AST node 
     let command_node = ParseNode {
@@ -188,7 +779,7 @@ fn parse_command(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &
     return Ok(())
 }
 
-fn parse_filelist(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+fn parse_filelist(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
 {
     // epsilon rule if all tokens have been parsed or not filename
     if *input_index == input_tokens.len()
@@ -196,9 +787,9 @@ fn parse_filelist(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node:
         return Ok(())
     }
     
-    let token = input_tokens[*input_index];
-    // Keep adding files until we hit a redirection operator
-    if !REDIRECTION_OPS.contains(token)
+    let token = &input_tokens[*input_index];
+    // Keep adding files until we hit a redirection or control operator
+    if !is_redirection_op(token) && !is_control_operator(token)
     {
         // Add token as file to syntax tree
         let file_node = ParseNode {
@@ -212,20 +803,37 @@ fn parse_filelist(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node:
     return Ok(())
 }
 
-fn parse_redirection_op(input_tokens: &Vec<&str>, input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
+fn parse_redirection_op(input_tokens: &[String], input_index: &mut usize, tree_node: &mut ParseNode) -> Result<()>
 {
-    let token = input_tokens[*input_index];
+    let token = &input_tokens[*input_index];
+    let operator = token.trim_start_matches(|c: char| c.is_ascii_digit());
     
-    let redirection_op_token = match token {
+    let redirection_op_token = match operator {
         ">"  => RedirectionOp::Output,
         ">>" => RedirectionOp::Append,
         "<"  => RedirectionOp::Input,
+        ">&" => RedirectionOp::DuplicateOutput,
+        "<&" => RedirectionOp::DuplicateInput,
+        "<<" | "<<-" => RedirectionOp::HereDocument,
         _    => return Err(ParseError { 
                     message: format!("expected redirection operator, got '{0}'", token),
                     token_index: *input_index,
                 })
     };
 
+    let io_number = &token[..token.len() - operator.len()];
+    if !io_number.is_empty()
+    {
+        let fd = io_number.parse().map_err(|_| ParseError {
+            message: format!("bad file descriptor '{0}'", io_number),
+            token_index: *input_index,
+        })?;
+        tree_node.children.as_mut().unwrap().push(ParseNode {
+            entry: ParseNodeType::IoNumber(fd),
+            children: None,
+        });
+    }
+
     let redirection_op_node = ParseNode {
         entry: ParseNodeType::RedirectionOp(redirection_op_token),
         children: None,
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::encoding::{from_os_str, to_path};
+
+// Where commands were last found in PATH, as reported by hash
+struct PathCache {
+    // The PATH the entries were found with. A different PATH empties the cache.
+    path: Option<OsString>,
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub hits: usize,
+}
+
+lazy_static! {
+    static ref PATH_CACHE: Mutex<PathCache> = Mutex::new(PathCache {
+        path: None,
+        entries: HashMap::new(),
+    });
+}
+
+// Names with a slash are paths already and are never looked up
+pub fn is_path(name: &str) -> bool
+{
+    name.contains('/') || (cfg!(target_family = "windows") && name.contains('\\'))
+}
+
+#[cfg(target_family = "unix")]
+pub fn is_executable(path: &Path) -> bool
+{
+    use std::os::unix::fs::PermissionsExt;
+    match path.metadata() {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(target_family = "windows")]
+pub fn is_executable(path: &Path) -> bool
+{
+    path.is_file()
+}
+
+// Candidate files for a name in one PATH directory
+fn candidates(directory: &Path, name: &str) -> Vec<PathBuf>
+{
+    let mut candidates = vec!(directory.join(to_path(name)));
+    if cfg!(target_family = "windows") && Path::new(name).extension().is_none() {
+        candidates.push(directory.join(format!("{}.exe", name)));
+    }
+    candidates
+}
+
+// Every executable called `name` in PATH, in search order
+pub fn search_path(name: &str) -> Vec<PathBuf>
+{
+    let path = match env::var_os("PATH") {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    env::split_paths(&path)
+        .flat_map(|directory| candidates(&directory, name))
+        .filter(|candidate| is_executable(candidate))
+        .collect()
+}
+
+// The name of every executable in PATH
+pub fn path_commands() -> Vec<String>
+{
+    let path = match env::var_os("PATH") {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    env::split_paths(&path)
+        .filter_map(|directory| fs::read_dir(directory).ok())
+        .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+        .filter(|entry| is_executable(&entry.path()))
+        .map(|entry| from_os_str(&entry.file_name()))
+        .collect()
+}
+
+// Empties the cache if PATH has changed since it was filled
+fn check_path(cache: &mut PathCache)
+{
+    let path = env::var_os("PATH");
+    if cache.path != path {
+        cache.entries.clear();
+        cache.path = path;
+    }
+}
+
+// Finds the file a command name runs, from the cache when it is still there
+pub fn find_command(name: &str) -> Option<PathBuf>
+{
+    if is_path(name) {
+        return Some(to_path(name))
+    }
+    let mut cache = PATH_CACHE.lock().unwrap();
+    check_path(&mut cache);
+    if let Some(entry) = cache.entries.get_mut(name) {
+        if is_executable(&entry.path) {
+            entry.hits += 1;
+            return Some(entry.path.clone())
+        }
+    }
+
+    let path = search_path(name).into_iter().next();
+    match &path {
+        Some(path) => {
+            cache.entries.insert(name.to_string(), CacheEntry { path: path.clone(), hits: 1 });
+        }
+        None => {
+            cache.entries.remove(name);
+        }
+    }
+    path
+}
+
+// Looks a command up and remembers it without counting a hit, as `hash name` does
+pub fn hash_command(name: &str) -> bool
+{
+    let path = search_path(name).into_iter().next();
+    let mut cache = PATH_CACHE.lock().unwrap();
+    check_path(&mut cache);
+    match path {
+        Some(path) => {
+            cache.entries.insert(name.to_string(), CacheEntry { path, hits: 0 });
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn cached_command(name: &str) -> Option<CacheEntry>
+{
+    let mut cache = PATH_CACHE.lock().unwrap();
+    check_path(&mut cache);
+    cache.entries.get(name).cloned()
+}
+
+// Every remembered command, sorted by name
+pub fn cached_commands() -> Vec<(String, CacheEntry)>
+{
+    let mut cache = PATH_CACHE.lock().unwrap();
+    check_path(&mut cache);
+    let mut entries: Vec<(String, CacheEntry)> = cache.entries
+        .iter()
+        .map(|(name, entry)| (name.clone(), entry.clone()))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+pub fn forget_command(name: &str) -> bool
+{
+    PATH_CACHE.lock().unwrap().entries.remove(name).is_some()
+}
+
+pub fn clear_cache()
+{
+    PATH_CACHE.lock().unwrap().entries.clear();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_path()
+    {
+        assert!(is_path("./script.sh"));
+        assert!(is_path("/bin/ls"));
+        assert!(!is_path("ls"));
+    }
+}
@@ -0,0 +1,109 @@
+// Runs a command's output through a pseudo-terminal so the command still sees
+// a terminal while the shell records what it writes. Only available on unix.
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+// How long the reader waits for more output once the command has finished
+const DRAIN_TIMEOUT_MS: libc::c_int = 50;
+
+pub struct PtyCapture {
+    slave: File,
+    finished: Arc<AtomicBool>,
+    reader: JoinHandle<Vec<u8>>,
+}
+
+// Only worth doing when the shell's own output is a terminal
+pub fn can_capture() -> bool
+{
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+impl PtyCapture {
+    // Opens a pseudo-terminal the size of the shell's terminal, and starts
+    // copying everything written to it to the shell's stdout
+    pub fn open() -> io::Result<PtyCapture> {
+        let (master, slave) = unsafe {
+            let mut tmodes: libc::termios = std::mem::zeroed();
+            let have_tmodes = libc::tcgetattr(libc::STDOUT_FILENO, &mut tmodes) == 0;
+            // Newlines are translated by the real terminal, not twice
+            tmodes.c_oflag &= !libc::OPOST;
+            let mut size: libc::winsize = std::mem::zeroed();
+            let have_size = libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0;
+
+            let mut master = -1;
+            let mut slave = -1;
+            let result = libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                if have_tmodes { &tmodes } else { std::ptr::null() },
+                if have_size { &size } else { std::ptr::null() },
+            );
+            if result != 0 {
+                return Err(io::Error::last_os_error())
+            }
+            libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+            libc::fcntl(slave, libc::F_SETFD, libc::FD_CLOEXEC);
+            (File::from_raw_fd(master), File::from_raw_fd(slave))
+        };
+
+        let finished = Arc::new(AtomicBool::new(false));
+        let reader_finished = Arc::clone(&finished);
+        let reader = thread::spawn(move || copy_output(master, &reader_finished));
+        Ok(PtyCapture { slave, finished, reader })
+    }
+
+    // A handle on the terminal end, for a command's stdout or stderr
+    pub fn terminal(&self) -> io::Result<File> {
+        self.slave.try_clone()
+    }
+
+    // Waits for the rest of the output once the command has finished,
+    // returning all of it
+    pub fn finish(self) -> Vec<u8> {
+        drop(self.slave);
+        self.finished.store(true, Ordering::SeqCst);
+        self.reader.join().unwrap_or_default()
+    }
+
+    // Leaves output to keep being copied until every process writing to the
+    // terminal has exited, for a command that was stopped rather than finished
+    pub fn detach(self) {
+        drop(self.slave);
+    }
+}
+
+fn copy_output(mut master: File, finished: &AtomicBool) -> Vec<u8>
+{
+    let mut output = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let mut poll_fd = libc::pollfd { fd: master.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let ready = unsafe { libc::poll(&mut poll_fd, 1, DRAIN_TIMEOUT_MS) };
+        if ready == 0 {
+            if finished.load(Ordering::SeqCst) {
+                break
+            }
+            continue
+        }
+        if ready == -1 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue
+        }
+
+        // Reading fails with EIO once nothing has the terminal end open
+        match master.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(count) => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&buffer[..count]);
+                let _ = stdout.flush();
+                output.extend_from_slice(&buffer[..count]);
+            }
+        }
+    }
+    output
+}
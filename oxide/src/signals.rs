@@ -0,0 +1,245 @@
+// Signal dispositions of the shell and of the processes it starts. Only
+// available on unix.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::options;
+
+// Set by SIGINT in an interactive shell, so running loops and lists stop
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Set by SIGCHLD, so background jobs get reaped at the next safe point
+static CHILD_CHANGED: AtomicBool = AtomicBool::new(false);
+
+// Signals the shell ignores itself, as a bit mask, put back to their defaults
+// in children. Kept in an atomic as it is read between fork and exec.
+static IGNORED: AtomicU64 = AtomicU64::new(0);
+
+// Signals the shell ignores when they are not trapped
+static SHELL_IGNORED: AtomicU64 = AtomicU64::new(0);
+
+// Trapped signals that have arrived, as a bit mask, whose traps run at the
+// next safe point
+static PENDING_TRAPS: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn handle_interrupt(_signal: libc::c_int)
+{
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn handle_child(_signal: libc::c_int)
+{
+    CHILD_CHANGED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn handle_trapped(signal: libc::c_int)
+{
+    PENDING_TRAPS.fetch_or(1 << signal, Ordering::SeqCst);
+    if signal == libc::SIGCHLD {
+        CHILD_CHANGED.store(true, Ordering::SeqCst);
+    }
+}
+
+fn install_handler(signal: libc::c_int, handler: extern "C" fn(libc::c_int), flags: libc::c_int) -> bool
+{
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = flags;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, std::ptr::null_mut()) == 0
+    }
+}
+
+// Every shell watches for children changing state
+pub fn init_signals()
+{
+    install_handler(libc::SIGCHLD, handle_child, libc::SA_RESTART);
+}
+
+// An interactive shell survives Ctrl-C and Ctrl-\. SIGINT is caught rather than
+// ignored so it can still stop a loop running in the shell itself. Left
+// without SA_RESTART so a builtin blocked reading input gives up.
+pub fn init_interactive_signals()
+{
+    install_handler(libc::SIGINT, handle_interrupt, 0);
+    ignore_signal(libc::SIGQUIT);
+    ignore_signal(libc::SIGTERM);
+}
+
+pub fn ignore_signal(signal: libc::c_int)
+{
+    unsafe {
+        libc::signal(signal, libc::SIG_IGN);
+    }
+    IGNORED.fetch_or(1 << signal, Ordering::SeqCst);
+    SHELL_IGNORED.fetch_or(1 << signal, Ordering::SeqCst);
+}
+
+// Runs a trap when the signal arrives. Children get the default disposition
+// back from exec.
+pub fn trap_signal(signal: libc::c_int) -> Result<(), String>
+{
+    if !install_handler(signal, handle_trapped, libc::SA_RESTART) {
+        return Err(String::from("signal can't be trapped"))
+    }
+    IGNORED.fetch_and(!(1 << signal), Ordering::SeqCst);
+    Ok(())
+}
+
+// An empty trap ignores the signal in the shell and everything it runs
+pub fn ignore_trapped_signal(signal: libc::c_int) -> Result<(), String>
+{
+    if unsafe { libc::signal(signal, libc::SIG_IGN) } == libc::SIG_ERR {
+        return Err(String::from("signal can't be trapped"))
+    }
+    IGNORED.fetch_and(!(1 << signal), Ordering::SeqCst);
+    Ok(())
+}
+
+// Puts back the disposition the shell uses when the signal is not trapped
+pub fn restore_signal(signal: libc::c_int)
+{
+    restore_disposition(signal, options::interactive());
+}
+
+// As restore_signal, for a forked child that can't lock the shell's options.
+// Only touches atomics and signal dispositions.
+pub fn restore_disposition(signal: libc::c_int, interactive: bool)
+{
+    PENDING_TRAPS.fetch_and(!(1 << signal), Ordering::SeqCst);
+    if signal == libc::SIGCHLD {
+        install_handler(signal, handle_child, libc::SA_RESTART);
+    } else if signal == libc::SIGINT && interactive {
+        install_handler(signal, handle_interrupt, 0);
+    } else if SHELL_IGNORED.load(Ordering::SeqCst) & (1 << signal) != 0 {
+        unsafe {
+            libc::signal(signal, libc::SIG_IGN);
+        }
+        IGNORED.fetch_or(1 << signal, Ordering::SeqCst);
+    } else {
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+}
+
+// Trapped signals that have arrived since the last call
+pub fn take_trapped_signals() -> Vec<libc::c_int>
+{
+    let pending = PENDING_TRAPS.swap(0, Ordering::SeqCst);
+    (1..64).filter(|signal| pending & (1 << signal) != 0).collect()
+}
+
+// Whether children need their signals reset before they run
+pub fn ignoring_signals() -> bool
+{
+    IGNORED.load(Ordering::SeqCst) != 0
+}
+
+// Called in a child process before it runs anything. Handlers are reset by
+// exec, so only ignored signals need putting back.
+pub fn reset_signals()
+{
+    let ignored = IGNORED.load(Ordering::SeqCst);
+    for signal in 1..64
+    {
+        if ignored & (1 << signal) != 0 {
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+        }
+    }
+}
+
+// Without job control a background job shares the terminal's process group,
+// so it ignores Ctrl-C and Ctrl-\ and so does everything it runs
+pub fn ignore_keyboard_signals()
+{
+    reset_signals();
+    IGNORED.store(0, Ordering::SeqCst);
+    SHELL_IGNORED.store(0, Ordering::SeqCst);
+    for signal in [libc::SIGINT, libc::SIGQUIT].iter()
+    {
+        unsafe {
+            libc::signal(*signal, libc::SIG_IGN);
+        }
+        SHELL_IGNORED.fetch_or(1 << signal, Ordering::SeqCst);
+    }
+}
+
+pub fn interrupted() -> bool
+{
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+pub fn set_interrupted()
+{
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+pub fn clear_interrupted()
+{
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+// Whether a child has changed state since the last call
+pub fn take_child_changed() -> bool
+{
+    CHILD_CHANGED.swap(false, Ordering::SeqCst)
+}
+
+// What gets printed when a foreground process is killed by a signal
+pub fn describe_signal(signal: libc::c_int) -> Option<&'static str>
+{
+    let description = match signal {
+        libc::SIGHUP => "Hangup",
+        libc::SIGINT => "Interrupted",
+        libc::SIGQUIT => "Quit",
+        libc::SIGILL => "Illegal instruction",
+        libc::SIGTRAP => "Trace/breakpoint trap",
+        libc::SIGABRT => "Aborted",
+        libc::SIGBUS => "Bus error",
+        libc::SIGFPE => "Floating point exception",
+        libc::SIGKILL => "Killed",
+        libc::SIGUSR1 => "User defined signal 1",
+        libc::SIGSEGV => "Segmentation fault",
+        libc::SIGUSR2 => "User defined signal 2",
+        libc::SIGALRM => "Alarm clock",
+        libc::SIGTERM => "Terminated",
+        libc::SIGXCPU => "CPU time limit exceeded",
+        libc::SIGXFSZ => "File size limit exceeded",
+        _ => return None,
+    };
+    Some(description)
+}
+
+// The report for a killed pipeline. A broken pipe is expected when a later
+// stage stops reading, so it goes unreported.
+pub fn kill_report(signal: libc::c_int, core_dumped: bool) -> Option<String>
+{
+    if signal == libc::SIGPIPE {
+        return None
+    }
+    let description = match describe_signal(signal) {
+        Some(description) => description.to_string(),
+        None => format!("Killed by signal {}", signal),
+    };
+    if core_dumped {
+        Some(format!("{} (core dumped)", description))
+    } else {
+        Some(description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kill_report()
+    {
+        assert_eq!(kill_report(libc::SIGINT, false), Some(String::from("Interrupted")));
+        assert_eq!(kill_report(libc::SIGSEGV, true), Some(String::from("Segmentation fault (core dumped)")));
+        assert_eq!(kill_report(libc::SIGPIPE, false), None);
+    }
+}
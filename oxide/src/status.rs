@@ -0,0 +1,109 @@
+use std::sync::Mutex;
+
+lazy_static! {
+    // Exit status of the most recent pipeline, $?
+    static ref LAST_STATUS: Mutex<i32> = Mutex::new(0);
+
+    // Exit status of each stage of the most recent pipeline, PIPESTATUS
+    static ref PIPE_STATUS: Mutex<Vec<i32>> = Mutex::new(vec!(0));
+}
+
+pub fn last_status() -> i32
+{
+    *LAST_STATUS.lock().unwrap()
+}
+
+pub fn set_last_status(status: i32)
+{
+    *LAST_STATUS.lock().unwrap() = status;
+}
+
+pub fn pipe_status() -> Vec<i32>
+{
+    PIPE_STATUS.lock().unwrap().clone()
+}
+
+pub fn set_pipe_status(statuses: Vec<i32>)
+{
+    *PIPE_STATUS.lock().unwrap() = statuses;
+}
+
+// Name of the signal behind a status above 128, the convention used for
+// processes killed by a signal
+#[cfg(target_family = "unix")]
+pub fn signal_name(status: i32) -> Option<&'static str>
+{
+    if status <= 128 {
+        return None
+    }
+    let name = match status - 128 {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGVTALRM => "SIGVTALRM",
+        libc::SIGPROF => "SIGPROF",
+        libc::SIGWINCH => "SIGWINCH",
+        libc::SIGIO => "SIGIO",
+        libc::SIGSYS => "SIGSYS",
+        libc::SIGURG => "SIGURG",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(target_family = "windows")]
+pub fn signal_name(_status: i32) -> Option<&'static str>
+{
+    None
+}
+
+// One line of JSON describing a status and the statuses of each pipeline
+// stage, for scripts to parse
+pub fn status_report(status: i32, pipe_status: &[i32]) -> String
+{
+    let signal = match signal_name(status) {
+        Some(name) => format!("\"{}\"", name),
+        None => String::from("null"),
+    };
+    let pipe_status: Vec<String> = pipe_status.iter().map(|status| status.to_string()).collect();
+    format!(
+        "{{\"status\": {}, \"success\": {}, \"signal\": {}, \"pipestatus\": [{}]}}",
+        status, status == 0, signal, pipe_status.join(", ")
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_report()
+    {
+        assert_eq!(status_report(0, &[0]), "{\"status\": 0, \"success\": true, \"signal\": null, \"pipestatus\": [0]}");
+        assert_eq!(status_report(1, &[1, 0]), "{\"status\": 1, \"success\": false, \"signal\": null, \"pipestatus\": [1, 0]}");
+        #[cfg(target_family = "unix")]
+        assert_eq!(status_report(130, &[130]),
+            "{\"status\": 130, \"success\": false, \"signal\": \"SIGINT\", \"pipestatus\": [130]}");
+    }
+}
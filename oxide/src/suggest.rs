@@ -0,0 +1,109 @@
+// "Did you mean" suggestions for commands that can't be found
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+// At most this many suggestions are shown
+const MAX_SUGGESTIONS: usize = 3;
+
+// Optimal string alignment distance: insertions, deletions, substitutions and
+// swaps of neighbouring characters each count as one edit, so gti is one edit
+// from git
+pub fn edit_distance(a: &str, b: &str) -> usize
+{
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec!(vec!(0; b.len() + 1); a.len() + 1);
+    for (i, row) in distances.iter_mut().enumerate()
+    {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate()
+    {
+        *distance = j;
+    }
+
+    for i in 1..=a.len()
+    {
+        for j in 1..=b.len()
+        {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1]
+            {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+// The names closest to `name`, best first. Short names allow one edit and
+// longer ones one more for every three characters.
+pub fn closest(name: &str, candidates: &BTreeSet<String>) -> Vec<String>
+{
+    let allowed = (name.chars().count() / 3).max(1);
+    let mut matches: Vec<(usize, &String)> = candidates.iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .collect();
+    matches.sort();
+    matches.into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+// Asks whether to run the corrected command line instead, reading the answer
+// from `input`. Only y or yes runs it; running out of input counts as no.
+pub fn offer_correction(correction: &str, input: &mut impl BufRead, output: &mut impl Write) -> bool
+{
+    let _ = write!(output, "Run `{}` instead? [y/N] ", correction);
+    let _ = output.flush();
+    let mut answer = String::new();
+    if input.read_line(&mut answer).is_err()
+    {
+        return false
+    }
+    let answer = answer.trim();
+    answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance()
+    {
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("sl", "ls"), 1);
+        assert_eq!(edit_distance("grpe", "grep"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_closest()
+    {
+        let candidates: BTreeSet<String> = ["git", "grep", "gzip", "cat"].iter().map(|name| name.to_string()).collect();
+        assert_eq!(closest("gti", &candidates), vec!(String::from("git")));
+        assert_eq!(closest("grpe", &candidates), vec!(String::from("grep")));
+        assert!(closest("xyz", &candidates).is_empty());
+    }
+
+    #[test]
+    fn test_offer_correction()
+    {
+        let mut output = Vec::new();
+        assert!(offer_correction("git status", &mut "y\n".as_bytes(), &mut output));
+        assert_eq!(String::from_utf8(output).unwrap(), "Run `git status` instead? [y/N] ");
+        assert!(offer_correction("git status", &mut "Yes\n".as_bytes(), &mut Vec::new()));
+        assert!(!offer_correction("git status", &mut "\n".as_bytes(), &mut Vec::new()));
+        assert!(!offer_correction("git status", &mut "".as_bytes(), &mut Vec::new()));
+    }
+}
@@ -0,0 +1,199 @@
+// Time limits on foreground pipelines, from `timeout` or the default set with
+// `set -o timeout=DURATION`. A pipeline that runs over its limit is sent
+// SIGTERM, then SIGKILL if it is still there after a grace period. Only
+// enforced on unix.
+use std::time::Duration;
+
+#[cfg(target_family = "unix")]
+use crate::traps::{parse_condition, TrapCondition};
+
+// Exit status of a pipeline that ran out of time, as GNU timeout uses
+pub const TIMEOUT_STATUS: i32 = 124;
+// How long a pipeline has to exit after SIGTERM before it is killed
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(5);
+#[cfg(target_family = "unix")]
+pub const DEFAULT_SIGNAL: libc::c_int = libc::SIGTERM;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limit {
+    pub duration: Duration,
+    pub grace: Duration,
+    // Sent first, before SIGKILL
+    #[cfg(target_family = "unix")]
+    pub signal: libc::c_int,
+}
+
+impl Limit {
+    // The limit `set -o timeout=DURATION` gives
+    pub fn new(duration: Duration) -> Limit {
+        Limit {
+            duration,
+            grace: DEFAULT_GRACE,
+            #[cfg(target_family = "unix")]
+            signal: DEFAULT_SIGNAL,
+        }
+    }
+}
+
+// A number of seconds, or a number followed by s, m, h or d, such as 1.5m.
+// For a time limit 0 means there is none.
+pub fn parse_duration(text: &str) -> Option<Duration>
+{
+    let (number, unit) = match text.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => (&text[..index], unit),
+        _ => (text, 's'),
+    };
+    let scale = match unit {
+        's' => 1.0,
+        'm' => 60.0,
+        'h' => 60.0 * 60.0,
+        'd' => 24.0 * 60.0 * 60.0,
+        _ => return None,
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => {
+            Duration::try_from_secs_f64(number * scale).ok()
+        }
+        _ => None,
+    }
+}
+
+// A duration as it would be written for parse_duration
+pub fn format_duration(duration: Duration) -> String
+{
+    let seconds = duration.as_secs_f64();
+    if seconds.fract() == 0.0 { format!("{}s", seconds) } else { format!("{:.3}s", seconds) }
+}
+
+// Reads the `[-s signal] [-k grace] duration` arguments of `timeout`, in
+// GNU's short or long forms, returning the limit and how many arguments it
+// took up. None if they aren't valid or use an option it doesn't know.
+pub fn parse_timeout_arguments(arguments: &[String]) -> Option<(Limit, usize)>
+{
+    let mut limit = Limit::new(Duration::ZERO);
+    let mut index = 0;
+    loop {
+        let argument = arguments.get(index)?;
+        // A value is joined to its option or the argument after it
+        let (option, value) = match argument.as_str() {
+            "--" => {
+                index += 1;
+                break
+            }
+            "-s" | "-k" | "--signal" | "--kill-after" => (argument.as_str(), None),
+            long if long.starts_with("--") => match long.split_once('=') {
+                Some((option, value)) => (option, Some(value)),
+                None => return None,
+            },
+            short if short.starts_with('-') && short.len() > 2 => (&short[..2], Some(&short[2..])),
+            _ => break,
+        };
+        let value = match value {
+            Some(value) => value,
+            None => {
+                index += 1;
+                arguments.get(index)?
+            }
+        };
+        match option {
+            #[cfg(target_family = "unix")]
+            "-s" | "--signal" => limit.signal = parse_signal(value)?,
+            "-k" | "--kill-after" => limit.grace = parse_duration(value)?,
+            _ => return None,
+        }
+        index += 1;
+    }
+    limit.duration = parse_duration(arguments.get(index)?)?;
+    Some((limit, index + 1))
+}
+
+// A signal name such as TERM or SIGINT, or its number
+#[cfg(target_family = "unix")]
+fn parse_signal(spec: &str) -> Option<libc::c_int>
+{
+    match parse_condition(spec)? {
+        TrapCondition::Signal(signal) => Some(signal),
+        _ => None,
+    }
+}
+
+// Watches over a running pipeline, signalling it once it has run out of time
+#[cfg(target_family = "unix")]
+pub struct Watchdog {
+    cancel: std::sync::mpsc::Sender<()>,
+    thread: std::thread::JoinHandle<bool>,
+}
+
+#[cfg(target_family = "unix")]
+impl Watchdog {
+    // Each target is given to kill(2), a negative one being a process group
+    pub fn start(targets: Vec<libc::pid_t>, limit: Limit) -> Watchdog {
+        use std::sync::mpsc::{self, RecvTimeoutError};
+
+        let (cancel, cancelled) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            if cancelled.recv_timeout(limit.duration) != Err(RecvTimeoutError::Timeout) {
+                return false
+            }
+            // Stopped processes only see the signal once they are continued
+            for target in targets.iter() {
+                unsafe {
+                    libc::kill(*target, limit.signal);
+                    libc::kill(*target, libc::SIGCONT);
+                }
+            }
+            // A grace period of 0 never kills it, as with GNU timeout's -k 0
+            if !limit.grace.is_zero() && cancelled.recv_timeout(limit.grace) == Err(RecvTimeoutError::Timeout) {
+                for target in targets.iter() {
+                    unsafe { libc::kill(*target, libc::SIGKILL) };
+                }
+            }
+            true
+        });
+        Watchdog { cancel, thread }
+    }
+
+    // Stops watching once the pipeline has finished, returning whether it
+    // ran out of time
+    pub fn finish(self) -> bool {
+        let _ = self.cancel.send(());
+        self.thread.join().unwrap_or(false)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration()
+    {
+        assert_eq!(parse_duration("10"), Some(Duration::from_secs(10)));
+        assert_eq!(parse_duration("0"), Some(Duration::ZERO));
+        assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration("-1"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_parse_timeout_arguments()
+    {
+        let arguments: Vec<String> = ["-k", "1", "30s", "make"].iter().map(|s| s.to_string()).collect();
+        let limit = Limit { duration: Duration::from_secs(30), grace: Duration::from_secs(1), signal: libc::SIGTERM };
+        assert_eq!(parse_timeout_arguments(&arguments), Some((limit, 3)));
+        assert_eq!(parse_timeout_arguments(&arguments[3..]), None);
+
+        let arguments: Vec<String> = ["-sINT", "--kill-after=2", "--", "1m", "make"].iter().map(|s| s.to_string()).collect();
+        let limit = Limit { duration: Duration::from_secs(60), grace: Duration::from_secs(2), signal: libc::SIGINT };
+        assert_eq!(parse_timeout_arguments(&arguments), Some((limit, 4)));
+        let arguments: Vec<String> = ["--signal", "KILL", "5", "make"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(parse_timeout_arguments(&arguments).map(|(limit, skip)| (limit.signal, skip)), Some((libc::SIGKILL, 3)));
+        // Options it doesn't know are left to GNU timeout
+        let arguments: Vec<String> = ["--foreground", "5", "make"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(parse_timeout_arguments(&arguments), None);
+    }
+}
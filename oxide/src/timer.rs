@@ -0,0 +1,193 @@
+// Resource usage for the `time` reserved word. Processes reaped while a timed
+// pipeline runs add their usage to every timer that is running, so nested
+// timers each see the whole of what ran inside them.
+use std::sync::Mutex;
+use std::time::Duration;
+
+// The report used when TIMEFORMAT is unset
+pub const DEFAULT_FORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS\nmaxrss\t%M KB";
+// The report for `time -p`
+pub const POSIX_FORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Usage {
+    pub user: Duration,
+    pub system: Duration,
+    // Largest resident set size of any one process, in kilobytes
+    pub max_rss: i64,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        self.user += other.user;
+        self.system += other.system;
+        self.max_rss = self.max_rss.max(other.max_rss);
+    }
+}
+
+struct Timer {
+    // The shell's own usage when the timer started, for builtins
+    shell_start: Usage,
+    children: Usage,
+}
+
+lazy_static! {
+    static ref TIMERS: Mutex<Vec<Timer>> = Mutex::new(Vec::new());
+}
+
+#[cfg(target_family = "unix")]
+pub fn usage_from_rusage(rusage: &libc::rusage) -> Usage
+{
+    let duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    Usage {
+        user: duration(rusage.ru_utime),
+        system: duration(rusage.ru_stime),
+        // c_long is only 32 bits on some targets
+        #[allow(clippy::unnecessary_cast)]
+        max_rss: rusage.ru_maxrss as i64,
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn shell_usage() -> Usage
+{
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut rusage) };
+    usage_from_rusage(&rusage)
+}
+
+#[cfg(target_family = "windows")]
+fn shell_usage() -> Usage
+{
+    Usage::default()
+}
+
+pub fn start_timer()
+{
+    TIMERS.lock().unwrap().push(Timer { shell_start: shell_usage(), children: Usage::default() });
+}
+
+// Counts a reaped child towards every running timer
+pub fn add_child_usage(usage: &Usage)
+{
+    for timer in TIMERS.lock().unwrap().iter_mut()
+    {
+        timer.children.add(usage);
+    }
+}
+
+// Stops the innermost timer, returning the usage of the shell and every
+// child reaped since it started
+pub fn stop_timer() -> Usage
+{
+    let timer = match TIMERS.lock().unwrap().pop() {
+        Some(timer) => timer,
+        None => return Usage::default(),
+    };
+    let shell_end = shell_usage();
+    let mut usage = timer.children;
+    usage.user += shell_end.user.saturating_sub(timer.shell_start.user);
+    usage.system += shell_end.system.saturating_sub(timer.shell_start.system);
+    // With no processes run, only the shell's own peak is known
+    if usage.max_rss == 0
+    {
+        usage.max_rss = shell_end.max_rss;
+    }
+    usage
+}
+
+// Formats a report as TIMEFORMAT describes it. %R, %U and %S are the real,
+// user and system times, optionally preceded by a precision from 0 to 3 and
+// `l` for minutes and seconds. %P is the CPU percentage and %M the peak RSS.
+pub fn format_times(format: &str, real: Duration, usage: &Usage) -> String
+{
+    let mut report = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next()
+    {
+        if c != '%'
+        {
+            report.push(c);
+            continue
+        }
+
+        let mut precision = 3;
+        if let Some(digit) = chars.peek().and_then(|c| c.to_digit(10))
+        {
+            precision = digit.min(3) as usize;
+            chars.next();
+        }
+        let long = chars.peek() == Some(&'l');
+        if long
+        {
+            chars.next();
+        }
+        let time = match chars.next() {
+            Some('R') => real,
+            Some('U') => usage.user,
+            Some('S') => usage.system,
+            Some('P') => {
+                let cpu = (usage.user + usage.system).as_secs_f64();
+                let real = real.as_secs_f64();
+                let percent = if real > 0.0 { cpu * 100.0 / real } else { 0.0 };
+                report.push_str(&format!("{:.2}", percent));
+                continue
+            }
+            Some('M') => {
+                report.push_str(&usage.max_rss.to_string());
+                continue
+            }
+            Some('%') => {
+                report.push('%');
+                continue
+            }
+            Some(other) => {
+                report.push('%');
+                report.push(other);
+                continue
+            }
+            None => {
+                report.push('%');
+                break
+            }
+        };
+        report.push_str(&format_duration(time, precision, long));
+    }
+    report
+}
+
+fn format_duration(time: Duration, precision: usize, long: bool) -> String
+{
+    let seconds = time.as_secs_f64();
+    if long
+    {
+        let minutes = (seconds / 60.0).floor();
+        format!("{}m{:.*}s", minutes, precision, seconds - minutes * 60.0)
+    }
+    else
+    {
+        format!("{:.*}", precision, seconds)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_times()
+    {
+        let usage = Usage {
+            user: Duration::from_millis(1500),
+            system: Duration::from_millis(250),
+            max_rss: 2048,
+        };
+        let real = Duration::from_millis(63_500);
+        assert_eq!(format_times("%3lR %U %1S", real, &usage), "1m3.500s 1.500 0.2");
+        assert_eq!(format_times(POSIX_FORMAT, real, &usage), "real 63.50\nuser 1.50\nsys 0.25");
+        assert_eq!(format_times("%0R %M KB 100%%", real, &usage), "64 2048 KB 100%");
+    }
+}
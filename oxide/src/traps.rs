@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(target_family = "unix")]
+use crate::signals;
+use crate::status::signal_name;
+
+// What a trap can be set on: a signal, or one of the pseudo-signals the shell
+// raises itself. Ordered the way `trap -p` lists them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrapCondition {
+    // The shell exiting
+    Exit,
+    Signal(i32),
+    // Before each simple command
+    Debug,
+    // A simple command failing outside of a condition
+    Err,
+    // A function returning
+    Return,
+}
+
+lazy_static! {
+    // Command string for each trap. An empty one ignores the condition.
+    static ref TRAPS: Mutex<BTreeMap<TrapCondition, String>> = Mutex::new(BTreeMap::new());
+}
+
+// Set while a trap runs, so it doesn't set off the DEBUG, ERR or RETURN traps
+static RUNNING_TRAP: AtomicBool = AtomicBool::new(false);
+
+// Set in a forked copy of the shell, whose traps other than ignored signals
+// are dropped the next time the table is used
+static IN_SUBSHELL: AtomicBool = AtomicBool::new(false);
+
+fn trap_table() -> MutexGuard<'static, BTreeMap<TrapCondition, String>>
+{
+    let mut traps = TRAPS.lock().unwrap();
+    if IN_SUBSHELL.swap(false, Ordering::SeqCst) {
+        traps.retain(|_, action| action.is_empty());
+    }
+    traps
+}
+
+// Parses a condition such as EXIT, 0, INT, SIGINT or 2
+pub fn parse_condition(spec: &str) -> Option<TrapCondition>
+{
+    let spec = spec.to_ascii_uppercase();
+    match spec.as_str() {
+        "0" | "EXIT" => return Some(TrapCondition::Exit),
+        "DEBUG" => return Some(TrapCondition::Debug),
+        "ERR" => return Some(TrapCondition::Err),
+        "RETURN" => return Some(TrapCondition::Return),
+        _ => (),
+    }
+
+    if let Ok(signal) = spec.parse::<i32>() {
+        return signal_name(128 + signal).map(|_| TrapCondition::Signal(signal))
+    }
+    let name = if spec.starts_with("SIG") { spec } else { format!("SIG{}", spec) };
+    signal_numbers()
+        .into_iter()
+        .find(|signal| signal_name(128 + signal) == Some(name.as_str()))
+        .map(TrapCondition::Signal)
+}
+
+pub fn condition_name(condition: TrapCondition) -> String
+{
+    match condition {
+        TrapCondition::Exit => String::from("EXIT"),
+        TrapCondition::Signal(signal) => match signal_name(128 + signal) {
+            Some(name) => name.to_string(),
+            None => signal.to_string(),
+        },
+        TrapCondition::Debug => String::from("DEBUG"),
+        TrapCondition::Err => String::from("ERR"),
+        TrapCondition::Return => String::from("RETURN"),
+    }
+}
+
+// Every signal the shell knows by name
+pub fn signal_numbers() -> Vec<i32>
+{
+    (1..65).filter(|signal| signal_name(128 + signal).is_some()).collect()
+}
+
+pub fn set_trap(condition: TrapCondition, action: &str) -> Result<(), String>
+{
+    #[cfg(target_family = "unix")]
+    if let TrapCondition::Signal(signal) = condition {
+        if action.is_empty() {
+            signals::ignore_trapped_signal(signal)?;
+        } else {
+            signals::trap_signal(signal)?;
+        }
+    }
+    trap_table().insert(condition, action.to_string());
+    Ok(())
+}
+
+pub fn reset_trap(condition: TrapCondition)
+{
+    #[cfg(target_family = "unix")]
+    if let TrapCondition::Signal(signal) = condition {
+        signals::restore_signal(signal);
+    }
+    trap_table().remove(&condition);
+}
+
+// The command to run for a condition, if it is trapped and not ignored
+pub fn trap_action(condition: TrapCondition) -> Option<String>
+{
+    trap_table().get(&condition).filter(|action| !action.is_empty()).cloned()
+}
+
+// Removes the EXIT trap so it only ever runs once
+pub fn take_exit_trap() -> Option<String>
+{
+    trap_table().remove(&TrapCondition::Exit).filter(|action| !action.is_empty())
+}
+
+pub fn traps() -> Vec<(TrapCondition, String)>
+{
+    trap_table().iter().map(|(condition, action)| (*condition, action.clone())).collect()
+}
+
+// Signals with a trap command, read before forking a copy of the shell
+#[cfg(target_family = "unix")]
+pub fn trapped_signals() -> Vec<i32>
+{
+    trap_table().iter()
+        .filter_map(|(condition, action)| match condition {
+            TrapCondition::Signal(signal) if !action.is_empty() => Some(*signal),
+            _ => None,
+        })
+        .collect()
+}
+
+// A forked copy of the shell keeps ignored signals but drops every other
+// trap. Runs between fork and anything else, so it takes what it needs from
+// the parent and leaves the table to be cleared later.
+#[cfg(target_family = "unix")]
+pub fn leave_traps_for_subshell(trapped: &[i32], interactive: bool)
+{
+    for signal in trapped
+    {
+        signals::restore_disposition(*signal, interactive);
+    }
+    IN_SUBSHELL.store(true, Ordering::SeqCst);
+}
+
+pub fn running_trap() -> bool
+{
+    RUNNING_TRAP.load(Ordering::SeqCst)
+}
+
+// Returns whether a trap was already running, to be restored afterwards
+pub fn set_running_trap(running: bool) -> bool
+{
+    RUNNING_TRAP.swap(running, Ordering::SeqCst)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_condition()
+    {
+        assert_eq!(parse_condition("EXIT"), Some(TrapCondition::Exit));
+        assert_eq!(parse_condition("0"), Some(TrapCondition::Exit));
+        assert_eq!(parse_condition("err"), Some(TrapCondition::Err));
+        assert_eq!(parse_condition("NOPE"), None);
+        #[cfg(target_family = "unix")]
+        {
+            assert_eq!(parse_condition("INT"), Some(TrapCondition::Signal(libc::SIGINT)));
+            assert_eq!(parse_condition("SIGTERM"), Some(TrapCondition::Signal(libc::SIGTERM)));
+            assert_eq!(parse_condition("15"), Some(TrapCondition::Signal(15)));
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::sync::Mutex;
+
+use crate::encoding::{from_os_str, to_os_string};
+
+lazy_static! {
+    // Shell variables that have not been exported to the environment
+    static ref VARIABLES: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+
+    // $0 followed by the positional parameters $1, $2, ...
+    static ref POSITIONAL: Mutex<Vec<String>> = Mutex::new(vec!(String::from("oxide")));
+
+    // Process id of the most recent background job, $!
+    static ref LAST_BACKGROUND_PID: Mutex<Option<u32>> = Mutex::new(None);
+
+    // Variables made read-only with the readonly builtin
+    static ref READONLY: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+    // For each function being run, the variables it made local, with the
+    // values to put back when it returns
+    static ref LOCAL_SCOPES: Mutex<Vec<Vec<SavedVariable>>> = Mutex::new(Vec::new());
+}
+
+struct SavedVariable {
+    name: String,
+    value: Option<String>,
+    exported: bool,
+}
+
+pub fn is_valid_name(name: &str) -> bool
+{
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Looks up a shell variable, falling back to the environment
+pub fn get_variable(name: &str) -> Option<String>
+{
+    if let Some(value) = VARIABLES.lock().unwrap().get(name) {
+        return Some(value.clone())
+    }
+    env::var_os(name).map(|value| from_os_str(&value))
+}
+
+fn check_writable(name: &str) -> Result<(), String>
+{
+    if READONLY.lock().unwrap().contains(name) {
+        return Err(format!("{}: readonly variable", name))
+    }
+    Ok(())
+}
+
+// Sets a variable, updating the environment if it has already been exported
+pub fn set_variable(name: &str, value: &str) -> Result<(), String>
+{
+    check_writable(name)?;
+    if env::var_os(name).is_some() {
+        env::set_var(name, to_os_string(value));
+    } else {
+        VARIABLES.lock().unwrap().insert(name.to_string(), value.to_string());
+    }
+    Ok(())
+}
+
+pub fn export_variable(name: &str, value: Option<&str>) -> Result<(), String>
+{
+    if value.is_some() {
+        check_writable(name)?;
+    }
+    let shell_value = VARIABLES.lock().unwrap().remove(name);
+    match value.map(String::from).or(shell_value) {
+        Some(value) => env::set_var(name, to_os_string(&value)),
+        None => {
+            if env::var_os(name).is_none() {
+                env::set_var(name, "");
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn unset_variable(name: &str) -> Result<(), String>
+{
+    check_writable(name)?;
+    VARIABLES.lock().unwrap().remove(name);
+    env::remove_var(name);
+    Ok(())
+}
+
+pub fn mark_readonly(name: &str)
+{
+    READONLY.lock().unwrap().insert(name.to_string());
+}
+
+// Read-only variables, sorted by name
+pub fn readonly_variables() -> Vec<String>
+{
+    READONLY.lock().unwrap().iter().cloned().collect()
+}
+
+// Started as a function is called, so the variables it makes local get their
+// values back when it returns
+pub fn push_local_scope()
+{
+    LOCAL_SCOPES.lock().unwrap().push(Vec::new());
+}
+
+pub fn pop_local_scope()
+{
+    let saved = LOCAL_SCOPES.lock().unwrap().pop().unwrap_or_default();
+    for variable in saved.into_iter().rev()
+    {
+        VARIABLES.lock().unwrap().remove(&variable.name);
+        env::remove_var(&variable.name);
+        match variable.value {
+            Some(value) if variable.exported => env::set_var(&variable.name, to_os_string(&value)),
+            Some(value) => {
+                VARIABLES.lock().unwrap().insert(variable.name, value);
+            }
+            None => (),
+        }
+    }
+}
+
+// Makes a variable local to the function being run. It keeps its value
+// until it is set, as in dash.
+pub fn make_local(name: &str) -> Result<(), String>
+{
+    check_writable(name)?;
+    let mut scopes = LOCAL_SCOPES.lock().unwrap();
+    let scope = match scopes.last_mut() {
+        Some(scope) => scope,
+        None => return Err(String::from("can only be used in a function")),
+    };
+    if !scope.iter().any(|variable| variable.name == name) {
+        scope.push(SavedVariable {
+            name: name.to_string(),
+            value: get_variable(name),
+            exported: env::var_os(name).is_some(),
+        });
+    }
+    Ok(())
+}
+
+// Shell variables that are not exported, sorted by name
+pub fn shell_variables() -> Vec<(String, String)>
+{
+    let mut variables: Vec<(String, String)> = VARIABLES.lock().unwrap()
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    variables.sort();
+    variables
+}
+
+pub fn last_background_pid() -> Option<u32>
+{
+    *LAST_BACKGROUND_PID.lock().unwrap()
+}
+
+pub fn set_last_background_pid(pid: u32)
+{
+    *LAST_BACKGROUND_PID.lock().unwrap() = Some(pid);
+}
+
+// Positional parameter $n, where $0 is the shell or script name
+pub fn positional_parameter(index: usize) -> Option<String>
+{
+    POSITIONAL.lock().unwrap().get(index).cloned()
+}
+
+// The positional parameters $1 onwards
+pub fn positional_parameters() -> Vec<String>
+{
+    POSITIONAL.lock().unwrap()[1..].to_vec()
+}
+
+pub fn set_script_name(name: &str)
+{
+    POSITIONAL.lock().unwrap()[0] = name.to_string();
+}
+
+// Replaces $1 onwards, returning the previous parameters so they can be restored
+pub fn set_positional_parameters(parameters: Vec<String>) -> Vec<String>
+{
+    let mut positional = POSITIONAL.lock().unwrap();
+    let previous = positional.split_off(1);
+    positional.extend(parameters);
+    previous
+}
@@ -0,0 +1,219 @@
+// Runs the built shell on command strings, for behaviour that touches
+// process-wide state such as the working directory or file descriptors
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn oxide(command: &str) -> Output
+{
+    Command::new(env!("CARGO_BIN_EXE_oxide"))
+        .arg("-c")
+        .arg(command)
+        .output()
+        .unwrap()
+}
+
+fn oxide_posix(command: &str) -> Output
+{
+    Command::new(env!("CARGO_BIN_EXE_oxide"))
+        .args(["--posix", "-c", command])
+        .output()
+        .unwrap()
+}
+
+// An empty folder for a test to work in
+fn scratch_dir(name: &str) -> PathBuf
+{
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn stdout(output: &Output) -> String
+{
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_redirection_without_target()
+{
+    let output = oxide("echo hi >");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout(&output).contains("unexpected token"));
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn test_numbered_streams()
+{
+    let output = oxide("sh -c 'echo three >&3' 3>&1; exec 4>&1; sh -c 'echo four >&4'; exec 4>&-; echo five >&4");
+    assert_eq!(stdout(&output), "three\nfour\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn test_builtin_in_pipeline_keeps_shell_state()
+{
+    let output = oxide("cd / | cat; pwd");
+    assert_eq!(stdout(&output), format!("{}\n", std::env::current_dir().unwrap().display()));
+}
+
+#[test]
+fn test_type_f_skips_functions()
+{
+    // A function shadowing a builtin
+    let output = oxide("shift() { true; }; type -f shift");
+    assert_eq!(stdout(&output), "shift is a shell builtin\n");
+}
+
+#[test]
+fn test_posix_redirection_takes_one_target()
+{
+    let dir = scratch_dir("one-target");
+    let output = oxide_posix(&format!("cd {}; echo hi > out.txt there; cat out.txt", dir.display()));
+    assert_eq!(stdout(&output), "hi there\n");
+    assert!(!dir.join("there").exists());
+}
+
+#[test]
+fn test_redirections_before_the_command()
+{
+    let dir = scratch_dir("leading-redirections");
+    let output = oxide(&format!("cd {}; > out.txt echo hi; < out.txt cat; > empty.txt", dir.display()));
+    assert_eq!(stdout(&output), "hi\n");
+    assert_eq!(fs::read_to_string(dir.join("empty.txt")).unwrap(), "");
+}
+
+#[test]
+fn test_subshell_keeps_shell_state()
+{
+    let output = oxide("x=1; (x=2; cd /; exit 3); echo $? $x; pwd");
+    assert_eq!(stdout(&output), format!("3 1\n{}\n", std::env::current_dir().unwrap().display()));
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn test_compound_commands_in_pipelines()
+{
+    let output = oxide("printf 'a\\nb\\n' | while read x; do echo got $x; done | cat; echo c | { read y; echo $y; }");
+    assert_eq!(stdout(&output), "got a\ngot b\nc\n");
+}
+
+#[test]
+fn test_redirected_compound_commands()
+{
+    let dir = scratch_dir("redirected-compound");
+    let script = "printf 'a\\nb\\n' > in.txt; while read x; do echo got $x; done < in.txt; \
+        { echo one; echo two; } > out.txt; f() { echo in f; } >> out.txt; f; cat out.txt";
+    let output = oxide(&format!("cd {}; {}", dir.display(), script));
+    assert_eq!(stdout(&output), "got a\ngot b\none\ntwo\nin f\n");
+}
+
+#[test]
+fn test_command_substitution()
+{
+    let output = oxide("f() { echo fn; }; x=`echo b`; echo $(echo a) \"$(f; echo \")\")\" $x; y=$(exit 4); echo $? $(false) $?");
+    assert_eq!(stdout(&output), "a fn\n) b\n4 1\n");
+}
+
+#[test]
+fn test_arithmetic_expansion()
+{
+    // A failed expansion ends a script
+    let output = oxide("x=4; echo $((x * 2 + 1)) $(( (x += 1) > 4 ? x : 0 )) $x; echo $((1 / 0)); echo unreached");
+    assert_eq!(stdout(&output), "9 5 5\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_here_documents()
+{
+    let script = "x=world; cat <<EOF; cat <<-'END' | tr a-z A-Z\nhello $x \"$(echo sub)\" \\$x\nEOF\n\t$x\n\tEND\n\
+        while read line; do echo got $line; done <<EOF\none\nEOF\n";
+    let output = oxide(script);
+    assert_eq!(stdout(&output), "hello world \"sub\" $x\n$X\ngot one\n");
+}
+
+#[test]
+fn test_errexit_and_nounset()
+{
+    let output = oxide("set -e; if false; then :; fi; ! true; false || true; f() { false; echo unreached; }; f; echo unreached");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = oxide("set -u; echo ${x:-default} $#; echo $x; echo unreached");
+    assert_eq!(stdout(&output), "default 0\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = oxide("set -eu a b; echo $2; set +eu; false; echo $x done");
+    assert_eq!(stdout(&output), "b\ndone\n");
+}
+
+#[test]
+fn test_readonly_and_local()
+{
+    let output = oxide("readonly x=1; x=2; echo $x; readonly -p");
+    assert_eq!(stdout(&output), "1\nreadonly x='1'\n");
+
+    let output = oxide("f() { local y=in z; echo $y; y=changed; }; y=out; f; echo $y; local w");
+    assert_eq!(stdout(&output), "in\nout\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_dot_and_eval()
+{
+    let dir = scratch_dir("dot_and_eval");
+    fs::write(dir.join("lib.sh"), "greeting=\"hello $1\"\nreturn 3\necho unreached\n").unwrap();
+    let output = oxide(&format!(
+        "cd {}; . ./lib.sh world; echo $? $greeting; eval 'echo a; echo b' > out; cat out",
+        dir.display()
+    ));
+    assert_eq!(stdout(&output), "3 hello world\na\nb\n");
+}
+
+#[test]
+fn test_touch_options()
+{
+    let dir = scratch_dir("touch_options");
+    let output = oxide(&format!(
+        "cd {}; touch -c missing; touch -t 200001021530.45 old; touch -m -d @86400 day; touch -r old copy",
+        dir.display()
+    ));
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!dir.join("missing").exists());
+    let modified = |name: &str| fs::metadata(dir.join(name)).unwrap().modified().unwrap();
+    assert_eq!(modified("day"), std::time::UNIX_EPOCH + std::time::Duration::from_secs(86400));
+    assert_eq!(modified("copy"), modified("old"));
+
+    // Options it doesn't know aren't passed on to touch(1)
+    let output = oxide(&format!("cd {}; touch -x file", dir.display()));
+    assert_eq!(output.status.code(), Some(2));
+    assert!(!dir.join("file").exists());
+}
+
+#[test]
+fn test_list_separates_only_printed_folders()
+{
+    let dir = scratch_dir("list_separators");
+    fs::create_dir_all(dir.join("a")).unwrap();
+    fs::create_dir_all(dir.join("b")).unwrap();
+    fs::write(dir.join("a").join("x"), "").unwrap();
+    let output = oxide(&format!("cd {}; list missing a b", dir.display()));
+    assert_eq!(stdout(&output), "a:\nx\n\nb:\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_suggestions_without_a_prompt()
+{
+    // Only the prompt offers to run the suggestion, so a script isn't held up
+    let output = oxide("ecoh hi");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Did you mean echo?"));
+    assert!(!stderr.contains("instead?"));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(output.status.code(), Some(127));
+}